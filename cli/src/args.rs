@@ -0,0 +1,292 @@
+use std::{path::PathBuf, time::Duration};
+
+use thiserror::Error;
+
+pub const USAGE: &str = "\
+Usage: riscv-emulator [OPTIONS] <PROGRAM>
+
+Run RV32 ELF executable or raw binary image PROGRAM.
+Guest console(NS16550A UART) is connected to stdin/stdout.
+Exits with the code the guest passed to the syscon(SiFive test finisher).
+
+Options:
+  --memory <SIZE>            RAM size. K/M/G suffixes are accepted [default: 128M]
+  --load-address <ADDR>      RAM base address. Raw images are loaded here [default: 0x80000000]
+  --entry <ADDR>             Initial pc [default: ELF entry point or load address]
+  --max-instructions <N>     Stop after N instructions are retired
+  --max-cycles <N>           Stop after N cycles including traps and WFI
+  --timeout <SECONDS>        Stop after SECONDS of wall-clock time
+  --breakpoint <ADDR>        Stop before the instruction at ADDR is executed. Can be repeated
+  --trace                    Print cycle, pc and disassembled instruction to stderr before each cycle
+  --signature <FILE>         Write memory between begin_signature and end_signature to FILE after the run
+  --hardware-ad-update       Set page table entry A/D bits on access instead of raising page faults
+  --uart-base <ADDR>         UART base address [default: 0x10000000]
+  --no-uart                  Do not map UART
+  --no-syscon                Do not map syscon
+  --no-clint                 Do not map CLINT
+  -h, --help                 Print help
+
+Exit status:
+  guest exit code when the guest powers off or exits
+  124 when stopped by --max-instructions, --max-cycles, --timeout or --breakpoint
+  1 on emulator errors
+";
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ArgsError {
+    #[error("missing value for {flag}")]
+    MissingValue { flag: String },
+    #[error("invalid value '{value}' for {flag}")]
+    InvalidValue { flag: String, value: String },
+    #[error("unknown option {option}")]
+    UnknownOption { option: String },
+    #[error("unexpected argument {arg}")]
+    UnexpectedArgument { arg: String },
+    #[error("PROGRAM is required")]
+    MissingProgram,
+}
+
+/// Command line arguments.
+#[derive(Debug, PartialEq)]
+pub struct Args {
+    pub program: PathBuf,
+    pub memory_size: u32,
+    pub load_address: u32,
+    pub entry: Option<u32>,
+    pub max_instructions: Option<u64>,
+    pub max_cycles: Option<u64>,
+    pub timeout: Option<Duration>,
+    pub breakpoints: Vec<u32>,
+    pub trace: bool,
+    /// File to write the riscv-arch-test signature to.
+    pub signature: Option<PathBuf>,
+    pub hardware_ad_update: bool,
+    /// UART base address. None if UART is not mapped.
+    pub uart: Option<u32>,
+    pub syscon: bool,
+    pub clint: bool,
+    pub help: bool,
+}
+
+impl Args {
+    pub const DEFAULT_MEMORY_SIZE: u32 = 128 << 20;
+    pub const DEFAULT_LOAD_ADDRESS: u32 = 0x8000_0000;
+    pub const DEFAULT_UART_BASE: u32 = 0x1000_0000;
+
+    /// Parse arguments excluding the program name.
+    pub fn parse<I>(args: I) -> Result<Self, ArgsError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut program = None;
+        let mut parsed = Args {
+            program: PathBuf::new(),
+            memory_size: Self::DEFAULT_MEMORY_SIZE,
+            load_address: Self::DEFAULT_LOAD_ADDRESS,
+            entry: None,
+            max_instructions: None,
+            max_cycles: None,
+            timeout: None,
+            breakpoints: Vec::new(),
+            trace: false,
+            signature: None,
+            hardware_ad_update: false,
+            uart: Some(Self::DEFAULT_UART_BASE),
+            syscon: true,
+            clint: true,
+            help: false,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |flag: &str| {
+                args.next().ok_or_else(|| ArgsError::MissingValue {
+                    flag: flag.to_owned(),
+                })
+            };
+            match arg.as_str() {
+                "-h" | "--help" => parsed.help = true,
+                "--memory" => parsed.memory_size = parse_size(&arg, &value(&arg)?)?,
+                "--load-address" => parsed.load_address = parse_u32(&arg, &value(&arg)?)?,
+                "--entry" => parsed.entry = Some(parse_u32(&arg, &value(&arg)?)?),
+                "--max-instructions" => {
+                    let v = value(&arg)?;
+                    let n = v.parse().map_err(|_| invalid(&arg, &v))?;
+                    parsed.max_instructions = Some(n);
+                }
+                "--max-cycles" => {
+                    let v = value(&arg)?;
+                    let n = v.parse().map_err(|_| invalid(&arg, &v))?;
+                    parsed.max_cycles = Some(n);
+                }
+                "--timeout" => {
+                    let v = value(&arg)?;
+                    let secs = v
+                        .parse::<f64>()
+                        .ok()
+                        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                        .ok_or_else(|| invalid(&arg, &v))?;
+                    parsed.timeout = Some(secs);
+                }
+                "--breakpoint" => parsed.breakpoints.push(parse_u32(&arg, &value(&arg)?)?),
+                "--trace" => parsed.trace = true,
+                "--signature" => parsed.signature = Some(PathBuf::from(value(&arg)?)),
+                "--hardware-ad-update" => parsed.hardware_ad_update = true,
+                "--uart-base" => parsed.uart = Some(parse_u32(&arg, &value(&arg)?)?),
+                "--no-uart" => parsed.uart = None,
+                "--no-syscon" => parsed.syscon = false,
+                "--no-clint" => parsed.clint = false,
+                option if option.starts_with('-') && option != "-" => {
+                    return Err(ArgsError::UnknownOption {
+                        option: option.to_owned(),
+                    })
+                }
+                _ if program.is_none() => program = Some(PathBuf::from(&arg)),
+                _ => return Err(ArgsError::UnexpectedArgument { arg: arg.clone() }),
+            }
+        }
+
+        match program {
+            Some(program) => parsed.program = program,
+            None if parsed.help => (),
+            None => return Err(ArgsError::MissingProgram),
+        }
+        Ok(parsed)
+    }
+}
+
+fn invalid(flag: &str, value: &str) -> ArgsError {
+    ArgsError::InvalidValue {
+        flag: flag.to_owned(),
+        value: value.to_owned(),
+    }
+}
+
+/// Parse decimal or 0x prefixed hexadecimal number.
+fn parse_u32(flag: &str, value: &str) -> Result<u32, ArgsError> {
+    let v = value.replace('_', "");
+    match v.strip_prefix("0x").or_else(|| v.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => v.parse(),
+    }
+    .map_err(|_| invalid(flag, value))
+}
+
+/// Parse number with optional K/M/G suffix.
+fn parse_size(flag: &str, value: &str) -> Result<u32, ArgsError> {
+    let (num, shift) = match value.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&value[..i], 10),
+        Some((i, 'm' | 'M')) => (&value[..i], 20),
+        Some((i, 'g' | 'G')) => (&value[..i], 30),
+        _ => (value, 0),
+    };
+    let size = parse_u32(flag, num).map_err(|_| invalid(flag, value))?;
+    size.checked_mul(1 << shift)
+        .filter(|&size| size > 0)
+        .ok_or_else(|| invalid(flag, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, ArgsError> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn defaults() {
+        let args = parse(&["hello.elf"]).unwrap();
+        assert_eq!(args.program, PathBuf::from("hello.elf"));
+        assert_eq!(args.memory_size, Args::DEFAULT_MEMORY_SIZE);
+        assert_eq!(args.load_address, Args::DEFAULT_LOAD_ADDRESS);
+        assert_eq!(args.entry, None);
+        assert_eq!(args.uart, Some(Args::DEFAULT_UART_BASE));
+        assert!(args.syscon);
+        assert!(args.clint);
+        assert!(!args.trace);
+        assert!(!args.hardware_ad_update);
+    }
+
+    #[test]
+    fn options() {
+        let args = parse(&[
+            "--memory",
+            "64K",
+            "--load-address",
+            "0x1000",
+            "--entry",
+            "4100",
+            "--max-instructions",
+            "1000",
+            "--max-cycles",
+            "2000",
+            "--timeout",
+            "1.5",
+            "--breakpoint",
+            "0x1010",
+            "--breakpoint",
+            "0x1020",
+            "--trace",
+            "--signature",
+            "out.sig",
+            "--hardware-ad-update",
+            "--no-uart",
+            "--no-syscon",
+            "--no-clint",
+            "image.bin",
+        ])
+        .unwrap();
+        assert_eq!(args.memory_size, 64 << 10);
+        assert_eq!(args.load_address, 0x1000);
+        assert_eq!(args.entry, Some(0x1004));
+        assert_eq!(args.max_instructions, Some(1000));
+        assert_eq!(args.max_cycles, Some(2000));
+        assert_eq!(args.timeout, Some(Duration::from_millis(1500)));
+        assert_eq!(args.breakpoints, vec![0x1010, 0x1020]);
+        assert!(args.trace);
+        assert_eq!(args.signature, Some(PathBuf::from("out.sig")));
+        assert!(args.hardware_ad_update);
+        assert_eq!(args.uart, None);
+        assert!(!args.syscon);
+        assert!(!args.clint);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse(&[]), Err(ArgsError::MissingProgram));
+        assert!(parse(&["--help"]).unwrap().help);
+        assert_eq!(
+            parse(&["a.elf", "--entry"]),
+            Err(ArgsError::MissingValue {
+                flag: "--entry".into()
+            })
+        );
+        assert_eq!(
+            parse(&["--memory", "8G", "a.elf"]),
+            Err(ArgsError::InvalidValue {
+                flag: "--memory".into(),
+                value: "8G".into()
+            })
+        );
+        assert_eq!(
+            parse(&["--load-address", "0xzz", "a.elf"]),
+            Err(ArgsError::InvalidValue {
+                flag: "--load-address".into(),
+                value: "0xzz".into()
+            })
+        );
+        assert_eq!(
+            parse(&["--verbose", "a.elf"]),
+            Err(ArgsError::UnknownOption {
+                option: "--verbose".into()
+            })
+        );
+        assert_eq!(
+            parse(&["a.elf", "b.elf"]),
+            Err(ArgsError::UnexpectedArgument {
+                arg: "b.elf".into()
+            })
+        );
+    }
+}
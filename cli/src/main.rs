@@ -0,0 +1,246 @@
+mod args;
+mod signature;
+
+use std::{
+    io::{BufWriter, Read, Write},
+    path::PathBuf,
+    process::ExitCode,
+    sync::mpsc,
+    thread,
+};
+
+use thiserror::Error;
+
+use args::{Args, ArgsError, USAGE};
+use emulator::{
+    bus::{clint::Clint, device::Device, memory::Ram, syscon::Syscon, uart::Uart, Bus, BusError},
+    elf::{Elf, ElfError},
+    instructions::{disasm::Line, Decoder},
+    runtime::{RunOutcome, Runtime, RuntimeError, StopReason},
+};
+use signature::SignatureError;
+
+/// Exit status when the run loop is stopped before the guest exits.
+const STOPPED_EXIT_CODE: u8 = 124;
+
+#[derive(Error, Debug)]
+enum CliError {
+    #[error(transparent)]
+    Args(#[from] ArgsError),
+    #[error("failed to read {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to write {path}: {source}")]
+    Write {
+        path: PathBuf,
+        source: SignatureError,
+    },
+    #[error("invalid ELF: {0}")]
+    Elf(#[from] ElfError),
+    #[error("invalid memory map: {0}")]
+    Bus(#[from] BusError),
+    #[error("invalid memory map: {device} {source}; {hint}")]
+    DeviceOverlap {
+        device: &'static str,
+        hint: &'static str,
+        source: BusError,
+    },
+    #[error("symbol {name} is required by --signature but not found")]
+    MissingSymbol { name: &'static str },
+    #[error("image of {image} bytes does not fit in {memory} bytes of memory")]
+    ImageTooLarge { image: usize, memory: u32 },
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("riscv-emulator: {err}");
+            if matches!(err, CliError::Args(_)) {
+                eprintln!("\n{USAGE}");
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<ExitCode, CliError> {
+    let args = Args::parse(std::env::args().skip(1))?;
+    if args.help {
+        print!("{USAGE}");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let image = std::fs::read(&args.program).map_err(|source| CliError::Io {
+        path: args.program.clone(),
+        source,
+    })?;
+
+    let mut bus = Bus::empty();
+    let elf = if image.starts_with(b"\x7fELF") {
+        let elf = Elf::parse(&image)?;
+        bus.map(args.load_address, Ram::new(args.memory_size))?;
+        Some(elf)
+    } else {
+        if image.len() > args.memory_size as usize {
+            return Err(CliError::ImageTooLarge {
+                image: image.len(),
+                memory: args.memory_size,
+            });
+        }
+        let mut data = image;
+        data.resize(args.memory_size as usize, 0);
+        bus.map(args.load_address, Ram::with_data(data))?;
+        None
+    };
+
+    // RAM is mapped first so an overlap is reported against the device with the flag to resolve it.
+    if args.clint {
+        let hint = "disable it with --no-clint";
+        map_device(&mut bus, "CLINT", hint, Clint::BASE, Clint::new())?;
+    }
+    if args.syscon {
+        let hint = "disable it with --no-syscon";
+        map_device(&mut bus, "syscon", hint, Syscon::BASE, Syscon::new())?;
+    }
+    if let Some(base) = args.uart {
+        let hint = "move it with --uart-base or disable it with --no-uart";
+        let uart = Uart::new(Box::new(std::io::stdout())).with_input(stdin());
+        map_device(&mut bus, "UART", hint, base, uart)?;
+    }
+
+    let entry = match &elf {
+        Some(elf) => {
+            elf.load(&mut bus)?;
+            elf.entry()
+        }
+        None => args.load_address,
+    };
+
+    // Resolve the signature region before running so a missing symbol fails fast.
+    let signature = match &args.signature {
+        Some(_) => {
+            let symbol = |name| {
+                elf.as_ref()
+                    .and_then(|elf| elf.symbol(name))
+                    .map(|sym| sym.addr)
+                    .ok_or(CliError::MissingSymbol { name })
+            };
+            Some((symbol("begin_signature")?, symbol("end_signature")?))
+        }
+        None => None,
+    };
+
+    let mut runtime = Runtime::new().entry(args.entry.unwrap_or(entry));
+    if let Some(n) = args.max_instructions {
+        runtime = runtime.max_instructions(n);
+    }
+    if let Some(n) = args.max_cycles {
+        runtime = runtime.max_cycles(n);
+    }
+    if let Some(timeout) = args.timeout {
+        runtime = runtime.timeout(timeout);
+    }
+    // riscv-tests and other HTIF programs report the result through tohost.
+    if let Some(tohost) = elf.as_ref().and_then(|elf| elf.symbol("tohost")) {
+        runtime = runtime.tohost(tohost.addr);
+    }
+    if args.hardware_ad_update {
+        runtime = runtime.hardware_ad_update(true);
+    }
+    for pc in args.breakpoints {
+        runtime = runtime.breakpoint(pc);
+    }
+    if args.trace {
+        let mut out = BufWriter::new(std::io::stderr());
+        let decoder = Decoder::new();
+        runtime = runtime.trace(move |record| {
+            let ir = match record.ir {
+                Some(word) => Line {
+                    addr: record.pc,
+                    word,
+                    instruction: decoder.try_decode(word),
+                }
+                .to_string(),
+                None => format!("{:08x}: ????????", record.pc),
+            };
+            let symbol = elf
+                .as_ref()
+                .and_then(|elf| elf.symbolize(record.pc))
+                .map(|(sym, offset)| format!(" <{}+{offset:#x}>", sym.name))
+                .unwrap_or_default();
+            _ = writeln!(out, "{:>10} {ir}{symbol}", record.cycle);
+        });
+    }
+
+    let outcome = runtime.run(&mut bus)?;
+    if let (Some(path), Some((begin, end))) = (&args.signature, signature) {
+        let write = |path: &PathBuf| -> Result<(), SignatureError> {
+            let mut out = BufWriter::new(std::fs::File::create(path)?);
+            signature::dump(&bus, begin, end, &mut out)?;
+            Ok(out.flush()?)
+        };
+        write(path).map_err(|source| CliError::Write {
+            path: path.clone(),
+            source,
+        })?;
+    }
+    Ok(exit_code(&outcome))
+}
+
+/// Map device at base. An overlap is reported with the hint naming the flag to resolve it.
+fn map_device<D: Device + 'static>(
+    bus: &mut Bus,
+    name: &'static str,
+    hint: &'static str,
+    base: u32,
+    device: D,
+) -> Result<(), CliError> {
+    bus.map(base, device).map_err(|source| match source {
+        BusError::Overlap { .. } => CliError::DeviceOverlap {
+            device: name,
+            hint,
+            source,
+        },
+        source => CliError::Bus(source),
+    })
+}
+
+/// Translate outcome into the process exit status.
+fn exit_code(outcome: &RunOutcome) -> ExitCode {
+    match outcome.reason {
+        StopReason::PowerOff | StopReason::Exit => {
+            // Exit status is truncated to 8 bits by the OS. Keep non-zero codes failures.
+            let code = outcome.exit_code.unwrap_or_default();
+            match u8::try_from(code) {
+                Ok(code) => ExitCode::from(code),
+                Err(_) => ExitCode::FAILURE,
+            }
+        }
+        reason => {
+            eprintln!(
+                "riscv-emulator: stopped by {reason:?} after {} instructions",
+                outcome.stats.instructions_retired
+            );
+            ExitCode::from(STOPPED_EXIT_CODE)
+        }
+    }
+}
+
+/// Forward bytes read from stdin to the guest console.
+fn stdin() -> mpsc::Receiver<u8> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for b in std::io::stdin().lock().bytes() {
+            let Ok(b) = b else { break };
+            if tx.send(b).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
@@ -0,0 +1,58 @@
+use std::io::{self, Write};
+
+use thiserror::Error;
+
+use emulator::bus::interface::{BusRead, BusReadException};
+
+#[derive(Error, Debug)]
+pub enum SignatureError {
+    #[error("failed to read signature at {addr:#010x}: {exception}")]
+    Read {
+        addr: u32,
+        exception: BusReadException,
+    },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Write memory in [begin, end) as one 32-bit word per line in lowercase hex.
+/// This is the signature format riscv-arch-test compares against the reference model.
+pub fn dump<B: BusRead, W: Write>(
+    bus: &B,
+    begin: u32,
+    end: u32,
+    out: &mut W,
+) -> Result<(), SignatureError> {
+    for addr in (begin..end).step_by(4) {
+        let word = bus
+            .read32(addr)
+            .map_err(|exception| SignatureError::Read { addr, exception })?;
+        writeln!(out, "{word:08x}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emulator::bus::{interface::BusWrite, Bus};
+
+    #[test]
+    fn one_word_per_line() {
+        let mut bus = Bus::new(vec![0; 0x20]);
+        bus.write32(0x10, 0xdeadbeef).unwrap();
+        bus.write32(0x14, 0x1).unwrap();
+
+        let mut out = Vec::new();
+        dump(&bus, 0x10, 0x1c, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "deadbeef\n00000001\n00000000\n"
+        );
+
+        assert!(matches!(
+            dump(&bus, 0x1c, 0x24, &mut Vec::new()),
+            Err(SignatureError::Read { addr: 0x20, .. })
+        ));
+    }
+}
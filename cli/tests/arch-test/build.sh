@@ -0,0 +1,31 @@
+#!/bin/sh
+# Build the architectural tests from rv32i_m/<ext>/src/*.S into rv32i_m/<ext>/<test>.elf
+# with the riscof DUT plugin environment in riscof/riscv_emulator/env.
+#
+# Requires a C preprocessor, llvm-mc and ld.lld. Override them with CPP, LLVM_MC and LD,
+# e.g. LD="rust-lld -flavor gnu".
+#
+# Usage: ./build.sh
+
+set -eu
+
+cd "$(dirname "$0")"
+
+CPP=${CPP:-cpp}
+LLVM_MC=${LLVM_MC:-llvm-mc}
+LD=${LD:-ld.lld}
+MODEL_ENV=../../../riscof/riscv_emulator/env
+
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+
+for src in rv32i_m/*/src/*.S; do
+  dir=$(dirname "$(dirname "$src")")
+  name=$(basename "$src" .S)
+  out="$dir/$name.elf"
+  $CPP -P -nostdinc -undef -x assembler-with-cpp -DTEST_CASE_1=True -DXLEN=32 \
+    -I "$MODEL_ENV" -I env "$src" > "$tmp/$name.s"
+  $LLVM_MC -triple=riscv32 -mattr=+m,-relax -filetype=obj -o "$tmp/$name.o" "$tmp/$name.s"
+  $LD -m elf32lriscv -static -nostdlib -T "$MODEL_ENV/link.ld" -o "$out" "$tmp/$name.o"
+  echo "$out"
+done
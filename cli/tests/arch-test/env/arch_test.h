@@ -0,0 +1,73 @@
+// Subset of riscv-arch-test arch_test.h used by the checked-in tests.
+// Test cases store their results at the signature base register plus offset.
+// RVMODEL_* macros come from the DUT plugin in riscof/riscv_emulator/env/model_test.h.
+
+#ifndef _ARCH_TEST_H
+#define _ARCH_TEST_H
+
+#define RVTEST_ISA(_STR)
+#define RVTEST_CASE(_PNUM, _DSTR, ...)
+
+#define LI(reg, val)    li reg, val
+#define LA(reg, val)    la reg, val
+
+#define RVTEST_CODE_BEGIN                                               \
+  .section .text.init;                                                  \
+  .align 4;                                                             \
+  .global rvtest_init;                                                  \
+rvtest_init:                                                            \
+  .global rvtest_code_begin;                                            \
+rvtest_code_begin:
+
+#define RVTEST_CODE_END                                                 \
+  .global rvtest_code_end;                                              \
+rvtest_code_end:
+
+#define RVTEST_DATA_BEGIN                                               \
+  .data;                                                                \
+  .align 4;                                                             \
+  .global rvtest_data_begin;                                            \
+rvtest_data_begin:
+
+#define RVTEST_DATA_END                                                 \
+  .global rvtest_data_end;                                              \
+rvtest_data_end:
+
+#define RVTEST_SIGBASE(_R, _TAG)                                        \
+  LA(_R, _TAG)
+
+#define RVTEST_SIGUPD(_BR, _R, _OFF)                                    \
+  sw _R, _OFF(_BR)
+
+//-----------------------------------------------------------------------
+// Test case macros
+//-----------------------------------------------------------------------
+
+#define TEST_RR_OP(inst, destreg, reg1, reg2, correctval, val1, val2, swreg, offset, testreg) \
+  LI(reg1, val1);                                                       \
+  LI(reg2, val2);                                                       \
+  inst destreg, reg1, reg2;                                             \
+  RVTEST_SIGUPD(swreg, destreg, offset)
+
+#define TEST_IMM_OP(inst, destreg, reg, correctval, val, imm, swreg, offset, testreg) \
+  LI(reg, val);                                                         \
+  inst destreg, reg, SEXT_IMM(imm);                                     \
+  RVTEST_SIGUPD(swreg, destreg, offset)
+
+#define SEXT_IMM(x) ((x) | (-(((x) >> 11) & 1) << 11))
+
+// Load from rvtest_data + index * 4 + adj with rs1 pointing imm_val bytes before it.
+#define TEST_LOAD(swreg, testreg, index, rs1, destreg, imm_val, offset, inst, adj) \
+  LA(rs1, rvtest_data + (index * 4) + adj - imm_val);                   \
+  inst destreg, imm_val(rs1);                                           \
+  RVTEST_SIGUPD(swreg, destreg, offset)
+
+// Store straight into the signature word at swreg + offset + adj.
+#define TEST_STORE(swreg, testreg, index, rs1, rs2, rs2_val, imm_val, offset, inst, adj) \
+  LI(rs2, rs2_val);                                                     \
+  addi rs1, swreg, offset + adj;                                        \
+  LI(testreg, imm_val);                                                 \
+  sub rs1, rs1, testreg;                                                \
+  inst rs2, imm_val(rs1)
+
+#endif // _ARCH_TEST_H
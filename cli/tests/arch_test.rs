@@ -0,0 +1,68 @@
+//! Run the architectural tests checked in under `tests/arch-test/rv32i_m/<ext>/` with `--signature`
+//! and compare the signatures with `references/<test>.reference_output`.
+//! See `tests/arch-test/README.md` for how to build the fixtures.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+fn run_suite(ext: &str) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/arch-test/rv32i_m")
+        .join(ext);
+    let mut tests: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap_or_else(|err| panic!("{}: {err}", dir.display()))
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "elf"))
+        .collect();
+    tests.sort();
+    assert!(!tests.is_empty(), "no tests in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for elf in &tests {
+        let name = elf.file_stem().unwrap().to_string_lossy();
+        let signature = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.signature"));
+        let status = Command::new(env!("CARGO_BIN_EXE_riscv-emulator"))
+            .args(["--max-cycles", "100000", "--no-uart", "--signature"])
+            .arg(&signature)
+            .arg(elf)
+            .status()
+            .unwrap();
+        if !status.success() {
+            failures.push(format!("{name}: exited with {status}"));
+            continue;
+        }
+
+        let reference = dir
+            .join("references")
+            .join(format!("{name}.reference_output"));
+        let expected = fs::read_to_string(&reference)
+            .unwrap_or_else(|err| panic!("{}: {err}", reference.display()));
+        let actual = fs::read_to_string(&signature).unwrap();
+        if actual != expected {
+            failures.push(format!(
+                "{name}: signature differs from {}",
+                reference.display()
+            ));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "rv32i_m/{ext}: {} of {} tests failed\n{}",
+        failures.len(),
+        tests.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn rv32i_m_i() {
+    run_suite("I");
+}
+
+#[test]
+fn rv32i_m_m() {
+    run_suite("M");
+}
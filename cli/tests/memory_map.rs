@@ -0,0 +1,70 @@
+//! Check that devices overlapping RAM are reported with the flag that resolves the conflict.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+/// Write a raw image which loops forever. Each test uses its own file.
+fn program(name: &str) -> PathBuf {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.bin"));
+    // addi x0, x0, 0; jal x0, 0
+    fs::write(&path, [0x13, 0, 0, 0, 0x6f, 0, 0, 0]).unwrap();
+    path
+}
+
+fn run(name: &str, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_riscv-emulator"))
+        .args(["--max-instructions", "10"])
+        .args(args)
+        .arg(program(name))
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn overlapping_device_names_its_flag() {
+    let cases: [(&[&str], &str); 3] = [
+        (
+            &["--memory", "33M", "--load-address", "0"],
+            "disable it with --no-clint",
+        ),
+        (
+            &["--memory", "33M", "--load-address", "0", "--no-clint"],
+            "disable it with --no-syscon",
+        ),
+        (
+            &["--memory", "4K", "--load-address", "0x10000000"],
+            "--uart-base or disable it with --no-uart",
+        ),
+    ];
+    for (args, expected) in cases {
+        let output = run("overlap", args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(1), "{args:?}: {stderr}");
+        assert!(stderr.contains(expected), "{args:?}: {stderr}");
+    }
+}
+
+#[test]
+fn ram_at_zero_without_low_devices() {
+    let output = run(
+        "ram_at_zero",
+        &[
+            "--memory",
+            "33M",
+            "--load-address",
+            "0",
+            "--no-clint",
+            "--no-syscon",
+        ],
+    );
+    // Stopped by --max-instructions.
+    assert_eq!(
+        output.status.code(),
+        Some(124),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,127 @@
+use super::{
+    device::Device,
+    interface::{BusReadException, BusWriteException, Interrupts},
+};
+
+/// Core Local Interruptor
+/// Provides machine software and timer interrupts with the SiFive compatible register layout.
+#[derive(Debug)]
+pub struct Clint {
+    msip: u32,
+    mtimecmp: u64,
+    mtime: u64,
+}
+
+impl Clint {
+    /// Base address used by QEMU virt machine.
+    pub const BASE: u32 = 0x0200_0000;
+    pub const SIZE: u32 = 0x1_0000;
+
+    const MSIP: u32 = 0x0000;
+    const MTIMECMP: u32 = 0x4000;
+    const MTIMECMPH: u32 = 0x4004;
+    const MTIME: u32 = 0xbff8;
+    const MTIMEH: u32 = 0xbffc;
+
+    pub fn new() -> Self {
+        Self {
+            msip: 0,
+            // Avoid timer interrupts until software programs mtimecmp.
+            mtimecmp: u64::MAX,
+            mtime: 0,
+        }
+    }
+}
+
+impl Device for Clint {
+    fn size(&self) -> u32 {
+        Self::SIZE
+    }
+
+    fn read32(&self, offset: u32) -> Result<u32, BusReadException> {
+        let v = match offset {
+            Self::MSIP => self.msip,
+            Self::MTIMECMP => self.mtimecmp as u32,
+            Self::MTIMECMPH => (self.mtimecmp >> 32) as u32,
+            Self::MTIME => self.mtime as u32,
+            Self::MTIMEH => (self.mtime >> 32) as u32,
+            _ => return Err(BusReadException::LoadAccessFault),
+        };
+        Ok(v)
+    }
+
+    fn read64(&self, offset: u32) -> Result<u64, BusReadException> {
+        match offset {
+            Self::MTIMECMP => Ok(self.mtimecmp),
+            Self::MTIME => Ok(self.mtime),
+            _ => Err(BusReadException::LoadAccessFault),
+        }
+    }
+
+    fn write32(&mut self, offset: u32, v: u32) -> Result<(), BusWriteException> {
+        match offset {
+            Self::MSIP => self.msip = v & 1,
+            Self::MTIMECMP => self.mtimecmp = (self.mtimecmp & !0xffff_ffff) | v as u64,
+            Self::MTIMECMPH => self.mtimecmp = (self.mtimecmp & 0xffff_ffff) | ((v as u64) << 32),
+            Self::MTIME => self.mtime = (self.mtime & !0xffff_ffff) | v as u64,
+            Self::MTIMEH => self.mtime = (self.mtime & 0xffff_ffff) | ((v as u64) << 32),
+            _ => return Err(BusWriteException::StoreAccessFault),
+        }
+        Ok(())
+    }
+
+    fn write64(&mut self, offset: u32, v: u64) -> Result<(), BusWriteException> {
+        match offset {
+            Self::MTIMECMP => self.mtimecmp = v,
+            Self::MTIME => self.mtime = v,
+            _ => return Err(BusWriteException::StoreAccessFault),
+        }
+        Ok(())
+    }
+
+    /// Increment mtime.
+    fn tick(&mut self) {
+        self.mtime = self.mtime.wrapping_add(1);
+    }
+
+    fn interrupts(&self) -> Interrupts {
+        Interrupts {
+            msip: self.msip & 1 != 0,
+            mtip: self.mtime >= self.mtimecmp,
+            meip: false,
+        }
+    }
+
+    fn mtime(&self) -> Option<u64> {
+        Some(self.mtime)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timer_interrupt() {
+        let mut clint = Clint::new();
+        assert!(!clint.interrupts().mtip);
+        clint.write32(Clint::MTIMECMPH, 0).unwrap();
+        clint.write32(Clint::MTIMECMP, 2).unwrap();
+        clint.tick();
+        assert!(!clint.interrupts().mtip);
+        clint.tick();
+        assert!(clint.interrupts().mtip);
+        assert_eq!(clint.read32(Clint::MTIME).unwrap(), 2);
+        assert_eq!(clint.read32(Clint::MTIMEH).unwrap(), 0);
+    }
+
+    #[test]
+    fn software_interrupt() {
+        let mut clint = Clint::new();
+        clint.write32(Clint::MSIP, 0xffff_ffff).unwrap();
+        assert!(clint.interrupts().msip);
+        assert_eq!(clint.read32(Clint::MSIP).unwrap(), 1);
+        clint.write32(Clint::MSIP, 0).unwrap();
+        assert!(!clint.interrupts().msip);
+    }
+}
@@ -0,0 +1,56 @@
+use super::interface::{BusReadException, BusWriteException, HaltRequest, Interrupts};
+
+/// Memory mapped device which can be registered on the `Bus`.
+/// Addresses passed to the handlers are offsets from the base address the device is mapped at.
+/// Accesses are naturally aligned and within `size`, the bus checks them before dispatch.
+/// Access widths which are not overridden raise access faults.
+///
+/// Reads take `&self`. Devices whose reads have side effects should use interior mutability.
+pub trait Device {
+    /// Size of the address range occupied by the device in bytes.
+    fn size(&self) -> u32;
+
+    fn read8(&self, _offset: u32) -> Result<u8, BusReadException> {
+        Err(BusReadException::LoadAccessFault)
+    }
+    fn read16(&self, _offset: u32) -> Result<u16, BusReadException> {
+        Err(BusReadException::LoadAccessFault)
+    }
+    fn read32(&self, _offset: u32) -> Result<u32, BusReadException> {
+        Err(BusReadException::LoadAccessFault)
+    }
+    fn read64(&self, _offset: u32) -> Result<u64, BusReadException> {
+        Err(BusReadException::LoadAccessFault)
+    }
+
+    fn write8(&mut self, _offset: u32, _v: u8) -> Result<(), BusWriteException> {
+        Err(BusWriteException::StoreAccessFault)
+    }
+    fn write16(&mut self, _offset: u32, _v: u16) -> Result<(), BusWriteException> {
+        Err(BusWriteException::StoreAccessFault)
+    }
+    fn write32(&mut self, _offset: u32, _v: u32) -> Result<(), BusWriteException> {
+        Err(BusWriteException::StoreAccessFault)
+    }
+    fn write64(&mut self, _offset: u32, _v: u64) -> Result<(), BusWriteException> {
+        Err(BusWriteException::StoreAccessFault)
+    }
+
+    /// Advance device by one cycle.
+    fn tick(&mut self) {}
+
+    /// Return current levels of the interrupt lines driven by the device.
+    fn interrupts(&self) -> Interrupts {
+        Interrupts::default()
+    }
+
+    /// Return value of the real-time counter if the device provides it.
+    fn mtime(&self) -> Option<u64> {
+        None
+    }
+
+    /// Return request to stop the machine raised by the guest through the device.
+    fn halt_request(&self) -> Option<HaltRequest> {
+        None
+    }
+}
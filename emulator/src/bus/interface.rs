@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-#[derive(Error, Debug, Clone, Copy)]
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BusReadException {
     #[error("load address misaligned")]
     LoadAddressMisaligned,
@@ -12,9 +12,16 @@ pub trait BusRead {
     fn read8(&self, addr: u32) -> Result<u8, BusReadException>;
     fn read16(&self, addr: u32) -> Result<u16, BusReadException>;
     fn read32(&self, addr: u32) -> Result<u32, BusReadException>;
+
+    /// Read a doubleword as two words, the lower word first.
+    fn read64(&self, addr: u32) -> Result<u64, BusReadException> {
+        let lo = self.read32(addr)? as u64;
+        let hi = self.read32(addr.wrapping_add(4))? as u64;
+        Ok(hi << 32 | lo)
+    }
 }
 
-#[derive(Error, Debug, Clone, Copy)]
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BusWriteException {
     #[error("store address misaligned")]
     StoreAddressMisaligned,
@@ -26,4 +33,113 @@ pub trait BusWrite {
     fn write8(&mut self, addr: u32, v: u8) -> Result<(), BusWriteException>;
     fn write16(&mut self, addr: u32, v: u16) -> Result<(), BusWriteException>;
     fn write32(&mut self, addr: u32, v: u32) -> Result<(), BusWriteException>;
+
+    /// Write a doubleword as two words, the lower word first.
+    /// Buses where the upper word can fault after the lower word is written should override this
+    /// so that a failed access leaves memory unchanged.
+    fn write64(&mut self, addr: u32, v: u64) -> Result<(), BusWriteException> {
+        self.write32(addr, v as u32)?;
+        self.write32(addr.wrapping_add(4), (v >> 32) as u32)
+    }
+}
+
+/// Interrupt lines driven by devices on the bus.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Interrupts {
+    /// Machine software interrupt
+    pub msip: bool,
+    /// Machine timer interrupt
+    pub mtip: bool,
+    /// Machine external interrupt
+    pub meip: bool,
+}
+
+/// Request from the guest to stop the machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltRequest {
+    /// Power off successfully
+    PowerOff,
+    /// Exit with the code
+    Exit(u32),
+}
+
+/// Devices which let the guest stop the machine.
+pub trait PowerControl {
+    /// Return request raised by the guest if any.
+    fn halt_request(&self) -> Option<HaltRequest> {
+        None
+    }
+}
+
+/// Devices which advance with the cpu clock and raise interrupts.
+/// Buses without such devices can rely on the default implementation.
+pub trait InterruptSource {
+    /// Advance devices by one cycle.
+    fn tick(&mut self) {}
+
+    /// Return current levels of interrupt lines.
+    fn interrupts(&self) -> Interrupts {
+        Interrupts::default()
+    }
+
+    /// Return value of the real-time counter read through the time CSR. Zero without a timer.
+    fn mtime(&self) -> u64 {
+        0
+    }
+}
+
+impl<T: BusRead> BusRead for &mut T {
+    fn read8(&self, addr: u32) -> Result<u8, BusReadException> {
+        (**self).read8(addr)
+    }
+
+    fn read16(&self, addr: u32) -> Result<u16, BusReadException> {
+        (**self).read16(addr)
+    }
+
+    fn read32(&self, addr: u32) -> Result<u32, BusReadException> {
+        (**self).read32(addr)
+    }
+
+    fn read64(&self, addr: u32) -> Result<u64, BusReadException> {
+        (**self).read64(addr)
+    }
+}
+
+impl<T: BusWrite> BusWrite for &mut T {
+    fn write8(&mut self, addr: u32, v: u8) -> Result<(), BusWriteException> {
+        (**self).write8(addr, v)
+    }
+
+    fn write16(&mut self, addr: u32, v: u16) -> Result<(), BusWriteException> {
+        (**self).write16(addr, v)
+    }
+
+    fn write32(&mut self, addr: u32, v: u32) -> Result<(), BusWriteException> {
+        (**self).write32(addr, v)
+    }
+
+    fn write64(&mut self, addr: u32, v: u64) -> Result<(), BusWriteException> {
+        (**self).write64(addr, v)
+    }
+}
+
+impl<T: PowerControl> PowerControl for &mut T {
+    fn halt_request(&self) -> Option<HaltRequest> {
+        (**self).halt_request()
+    }
+}
+
+impl<T: InterruptSource> InterruptSource for &mut T {
+    fn tick(&mut self) {
+        (**self).tick()
+    }
+
+    fn interrupts(&self) -> Interrupts {
+        (**self).interrupts()
+    }
+
+    fn mtime(&self) -> u64 {
+        (**self).mtime()
+    }
 }
@@ -0,0 +1,100 @@
+use super::{
+    device::Device,
+    interface::{BusReadException, BusWriteException},
+};
+
+/// Readable and writable memory.
+#[derive(Debug)]
+pub struct Ram {
+    data: Vec<u8>,
+}
+
+impl Ram {
+    /// Construct zero filled `Ram` of size bytes.
+    pub fn new(size: u32) -> Self {
+        Self::with_data(vec![0; size as usize])
+    }
+
+    /// Construct `Ram` initialized with data.
+    pub fn with_data(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+}
+
+/// Read-only memory. Writes raise store access faults.
+#[derive(Debug)]
+pub struct Rom {
+    data: Vec<u8>,
+}
+
+impl Rom {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+}
+
+/// Read little-endian value of N bytes from data.
+fn read<const N: usize>(data: &[u8], offset: u32) -> [u8; N] {
+    let offset = offset as usize;
+    data[offset..offset + N].try_into().unwrap()
+}
+
+fn write<const N: usize>(data: &mut [u8], offset: u32, v: [u8; N]) {
+    let offset = offset as usize;
+    data[offset..offset + N].copy_from_slice(&v);
+}
+
+impl Device for Ram {
+    fn size(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    fn read8(&self, offset: u32) -> Result<u8, BusReadException> {
+        Ok(self.data[offset as usize])
+    }
+    fn read16(&self, offset: u32) -> Result<u16, BusReadException> {
+        Ok(u16::from_le_bytes(read(&self.data, offset)))
+    }
+    fn read32(&self, offset: u32) -> Result<u32, BusReadException> {
+        Ok(u32::from_le_bytes(read(&self.data, offset)))
+    }
+    fn read64(&self, offset: u32) -> Result<u64, BusReadException> {
+        Ok(u64::from_le_bytes(read(&self.data, offset)))
+    }
+
+    fn write8(&mut self, offset: u32, v: u8) -> Result<(), BusWriteException> {
+        self.data[offset as usize] = v;
+        Ok(())
+    }
+    fn write16(&mut self, offset: u32, v: u16) -> Result<(), BusWriteException> {
+        write(&mut self.data, offset, v.to_le_bytes());
+        Ok(())
+    }
+    fn write32(&mut self, offset: u32, v: u32) -> Result<(), BusWriteException> {
+        write(&mut self.data, offset, v.to_le_bytes());
+        Ok(())
+    }
+    fn write64(&mut self, offset: u32, v: u64) -> Result<(), BusWriteException> {
+        write(&mut self.data, offset, v.to_le_bytes());
+        Ok(())
+    }
+}
+
+impl Device for Rom {
+    fn size(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    fn read8(&self, offset: u32) -> Result<u8, BusReadException> {
+        Ok(self.data[offset as usize])
+    }
+    fn read16(&self, offset: u32) -> Result<u16, BusReadException> {
+        Ok(u16::from_le_bytes(read(&self.data, offset)))
+    }
+    fn read32(&self, offset: u32) -> Result<u32, BusReadException> {
+        Ok(u32::from_le_bytes(read(&self.data, offset)))
+    }
+    fn read64(&self, offset: u32) -> Result<u64, BusReadException> {
+        Ok(u64::from_le_bytes(read(&self.data, offset)))
+    }
+}
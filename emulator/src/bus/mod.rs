@@ -1,36 +1,429 @@
-pub(crate) mod interface;
+pub mod clint;
+pub mod device;
+pub mod interface;
+pub mod memory;
+pub mod syscon;
+pub mod uart;
 
-use interface::{BusRead, BusReadException};
+use thiserror::Error;
 
-pub(crate) struct Bus {
-    ram: Vec<u8>,
+use device::Device;
+use interface::{
+    BusRead, BusReadException, BusWrite, BusWriteException, HaltRequest, InterruptSource,
+    Interrupts, PowerControl,
+};
+use memory::{Ram, Rom};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum BusError {
+    #[error("region {base:#010x}+{size:#x} overlaps with region at {other:#010x}")]
+    Overlap { base: u32, size: u32, other: u32 },
+    #[error("region {base:#010x}+{size:#x} is empty or exceeds the address space")]
+    InvalidRegion { base: u32, size: u32 },
+}
+
+struct Region {
+    base: u32,
+    size: u32,
+    device: Box<dyn Device>,
+}
+
+impl Region {
+    /// Return end address(exclusive) of the region.
+    fn end(&self) -> u64 {
+        self.base as u64 + self.size as u64
+    }
+}
+
+/// Bus composes the physical address space from ram, rom and devices mapped at base/size ranges.
+/// Accesses to unmapped addresses raise access faults.
+pub struct Bus {
+    /// Mapped regions sorted by base address.
+    regions: Vec<Region>,
 }
 
 impl Bus {
-    pub(crate) fn new(ram: Vec<u8>) -> Self {
-        Self { ram }
+    /// Base address of DRAM used by QEMU virt machine.
+    pub const DRAM_BASE: u32 = 0x8000_0000;
+
+    /// Construct `Bus` with ram mapped at address 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if ram does not fit in the 32-bit address space.
+    pub fn new(ram: Vec<u8>) -> Self {
+        let mut bus = Self::empty();
+        if !ram.is_empty() {
+            bus.map(0, Ram::with_data(ram))
+                .expect("ram exceeds the address space");
+        }
+        bus
+    }
+
+    /// Construct `Bus` without any mapped regions.
+    pub fn empty() -> Self {
+        Self {
+            regions: Vec::new(),
+        }
+    }
+
+    /// Register device at base address. The region occupies `device.size()` bytes.
+    pub fn map<D: Device + 'static>(&mut self, base: u32, device: D) -> Result<(), BusError> {
+        let size = device.size();
+        if size == 0 || base as u64 + size as u64 > 1 << 32 {
+            return Err(BusError::InvalidRegion { base, size });
+        }
+        let region = Region {
+            base,
+            size,
+            device: Box::new(device),
+        };
+
+        let idx = self.regions.partition_point(|r| r.base < base);
+        let prev = idx.checked_sub(1).map(|i| &self.regions[i]);
+        let next = self.regions.get(idx);
+        if let Some(other) = prev
+            .filter(|prev| prev.end() > base as u64)
+            .or(next.filter(|next| (next.base as u64) < region.end()))
+        {
+            return Err(BusError::Overlap {
+                base,
+                size,
+                other: other.base,
+            });
+        }
+
+        self.regions.insert(idx, region);
+        Ok(())
+    }
+
+    /// Register zero filled ram of size bytes at base address.
+    pub fn map_ram(&mut self, base: u32, size: u32) -> Result<(), BusError> {
+        self.map(base, Ram::new(size))
+    }
+
+    /// Register read-only memory initialized with data at base address.
+    pub fn map_rom(&mut self, base: u32, data: Vec<u8>) -> Result<(), BusError> {
+        self.map(base, Rom::new(data))
+    }
+
+    /// Return region containing [addr, addr + len) and offset of addr in it.
+    fn find(&self, addr: u32, len: u32) -> Option<(&Region, u32)> {
+        let idx = self.regions.partition_point(|r| r.base <= addr);
+        let region = &self.regions[idx.checked_sub(1)?];
+        let offset = addr - region.base;
+        (offset as u64 + len as u64 <= region.size as u64).then_some((region, offset))
+    }
+
+    fn find_mut(&mut self, addr: u32, len: u32) -> Option<(&mut Region, u32)> {
+        let idx = self.regions.partition_point(|r| r.base <= addr);
+        let region = &mut self.regions[idx.checked_sub(1)?];
+        let offset = addr - region.base;
+        (offset as u64 + len as u64 <= region.size as u64).then_some((region, offset))
     }
 }
 
-// TODO: alignment check, address check
 impl BusRead for Bus {
-    fn read8(&self, _addr: u32) -> Result<u8, BusReadException> {
-        todo!()
+    fn read8(&self, addr: u32) -> Result<u8, BusReadException> {
+        let (region, offset) = self
+            .find(addr, 1)
+            .ok_or(BusReadException::LoadAccessFault)?;
+        region.device.read8(offset)
     }
-    fn read16(&self, _addr: u32) -> Result<u16, BusReadException> {
-        todo!()
+    fn read16(&self, addr: u32) -> Result<u16, BusReadException> {
+        if addr & 1 != 0 {
+            return Err(BusReadException::LoadAddressMisaligned);
+        }
+        let (region, offset) = self
+            .find(addr, 2)
+            .ok_or(BusReadException::LoadAccessFault)?;
+        region.device.read16(offset)
     }
     fn read32(&self, addr: u32) -> Result<u32, BusReadException> {
         if addr & 3 != 0 {
+            return Err(BusReadException::LoadAddressMisaligned);
+        }
+        let (region, offset) = self
+            .find(addr, 4)
+            .ok_or(BusReadException::LoadAccessFault)?;
+        region.device.read32(offset)
+    }
+    fn read64(&self, addr: u32) -> Result<u64, BusReadException> {
+        if addr & 7 != 0 {
+            return Err(BusReadException::LoadAddressMisaligned);
+        }
+        let (region, offset) = self
+            .find(addr, 8)
+            .ok_or(BusReadException::LoadAccessFault)?;
+        region.device.read64(offset)
+    }
+}
+
+impl BusWrite for Bus {
+    fn write8(&mut self, addr: u32, v: u8) -> Result<(), BusWriteException> {
+        let (region, offset) = self
+            .find_mut(addr, 1)
+            .ok_or(BusWriteException::StoreAccessFault)?;
+        region.device.write8(offset, v)
+    }
+    fn write16(&mut self, addr: u32, v: u16) -> Result<(), BusWriteException> {
+        if addr & 1 != 0 {
+            return Err(BusWriteException::StoreAddressMisaligned);
+        }
+        let (region, offset) = self
+            .find_mut(addr, 2)
+            .ok_or(BusWriteException::StoreAccessFault)?;
+        region.device.write16(offset, v)
+    }
+    fn write32(&mut self, addr: u32, v: u32) -> Result<(), BusWriteException> {
+        if addr & 3 != 0 {
+            return Err(BusWriteException::StoreAddressMisaligned);
+        }
+        let (region, offset) = self
+            .find_mut(addr, 4)
+            .ok_or(BusWriteException::StoreAccessFault)?;
+        region.device.write32(offset, v)
+    }
+    /// The doubleword is written by a single device access, so a fault leaves memory unchanged.
+    fn write64(&mut self, addr: u32, v: u64) -> Result<(), BusWriteException> {
+        if addr & 7 != 0 {
+            return Err(BusWriteException::StoreAddressMisaligned);
+        }
+        let (region, offset) = self
+            .find_mut(addr, 8)
+            .ok_or(BusWriteException::StoreAccessFault)?;
+        region.device.write64(offset, v)
+    }
+}
+
+impl InterruptSource for Bus {
+    fn tick(&mut self) {
+        for region in &mut self.regions {
+            region.device.tick();
+        }
+    }
+
+    /// Interrupt lines of all devices are OR-ed.
+    fn interrupts(&self) -> Interrupts {
+        self.regions
+            .iter()
+            .map(|region| region.device.interrupts())
+            .fold(Interrupts::default(), |acc, irq| Interrupts {
+                msip: acc.msip || irq.msip,
+                mtip: acc.mtip || irq.mtip,
+                meip: acc.meip || irq.meip,
+            })
+    }
+
+    /// Value of the lowest addressed timer.
+    fn mtime(&self) -> u64 {
+        self.regions
+            .iter()
+            .find_map(|region| region.device.mtime())
+            .unwrap_or(0)
+    }
+}
+
+impl PowerControl for Bus {
+    /// Return the request of the lowest addressed device which raised one.
+    fn halt_request(&self) -> Option<HaltRequest> {
+        self.regions
+            .iter()
+            .find_map(|region| region.device.halt_request())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clint::Clint;
+
+    #[test]
+    fn read_write_little_endian() {
+        let mut bus = Bus::new(vec![0; 8]);
+        bus.write32(4, 0x1234_5678).unwrap();
+        assert_eq!(bus.read8(4).unwrap(), 0x78);
+        assert_eq!(bus.read16(6).unwrap(), 0x1234);
+        bus.write16(0, 0xbeef).unwrap();
+        bus.write8(2, 0xad).unwrap();
+        assert_eq!(bus.read32(0).unwrap(), 0x00ad_beef);
+    }
+
+    #[test]
+    fn out_of_range_access_is_fault() {
+        let mut bus = Bus::new(vec![0; 8]);
+        assert!(matches!(
+            bus.read8(8),
+            Err(BusReadException::LoadAccessFault)
+        ));
+        assert!(matches!(
+            bus.read32(8),
+            Err(BusReadException::LoadAccessFault)
+        ));
+        assert!(matches!(
+            bus.read32(u32::MAX - 3),
+            Err(BusReadException::LoadAccessFault)
+        ));
+        assert!(matches!(
+            bus.write16(8, 0),
+            Err(BusWriteException::StoreAccessFault)
+        ));
+    }
+
+    #[test]
+    fn doubleword_access() {
+        let mut bus = Bus::new(vec![0; 12]);
+        bus.write64(0, 0x1234_5678_9abc_def0).unwrap();
+        assert_eq!(bus.read32(4).unwrap(), 0x1234_5678);
+        assert_eq!(bus.read64(0).unwrap(), 0x1234_5678_9abc_def0);
+        assert!(matches!(
+            bus.write64(4, 0),
+            Err(BusWriteException::StoreAddressMisaligned)
+        ));
+        // Nothing is written if the doubleword is not in a single region.
+        bus.write32(8, 1).unwrap();
+        assert!(matches!(
+            bus.write64(8, 0),
+            Err(BusWriteException::StoreAccessFault)
+        ));
+        assert_eq!(bus.read32(8).unwrap(), 1);
+        assert!(matches!(
+            bus.read64(8),
+            Err(BusReadException::LoadAccessFault)
+        ));
+    }
+
+    #[test]
+    fn clint_interrupts() {
+        let mut bus = Bus::new(vec![0; 8]);
+        bus.map(Clint::BASE, Clint::new()).unwrap();
+        bus.write32(Clint::BASE, 1).unwrap();
+        assert!(bus.interrupts().msip);
+        assert!(matches!(
+            bus.read8(Clint::BASE),
+            Err(BusReadException::LoadAccessFault)
+        ));
+    }
+
+    #[test]
+    fn misaligned_access() {
+        let mut bus = Bus::new(vec![0; 8]);
+        assert!(matches!(
+            bus.read16(1),
             Err(BusReadException::LoadAddressMisaligned)
-        } else {
-            let addr = addr as usize;
-            Ok(u32::from_le_bytes([
-                self.ram[addr],
-                self.ram[addr + 1],
-                self.ram[addr + 2],
-                self.ram[addr + 3],
-            ]))
+        ));
+        assert!(matches!(
+            bus.write32(2, 0),
+            Err(BusWriteException::StoreAddressMisaligned)
+        ));
+    }
+
+    #[test]
+    fn memory_map() {
+        let mut bus = Bus::empty();
+        bus.map_rom(0x1000, vec![0x13, 0, 0, 0]).unwrap();
+        bus.map_ram(Bus::DRAM_BASE, 0x100).unwrap();
+
+        assert_eq!(bus.read32(0x1000).unwrap(), 0x13);
+        assert!(matches!(
+            bus.write32(0x1000, 0),
+            Err(BusWriteException::StoreAccessFault)
+        ));
+
+        bus.write32(Bus::DRAM_BASE + 0xfc, 0xdead_beef).unwrap();
+        assert_eq!(bus.read32(Bus::DRAM_BASE + 0xfc).unwrap(), 0xdead_beef);
+
+        // unmapped
+        assert!(matches!(
+            bus.read32(0),
+            Err(BusReadException::LoadAccessFault)
+        ));
+        assert!(matches!(
+            bus.read32(Bus::DRAM_BASE + 0x100),
+            Err(BusReadException::LoadAccessFault)
+        ));
+        assert!(matches!(
+            bus.write8(0x0fff, 0),
+            Err(BusWriteException::StoreAccessFault)
+        ));
+    }
+
+    #[test]
+    fn overlapping_region_is_rejected() {
+        let mut bus = Bus::empty();
+        bus.map_ram(0x1000, 0x1000).unwrap();
+        bus.map_ram(0x3000, 0x1000).unwrap();
+
+        let overlap = |base, size| BusError::Overlap {
+            base,
+            size,
+            other: 0x1000,
+        };
+        assert_eq!(bus.map_ram(0x1fff, 0x10), Err(overlap(0x1fff, 0x10)));
+        assert_eq!(bus.map_ram(0x0800, 0x1000), Err(overlap(0x0800, 0x1000)));
+        assert_eq!(bus.map_ram(0x0000, 0x10000), Err(overlap(0x0000, 0x10000)));
+        // adjacent regions are allowed
+        bus.map_ram(0x2000, 0x1000).unwrap();
+        bus.map_ram(0x0000, 0x1000).unwrap();
+
+        assert_eq!(
+            bus.map_ram(0xffff_f000, 0x2000),
+            Err(BusError::InvalidRegion {
+                base: 0xffff_f000,
+                size: 0x2000
+            })
+        );
+        assert_eq!(
+            bus.map_ram(0x8000, 0),
+            Err(BusError::InvalidRegion {
+                base: 0x8000,
+                size: 0
+            })
+        );
+    }
+
+    /// Device which only supports word access and counts reads.
+    struct Counter {
+        count: std::cell::Cell<u32>,
+    }
+
+    impl Device for Counter {
+        fn size(&self) -> u32 {
+            4
+        }
+        fn read32(&self, _offset: u32) -> Result<u32, BusReadException> {
+            self.count.set(self.count.get() + 1);
+            Ok(self.count.get())
+        }
+        fn interrupts(&self) -> Interrupts {
+            Interrupts {
+                meip: self.count.get() >= 2,
+                ..Default::default()
+            }
         }
     }
+
+    #[test]
+    fn user_device() {
+        let mut bus = Bus::empty();
+        bus.map(
+            0x1000_0000,
+            Counter {
+                count: std::cell::Cell::new(0),
+            },
+        )
+        .unwrap();
+        assert_eq!(bus.read32(0x1000_0000).unwrap(), 1);
+        assert!(!bus.interrupts().meip);
+        assert_eq!(bus.read32(0x1000_0000).unwrap(), 2);
+        assert!(bus.interrupts().meip);
+        assert!(matches!(
+            bus.read8(0x1000_0000),
+            Err(BusReadException::LoadAccessFault)
+        ));
+        assert!(matches!(
+            bus.write32(0x1000_0000, 0),
+            Err(BusWriteException::StoreAccessFault)
+        ));
+    }
 }
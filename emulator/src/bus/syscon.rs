@@ -0,0 +1,77 @@
+use super::{
+    device::Device,
+    interface::{BusReadException, BusWriteException, HaltRequest},
+};
+
+/// System controller compatible with SiFive test finisher(`sifive,test0`) of QEMU virt machine.
+/// Writing to the register requests the emulator to stop.
+///
+/// | value                 | request              |
+/// |-----------------------|----------------------|
+/// | `0x5555`              | power off            |
+/// | `code << 16 \| 0x3333` | exit with `code`     |
+#[derive(Debug)]
+pub struct Syscon {
+    request: Option<HaltRequest>,
+}
+
+impl Syscon {
+    /// Base address on QEMU virt machine.
+    pub const BASE: u32 = 0x0010_0000;
+    pub const SIZE: u32 = 0x1000;
+
+    const FINISHER_PASS: u32 = 0x5555;
+    const FINISHER_FAIL: u32 = 0x3333;
+
+    pub fn new() -> Self {
+        Self { request: None }
+    }
+}
+
+impl Device for Syscon {
+    fn size(&self) -> u32 {
+        Self::SIZE
+    }
+
+    fn read32(&self, offset: u32) -> Result<u32, BusReadException> {
+        match offset {
+            0 => Ok(0),
+            _ => Err(BusReadException::LoadAccessFault),
+        }
+    }
+
+    fn write32(&mut self, offset: u32, v: u32) -> Result<(), BusWriteException> {
+        if offset != 0 {
+            return Err(BusWriteException::StoreAccessFault);
+        }
+        match v & 0xffff {
+            Self::FINISHER_PASS => self.request = Some(HaltRequest::PowerOff),
+            Self::FINISHER_FAIL => self.request = Some(HaltRequest::Exit(v >> 16)),
+            // Reset is not supported.
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn halt_request(&self) -> Option<HaltRequest> {
+        self.request
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finisher() {
+        let mut syscon = Syscon::new();
+        syscon.write32(0, 0x7777).unwrap();
+        assert_eq!(syscon.halt_request(), None);
+
+        syscon.write32(0, 0x5555).unwrap();
+        assert_eq!(syscon.halt_request(), Some(HaltRequest::PowerOff));
+
+        syscon.write32(0, 3 << 16 | 0x3333).unwrap();
+        assert_eq!(syscon.halt_request(), Some(HaltRequest::Exit(3)));
+    }
+}
@@ -0,0 +1,196 @@
+use std::{cell::Cell, io::Write, sync::mpsc::Receiver};
+
+use super::{
+    device::Device,
+    interface::{BusReadException, BusWriteException},
+};
+
+/// Minimal NS16550A compatible UART used as the guest console.
+/// Registers are byte wide and placed at consecutive addresses(reg-shift 0).
+///
+/// Interrupts are not raised, the guest is expected to poll LSR.
+pub struct Uart {
+    output: Box<dyn Write>,
+    input: Option<Receiver<u8>>,
+    /// Byte received from input but not read by the guest yet.
+    rbr: Cell<Option<u8>>,
+    ier: u8,
+    lcr: u8,
+    mcr: u8,
+    scr: u8,
+    /// Divisor latch
+    dll: u8,
+    dlm: u8,
+}
+
+impl Uart {
+    /// Base address on QEMU virt machine.
+    pub const BASE: u32 = 0x1000_0000;
+    pub const SIZE: u32 = 0x100;
+
+    const RBR_THR: u32 = 0;
+    const IER: u32 = 1;
+    const IIR_FCR: u32 = 2;
+    const LCR: u32 = 3;
+    const MCR: u32 = 4;
+    const LSR: u32 = 5;
+    const MSR: u32 = 6;
+    const SCR: u32 = 7;
+
+    /// Divisor latch access bit
+    const LCR_DLAB: u8 = 1 << 7;
+    /// Data ready
+    const LSR_DR: u8 = 1 << 0;
+    /// Transmitter holding register empty
+    const LSR_THRE: u8 = 1 << 5;
+    /// Transmitter empty
+    const LSR_TEMT: u8 = 1 << 6;
+    /// No interrupt pending
+    const IIR_NO_INTERRUPT: u8 = 1 << 0;
+
+    /// Construct `Uart` which transmits to output.
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self {
+            output,
+            input: None,
+            rbr: Cell::new(None),
+            ier: 0,
+            lcr: 0,
+            mcr: 0,
+            scr: 0,
+            dll: 0,
+            dlm: 0,
+        }
+    }
+
+    /// Receive bytes sent through the channel.
+    pub fn with_input(mut self, input: Receiver<u8>) -> Self {
+        self.input = Some(input);
+        self
+    }
+
+    fn dlab(&self) -> bool {
+        self.lcr & Self::LCR_DLAB != 0
+    }
+
+    /// Return received byte without consuming it.
+    fn peek(&self) -> Option<u8> {
+        if self.rbr.get().is_none() {
+            self.rbr
+                .set(self.input.as_ref().and_then(|rx| rx.try_recv().ok()));
+        }
+        self.rbr.get()
+    }
+}
+
+impl Device for Uart {
+    fn size(&self) -> u32 {
+        Self::SIZE
+    }
+
+    fn read8(&self, offset: u32) -> Result<u8, BusReadException> {
+        let v = match offset {
+            Self::RBR_THR if self.dlab() => self.dll,
+            Self::RBR_THR => {
+                let v = self.peek().unwrap_or(0);
+                self.rbr.set(None);
+                v
+            }
+            Self::IER if self.dlab() => self.dlm,
+            Self::IER => self.ier,
+            Self::IIR_FCR => Self::IIR_NO_INTERRUPT,
+            Self::LCR => self.lcr,
+            Self::MCR => self.mcr,
+            Self::LSR => {
+                let dr = if self.peek().is_some() {
+                    Self::LSR_DR
+                } else {
+                    0
+                };
+                dr | Self::LSR_THRE | Self::LSR_TEMT
+            }
+            Self::MSR => 0,
+            Self::SCR => self.scr,
+            _ => return Err(BusReadException::LoadAccessFault),
+        };
+        Ok(v)
+    }
+
+    fn write8(&mut self, offset: u32, v: u8) -> Result<(), BusWriteException> {
+        match offset {
+            Self::RBR_THR if self.dlab() => self.dll = v,
+            Self::RBR_THR => {
+                // Console output is best effort, the guest can not observe host I/O errors.
+                _ = self.output.write_all(&[v]);
+                _ = self.output.flush();
+            }
+            Self::IER if self.dlab() => self.dlm = v,
+            Self::IER => self.ier = v & 0x0f,
+            // FIFO control is accepted and ignored.
+            Self::IIR_FCR => (),
+            Self::LCR => self.lcr = v,
+            Self::MCR => self.mcr = v,
+            Self::LSR | Self::MSR => (),
+            Self::SCR => self.scr = v,
+            _ => return Err(BusWriteException::StoreAccessFault),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc, sync::mpsc};
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn transmit() {
+        let output = Output::default();
+        let mut uart = Uart::new(Box::new(output.clone()));
+        for b in b"hello" {
+            assert_ne!(uart.read8(Uart::LSR).unwrap() & Uart::LSR_THRE, 0);
+            uart.write8(Uart::RBR_THR, *b).unwrap();
+        }
+        assert_eq!(output.0.borrow().as_slice(), b"hello");
+    }
+
+    #[test]
+    fn receive() {
+        let (tx, rx) = mpsc::channel();
+        let uart = Uart::new(Box::new(std::io::sink())).with_input(rx);
+        assert_eq!(uart.read8(Uart::LSR).unwrap() & Uart::LSR_DR, 0);
+
+        tx.send(b'a').unwrap();
+        tx.send(b'b').unwrap();
+        assert_ne!(uart.read8(Uart::LSR).unwrap() & Uart::LSR_DR, 0);
+        assert_eq!(uart.read8(Uart::RBR_THR).unwrap(), b'a');
+        assert_eq!(uart.read8(Uart::RBR_THR).unwrap(), b'b');
+        assert_eq!(uart.read8(Uart::LSR).unwrap() & Uart::LSR_DR, 0);
+    }
+
+    #[test]
+    fn divisor_latch() {
+        let output = Output::default();
+        let mut uart = Uart::new(Box::new(output.clone()));
+        uart.write8(Uart::LCR, Uart::LCR_DLAB | 0x03).unwrap();
+        uart.write8(Uart::RBR_THR, 0x01).unwrap();
+        uart.write8(Uart::IER, 0x00).unwrap();
+        uart.write8(Uart::LCR, 0x03).unwrap();
+        assert!(output.0.borrow().is_empty());
+        uart.write8(Uart::LCR, Uart::LCR_DLAB).unwrap();
+        assert_eq!(uart.read8(Uart::RBR_THR).unwrap(), 0x01);
+    }
+}
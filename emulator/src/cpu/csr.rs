@@ -1,41 +1,1086 @@
+use thiserror::Error;
+
+use super::{pmp::Pmp, Mode};
 use crate::instructions::RegisterIdx;
 
-enum CsrAddr {
+/// Generate getter and setter for a single bit field.
+/// Only the getter is generated if the setter is omitted.
+macro_rules! bit_field {
+    ($(#[$doc:meta])* $get:ident, $bit:expr) => {
+        $(#[$doc])*
+        pub fn $get(&self) -> bool {
+            self.0 & (1 << $bit) != 0
+        }
+    };
+    ($(#[$doc:meta])* $get:ident, $set:ident, $bit:expr) => {
+        bit_field!($(#[$doc])* $get, $bit);
+
+        pub fn $set(&mut self, v: bool) {
+            if v {
+                self.0 |= 1 << $bit;
+            } else {
+                self.0 &= !(1 << $bit);
+            }
+        }
+    };
+}
+
+/// Address of implemented CSRs.
+/// Volume II: RISC-V Privileged Architectures V20211203 P8
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsrAddr {
+    // Unprivileged floating-point CSRs
+    Fflags = 0x001,
+    Frm = 0x002,
+    Fcsr = 0x003,
+
+    // Supervisor trap setup
+    Sstatus = 0x100,
+    Sie = 0x104,
+    Stvec = 0x105,
+    Scounteren = 0x106,
+
+    // Supervisor trap handling
+    Sscratch = 0x140,
+    Sepc = 0x141,
+    Scause = 0x142,
+    Stval = 0x143,
+    Sip = 0x144,
+
+    // Supervisor protection and translation
+    Satp = 0x180,
+
+    // Unprivileged counters/timers
+    Cycle = 0xc00,
+    Time = 0xc01,
+    Instret = 0xc02,
+    Cycleh = 0xc80,
+    Timeh = 0xc81,
+    Instreth = 0xc82,
+
+    // Machine information registers
+    Mvendorid = 0xf11,
+    Marchid = 0xf12,
+    Mimpid = 0xf13,
+    Mhartid = 0xf14,
+    Mconfigptr = 0xf15,
+
+    // Machine trap setup
     Mstatus = 0x300,
+    Misa = 0x301,
+    Medeleg = 0x302,
+    Mideleg = 0x303,
+    Mie = 0x304,
+    Mtvec = 0x305,
+    Mcounteren = 0x306,
+    Mstatush = 0x310,
+
+    // Machine trap handling
+    Mscratch = 0x340,
+    Mepc = 0x341,
+    Mcause = 0x342,
+    Mtval = 0x343,
+    Mip = 0x344,
+
+    // Machine memory protection
+    Pmpcfg0 = 0x3a0,
+    Pmpcfg1 = 0x3a1,
+    Pmpcfg2 = 0x3a2,
+    Pmpcfg3 = 0x3a3,
+    Pmpaddr0 = 0x3b0,
+    Pmpaddr1 = 0x3b1,
+    Pmpaddr2 = 0x3b2,
+    Pmpaddr3 = 0x3b3,
+    Pmpaddr4 = 0x3b4,
+    Pmpaddr5 = 0x3b5,
+    Pmpaddr6 = 0x3b6,
+    Pmpaddr7 = 0x3b7,
+    Pmpaddr8 = 0x3b8,
+    Pmpaddr9 = 0x3b9,
+    Pmpaddr10 = 0x3ba,
+    Pmpaddr11 = 0x3bb,
+    Pmpaddr12 = 0x3bc,
+    Pmpaddr13 = 0x3bd,
+    Pmpaddr14 = 0x3be,
+    Pmpaddr15 = 0x3bf,
+
+    // Machine counters/timers
+    Mcycle = 0xb00,
+    Minstret = 0xb02,
+    Mcycleh = 0xb80,
+    Minstreth = 0xb82,
+}
+
+impl CsrAddr {
+    /// Look up implemented CSR from address.
+    pub fn from_addr(addr: RegisterIdx) -> Option<Self> {
+        use CsrAddr::*;
+        let csr = match addr {
+            0x001 => Fflags,
+            0x002 => Frm,
+            0x003 => Fcsr,
+            0x100 => Sstatus,
+            0x104 => Sie,
+            0x105 => Stvec,
+            0x106 => Scounteren,
+            0x140 => Sscratch,
+            0x141 => Sepc,
+            0x142 => Scause,
+            0x143 => Stval,
+            0x144 => Sip,
+            0x180 => Satp,
+            0xc00 => Cycle,
+            0xc01 => Time,
+            0xc02 => Instret,
+            0xc80 => Cycleh,
+            0xc81 => Timeh,
+            0xc82 => Instreth,
+            0xf11 => Mvendorid,
+            0xf12 => Marchid,
+            0xf13 => Mimpid,
+            0xf14 => Mhartid,
+            0xf15 => Mconfigptr,
+            0x300 => Mstatus,
+            0x301 => Misa,
+            0x302 => Medeleg,
+            0x303 => Mideleg,
+            0x304 => Mie,
+            0x305 => Mtvec,
+            0x306 => Mcounteren,
+            0x310 => Mstatush,
+            0x340 => Mscratch,
+            0x341 => Mepc,
+            0x342 => Mcause,
+            0x343 => Mtval,
+            0x344 => Mip,
+            0x3a0 => Pmpcfg0,
+            0x3a1 => Pmpcfg1,
+            0x3a2 => Pmpcfg2,
+            0x3a3 => Pmpcfg3,
+            0x3b0 => Pmpaddr0,
+            0x3b1 => Pmpaddr1,
+            0x3b2 => Pmpaddr2,
+            0x3b3 => Pmpaddr3,
+            0x3b4 => Pmpaddr4,
+            0x3b5 => Pmpaddr5,
+            0x3b6 => Pmpaddr6,
+            0x3b7 => Pmpaddr7,
+            0x3b8 => Pmpaddr8,
+            0x3b9 => Pmpaddr9,
+            0x3ba => Pmpaddr10,
+            0x3bb => Pmpaddr11,
+            0x3bc => Pmpaddr12,
+            0x3bd => Pmpaddr13,
+            0x3be => Pmpaddr14,
+            0x3bf => Pmpaddr15,
+            0xb00 => Mcycle,
+            0xb02 => Minstret,
+            0xb80 => Mcycleh,
+            0xb82 => Minstreth,
+            _ => return None,
+        };
+        Some(csr)
+    }
+
+    /// Look up implemented CSR from assembler name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        use CsrAddr::*;
+        let csr = match name {
+            "fflags" => Fflags,
+            "frm" => Frm,
+            "fcsr" => Fcsr,
+            "sstatus" => Sstatus,
+            "sie" => Sie,
+            "stvec" => Stvec,
+            "scounteren" => Scounteren,
+            "sscratch" => Sscratch,
+            "sepc" => Sepc,
+            "scause" => Scause,
+            "stval" => Stval,
+            "sip" => Sip,
+            "satp" => Satp,
+            "cycle" => Cycle,
+            "time" => Time,
+            "instret" => Instret,
+            "cycleh" => Cycleh,
+            "timeh" => Timeh,
+            "instreth" => Instreth,
+            "mvendorid" => Mvendorid,
+            "marchid" => Marchid,
+            "mimpid" => Mimpid,
+            "mhartid" => Mhartid,
+            "mconfigptr" => Mconfigptr,
+            "mstatus" => Mstatus,
+            "misa" => Misa,
+            "medeleg" => Medeleg,
+            "mideleg" => Mideleg,
+            "mie" => Mie,
+            "mtvec" => Mtvec,
+            "mcounteren" => Mcounteren,
+            "mstatush" => Mstatush,
+            "mscratch" => Mscratch,
+            "mepc" => Mepc,
+            "mcause" => Mcause,
+            "mtval" => Mtval,
+            "mip" => Mip,
+            "pmpcfg0" => Pmpcfg0,
+            "pmpcfg1" => Pmpcfg1,
+            "pmpcfg2" => Pmpcfg2,
+            "pmpcfg3" => Pmpcfg3,
+            "pmpaddr0" => Pmpaddr0,
+            "pmpaddr1" => Pmpaddr1,
+            "pmpaddr2" => Pmpaddr2,
+            "pmpaddr3" => Pmpaddr3,
+            "pmpaddr4" => Pmpaddr4,
+            "pmpaddr5" => Pmpaddr5,
+            "pmpaddr6" => Pmpaddr6,
+            "pmpaddr7" => Pmpaddr7,
+            "pmpaddr8" => Pmpaddr8,
+            "pmpaddr9" => Pmpaddr9,
+            "pmpaddr10" => Pmpaddr10,
+            "pmpaddr11" => Pmpaddr11,
+            "pmpaddr12" => Pmpaddr12,
+            "pmpaddr13" => Pmpaddr13,
+            "pmpaddr14" => Pmpaddr14,
+            "pmpaddr15" => Pmpaddr15,
+            "mcycle" => Mcycle,
+            "minstret" => Minstret,
+            "mcycleh" => Mcycleh,
+            "minstreth" => Minstreth,
+            _ => return None,
+        };
+        Some(csr)
+    }
+
+    /// Return assembler name of the CSR.
+    pub fn name(self) -> &'static str {
+        use CsrAddr::*;
+        match self {
+            Fflags => "fflags",
+            Frm => "frm",
+            Fcsr => "fcsr",
+            Sstatus => "sstatus",
+            Sie => "sie",
+            Stvec => "stvec",
+            Scounteren => "scounteren",
+            Sscratch => "sscratch",
+            Sepc => "sepc",
+            Scause => "scause",
+            Stval => "stval",
+            Sip => "sip",
+            Satp => "satp",
+            Cycle => "cycle",
+            Time => "time",
+            Instret => "instret",
+            Cycleh => "cycleh",
+            Timeh => "timeh",
+            Instreth => "instreth",
+            Mvendorid => "mvendorid",
+            Marchid => "marchid",
+            Mimpid => "mimpid",
+            Mhartid => "mhartid",
+            Mconfigptr => "mconfigptr",
+            Mstatus => "mstatus",
+            Misa => "misa",
+            Medeleg => "medeleg",
+            Mideleg => "mideleg",
+            Mie => "mie",
+            Mtvec => "mtvec",
+            Mcounteren => "mcounteren",
+            Mstatush => "mstatush",
+            Mscratch => "mscratch",
+            Mepc => "mepc",
+            Mcause => "mcause",
+            Mtval => "mtval",
+            Mip => "mip",
+            Pmpcfg0 => "pmpcfg0",
+            Pmpcfg1 => "pmpcfg1",
+            Pmpcfg2 => "pmpcfg2",
+            Pmpcfg3 => "pmpcfg3",
+            Pmpaddr0 => "pmpaddr0",
+            Pmpaddr1 => "pmpaddr1",
+            Pmpaddr2 => "pmpaddr2",
+            Pmpaddr3 => "pmpaddr3",
+            Pmpaddr4 => "pmpaddr4",
+            Pmpaddr5 => "pmpaddr5",
+            Pmpaddr6 => "pmpaddr6",
+            Pmpaddr7 => "pmpaddr7",
+            Pmpaddr8 => "pmpaddr8",
+            Pmpaddr9 => "pmpaddr9",
+            Pmpaddr10 => "pmpaddr10",
+            Pmpaddr11 => "pmpaddr11",
+            Pmpaddr12 => "pmpaddr12",
+            Pmpaddr13 => "pmpaddr13",
+            Pmpaddr14 => "pmpaddr14",
+            Pmpaddr15 => "pmpaddr15",
+            Mcycle => "mcycle",
+            Minstret => "minstret",
+            Mcycleh => "mcycleh",
+            Minstreth => "minstreth",
+        }
+    }
+}
+
+/// Return whether addr is one of the hardware performance monitoring counters or events
+/// (mhpmcounter3-31, mhpmcounter3h-31h, mhpmevent3-31, hpmcounter3-31, hpmcounter3h-31h).
+/// They are implemented as read-only zero.
+fn is_hpm(addr: RegisterIdx) -> bool {
+    matches!(addr & 0x1f, 3..=31) && matches!(addr & !0x1f, 0xb00 | 0xb80 | 0x320 | 0xc00 | 0xc80)
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsrError {
+    #[error("csr {0:#05x} is not implemented")]
+    NotImplemented(RegisterIdx),
+    #[error("csr {0:#05x} requires higher privilege")]
+    Privilege(RegisterIdx),
+    #[error("csr {0:#05x} is read-only")]
+    ReadOnly(RegisterIdx),
+    #[error("csr {0:#05x} is disabled")]
+    Disabled(RegisterIdx),
 }
 
 /// Control and Status Register
 #[derive(Debug)]
 pub struct Csr {
-    r: [u32; Self::ADDR_SPACE],
+    /// sstatus is a restricted view of mstatus.
+    pub mstatus: Mstatus,
+    /// sie is a view of mie restricted to the delegated interrupts.
+    pub mie: Mie,
+    /// sip is a view of mip restricted to the delegated interrupts.
+    pub mip: Mip,
+    pub mtvec: Tvec,
+    pub mcounteren: Counteren,
+    pub mscratch: u32,
+    pub mepc: u32,
+    pub mcause: Cause,
+    pub mtval: u32,
+    /// Exceptions delegated to S-mode
+    pub medeleg: u32,
+    /// Interrupts delegated to S-mode
+    pub mideleg: u32,
+    pub stvec: Tvec,
+    pub scounteren: Counteren,
+    pub sscratch: u32,
+    pub sepc: u32,
+    pub scause: Cause,
+    pub stval: u32,
+    pub satp: Satp,
+    pub pmp: Pmp,
+    pub mcycle: u64,
+    /// Real-time counter mirrored from the bus timer
+    pub time: u64,
+    pub minstret: u64,
+    /// Accrued floating-point exception flags
+    pub fflags: u32,
+    /// Floating-point dynamic rounding mode
+    pub frm: u32,
 }
 
 impl Csr {
-    const ADDR_SPACE: usize = 4096;
+    /// Supported ISA. MXL=1(32bit), extensions I, M, A, F, D, C, S and U.
+    pub const MISA: u32 =
+        (1 << 30) | Misa::I | Misa::M | Misa::A | Misa::F | Misa::D | Misa::C | Misa::S | Misa::U;
+    /// Exceptions which can be delegated. Environment call from M-mode is never delegated.
+    const MEDELEG_MASK: u32 = 0xb3ff;
+    /// Supervisor software, timer and external interrupts can be delegated.
+    const MIDELEG_MASK: u32 = Mip::SSIP | Mip::STIP | Mip::SEIP;
+
     pub fn new() -> Self {
         Self {
-            r: [0; Self::ADDR_SPACE],
+            mstatus: Mstatus(0),
+            mie: Mie(0),
+            mip: Mip(0),
+            mtvec: Tvec(0),
+            mcounteren: Counteren(0),
+            mscratch: 0,
+            mepc: 0,
+            mcause: Cause(0),
+            mtval: 0,
+            medeleg: 0,
+            mideleg: 0,
+            stvec: Tvec(0),
+            scounteren: Counteren(0),
+            sscratch: 0,
+            sepc: 0,
+            scause: Cause(0),
+            stval: 0,
+            satp: Satp(0),
+            pmp: Pmp::new(),
+            mcycle: 0,
+            time: 0,
+            minstret: 0,
+            fflags: 0,
+            frm: 0,
         }
     }
 
-    pub fn read_mstatus(&self) -> Mstatus {
-        Mstatus(self.read(CsrAddr::Mstatus as usize))
+    /// Return interrupts which are both pending and enabled in mie regardless of global enable.
+    pub fn local_interrupts(&self) -> u32 {
+        self.mip.0 & self.mie.0
     }
 
-    pub fn read(&self, addr: RegisterIdx) -> u32 {
-        self.r[addr]
+    /// Return whether any interrupt is both pending and enabled in mie regardless of global enable.
+    pub fn has_local_interrupt(&self) -> bool {
+        self.local_interrupts() != 0
     }
 
-    pub fn write(&mut self, addr: RegisterIdx, value: u32) {
-        self.r[addr] = value;
+    /// Check whether csr instruction in mode can access the csr.
+    /// The top two bits(csr[11:10]) indicate whether the register is read/write(00, 01, or 10) or read-only(11).
+    /// The next two bits(csr[9:8]) encode the lowest privilege level that can access the CSR.
+    pub fn check_access(&self, addr: RegisterIdx, mode: Mode, write: bool) -> Result<(), CsrError> {
+        if CsrAddr::from_addr(addr).is_none() && !is_hpm(addr) {
+            return Err(CsrError::NotImplemented(addr));
+        }
+        if mode.bits() < ((addr as u32 >> 8) & 0b11) {
+            return Err(CsrError::Privilege(addr));
+        }
+        if write && (addr >> 10) & 0b11 == 0b11 {
+            return Err(CsrError::ReadOnly(addr));
+        }
+        // Floating-point CSRs are not accessible while the floating-point unit is off.
+        if matches!(addr, 0x001..=0x003) && self.mstatus.fs() == Mstatus::FS_OFF {
+            return Err(CsrError::Disabled(addr));
+        }
+        // satp is not accessible from S-mode while mstatus.TVM is set.
+        if addr == CsrAddr::Satp as RegisterIdx && mode == Mode::S && self.mstatus.tvm() {
+            return Err(CsrError::Privilege(addr));
+        }
+        // mcounteren controls the availability of unprivileged counters to S-mode and U-mode,
+        // scounteren additionally controls them for U-mode.
+        if mode != Mode::M {
+            let enabled = |counteren: Counteren| match addr & !0x80 {
+                0xc00 => counteren.cy(),
+                0xc01 => counteren.tm(),
+                0xc02 => counteren.ir(),
+                _ => true,
+            };
+            if !enabled(self.mcounteren) || mode == Mode::U && !enabled(self.scounteren) {
+                return Err(CsrError::Privilege(addr));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read(&self, addr: RegisterIdx) -> Result<u32, CsrError> {
+        use CsrAddr::*;
+        let Some(csr) = CsrAddr::from_addr(addr) else {
+            return if is_hpm(addr) {
+                Ok(0)
+            } else {
+                Err(CsrError::NotImplemented(addr))
+            };
+        };
+        let v = match csr {
+            Fflags => self.fflags,
+            Frm => self.frm,
+            Fcsr => self.frm << 5 | self.fflags,
+            Sstatus => self.mstatus.0 & self::Mstatus::SSTATUS_MASK,
+            Sie => self.mie.0 & self.mideleg,
+            Stvec => self.stvec.0,
+            Scounteren => self.scounteren.0,
+            Sscratch => self.sscratch,
+            Sepc => self.sepc,
+            Scause => self.scause.0,
+            Stval => self.stval,
+            Sip => self.mip.0 & self.mideleg,
+            Satp => self.satp.0,
+            Cycle | Mcycle => self.mcycle as u32,
+            Cycleh | Mcycleh => (self.mcycle >> 32) as u32,
+            Time => self.time as u32,
+            Timeh => (self.time >> 32) as u32,
+            Instret | Minstret => self.minstret as u32,
+            Instreth | Minstreth => (self.minstret >> 32) as u32,
+            Mvendorid | Marchid | Mimpid | Mhartid | Mconfigptr => 0,
+            Mstatus => self.mstatus.0,
+            Mstatush => 0,
+            Misa => Self::MISA,
+            Medeleg => self.medeleg,
+            Mideleg => self.mideleg,
+            Mie => self.mie.0,
+            Mtvec => self.mtvec.0,
+            Mcounteren => self.mcounteren.0,
+            Mscratch => self.mscratch,
+            Mepc => self.mepc,
+            Mcause => self.mcause.0,
+            Mtval => self.mtval,
+            Mip => self.mip.0,
+            Pmpcfg0 | Pmpcfg1 | Pmpcfg2 | Pmpcfg3 => self.pmp.cfg(csr as usize - Pmpcfg0 as usize),
+            Pmpaddr0 | Pmpaddr1 | Pmpaddr2 | Pmpaddr3 | Pmpaddr4 | Pmpaddr5 | Pmpaddr6
+            | Pmpaddr7 | Pmpaddr8 | Pmpaddr9 | Pmpaddr10 | Pmpaddr11 | Pmpaddr12 | Pmpaddr13
+            | Pmpaddr14 | Pmpaddr15 => self.pmp.addr(csr as usize - Pmpaddr0 as usize),
+        };
+        Ok(v)
+    }
+
+    /// Write value to csr.
+    /// Fields are WARL(Write Any values, Reads Legal values) so unsupported bits are masked.
+    pub fn write(&mut self, addr: RegisterIdx, value: u32) -> Result<(), CsrError> {
+        use CsrAddr::*;
+        let Some(csr) = CsrAddr::from_addr(addr) else {
+            return if is_hpm(addr) {
+                Ok(())
+            } else {
+                Err(CsrError::NotImplemented(addr))
+            };
+        };
+        match csr {
+            Cycle | Cycleh | Time | Timeh | Instret | Instreth | Mvendorid | Marchid | Mimpid
+            | Mhartid | Mconfigptr => return Err(CsrError::ReadOnly(addr)),
+            // Writes are ignored since misa and mstatush are not writable.
+            Misa | Mstatush => (),
+            Fflags | Frm | Fcsr => {
+                match csr {
+                    Fflags => self.fflags = value & 0x1f,
+                    Frm => self.frm = value & 0b111,
+                    _ => {
+                        self.fflags = value & 0x1f;
+                        self.frm = (value >> 5) & 0b111;
+                    }
+                }
+                self.mstatus.set_fs(self::Mstatus::FS_DIRTY);
+            }
+            Sstatus => self.mstatus.write_sstatus(value),
+            Sie => self.mie.write_masked(value, self.mideleg),
+            Stvec => self.stvec.write(value),
+            Scounteren => self.scounteren.write(value),
+            Sscratch => self.sscratch = value,
+            Sepc => self.sepc = value & !0b1,
+            Scause => self.scause.0 = value,
+            Stval => self.stval = value,
+            // Only SSIP is writable in sip.
+            Sip => self.mip.write_masked(value, self.mideleg & self::Mip::SSIP),
+            Satp => self.satp.write(value),
+            Mstatus => self.mstatus.write(value),
+            Medeleg => self.medeleg = value & Self::MEDELEG_MASK,
+            Mideleg => self.mideleg = value & Self::MIDELEG_MASK,
+            Mie => self.mie.write_masked(value, u32::MAX),
+            // MSIP, MTIP and MEIP are read-only in mip. They are set by devices.
+            Mip => self.mip.write_masked(value, u32::MAX),
+            Mtvec => self.mtvec.write(value),
+            Mcounteren => self.mcounteren.write(value),
+            Mscratch => self.mscratch = value,
+            // IALIGN=16 with the C extension, mepc[0] is always zero.
+            Mepc => self.mepc = value & !0b1,
+            Mcause => self.mcause.0 = value,
+            Mtval => self.mtval = value,
+            Pmpcfg0 | Pmpcfg1 | Pmpcfg2 | Pmpcfg3 => {
+                self.pmp.write_cfg(csr as usize - Pmpcfg0 as usize, value)
+            }
+            Pmpaddr0 | Pmpaddr1 | Pmpaddr2 | Pmpaddr3 | Pmpaddr4 | Pmpaddr5 | Pmpaddr6
+            | Pmpaddr7 | Pmpaddr8 | Pmpaddr9 | Pmpaddr10 | Pmpaddr11 | Pmpaddr12 | Pmpaddr13
+            | Pmpaddr14 | Pmpaddr15 => self.pmp.write_addr(csr as usize - Pmpaddr0 as usize, value),
+            Mcycle => self.mcycle = (self.mcycle & !0xffff_ffff) | value as u64,
+            Mcycleh => self.mcycle = (self.mcycle & 0xffff_ffff) | ((value as u64) << 32),
+            Minstret => self.minstret = (self.minstret & !0xffff_ffff) | value as u64,
+            Minstreth => self.minstret = (self.minstret & 0xffff_ffff) | ((value as u64) << 32),
+        }
+        Ok(())
     }
 }
 
+/// Machine ISA register extension bits.
+pub struct Misa;
+
+impl Misa {
+    pub const A: u32 = 1 << 0;
+    pub const C: u32 = 1 << 2;
+    pub const D: u32 = 1 << 3;
+    pub const F: u32 = 1 << 5;
+    pub const I: u32 = 1 << 8;
+    pub const M: u32 = 1 << 12;
+    pub const S: u32 = 1 << 18;
+    pub const U: u32 = 1 << 20;
+}
+
+/// Machine status register
+#[derive(Debug, Clone, Copy)]
 pub struct Mstatus(u32);
 
 impl Mstatus {
-    /// Return machine interrupt enable bit
-    pub fn mie(&self) -> bool {
-        (self.0 & 0x08) != 0
+    const SIE: u32 = 1 << 1;
+    const SPIE: u32 = 1 << 5;
+    const SPP: u32 = 1 << 8;
+    const MPP_SHIFT: u32 = 11;
+    const MPP: u32 = 0b11 << Self::MPP_SHIFT;
+    const FS_SHIFT: u32 = 13;
+    const FS: u32 = 0b11 << Self::FS_SHIFT;
+    const MPRV: u32 = 1 << 17;
+    const SUM: u32 = 1 << 18;
+    const MXR: u32 = 1 << 19;
+    const TVM: u32 = 1 << 20;
+    const TW: u32 = 1 << 21;
+    const TSR: u32 = 1 << 22;
+    /// State dirty. Read-only summary of FS.
+    const SD: u32 = 1 << 31;
+    const WRITE_MASK: u32 = Self::SIE
+        | Self::SPIE
+        | (1 << 3)
+        | (1 << 7)
+        | Self::SPP
+        | Self::MPP
+        | Self::FS
+        | Self::MPRV
+        | Self::SUM
+        | Self::MXR
+        | Self::TVM
+        | Self::TW
+        | Self::TSR;
+    /// Fields visible in sstatus.
+    const SSTATUS_MASK: u32 = Self::SSTATUS_WRITE_MASK | Self::SD;
+    /// Fields writable through sstatus.
+    const SSTATUS_WRITE_MASK: u32 =
+        Self::SIE | Self::SPIE | Self::SPP | Self::FS | Self::SUM | Self::MXR;
+
+    pub const FS_OFF: u32 = 0b00;
+    pub const FS_DIRTY: u32 = 0b11;
+
+    bit_field!(
+        /// Return supervisor interrupt enable bit
+        sie,
+        set_sie,
+        1
+    );
+    bit_field!(
+        /// Return machine interrupt enable bit
+        mie,
+        set_mie,
+        3
+    );
+    bit_field!(
+        /// Return supervisor previous interrupt enable bit
+        spie,
+        set_spie,
+        5
+    );
+    bit_field!(
+        /// Return machine previous interrupt enable bit
+        mpie,
+        set_mpie,
+        7
+    );
+    bit_field!(
+        /// Return supervisor previous privilege mode. S-mode if set, otherwise U-mode.
+        spp,
+        set_spp,
+        8
+    );
+    bit_field!(
+        /// Modify privilege. Loads and stores in M-mode use the privilege mode in MPP.
+        mprv,
+        set_mprv,
+        17
+    );
+    bit_field!(
+        /// Permit supervisor user memory access. S-mode may access pages with U=1.
+        sum,
+        18
+    );
+    bit_field!(
+        /// Make executable readable. Loads from pages with X=1 succeed even if R=0.
+        mxr,
+        19
+    );
+    bit_field!(
+        /// Trap virtual memory. satp accesses and SFENCE.VMA in S-mode are illegal.
+        tvm,
+        20
+    );
+    bit_field!(
+        /// Timeout wait. WFI in modes below M is illegal.
+        tw,
+        21
+    );
+    bit_field!(
+        /// Trap SRET. SRET in S-mode is illegal.
+        tsr,
+        22
+    );
+
+    /// Return machine previous privilege mode
+    pub fn mpp(&self) -> u32 {
+        (self.0 & Self::MPP) >> Self::MPP_SHIFT
+    }
+
+    pub fn set_mpp(&mut self, mode: u32) {
+        self.0 = (self.0 & !Self::MPP) | ((mode << Self::MPP_SHIFT) & Self::MPP);
+    }
+
+    /// Return floating-point unit status. Off(0), Initial(1), Clean(2) or Dirty(3).
+    pub fn fs(&self) -> u32 {
+        (self.0 & Self::FS) >> Self::FS_SHIFT
+    }
+
+    pub fn set_fs(&mut self, fs: u32) {
+        self.0 = (self.0 & !Self::FS) | ((fs << Self::FS_SHIFT) & Self::FS);
+        self.update_sd();
+    }
+
+    fn update_sd(&mut self) {
+        if self.fs() == Self::FS_DIRTY {
+            self.0 |= Self::SD;
+        } else {
+            self.0 &= !Self::SD;
+        }
+    }
+
+    fn write(&mut self, value: u32) {
+        let mpp = self.mpp();
+        self.0 = value & Self::WRITE_MASK;
+        // MPP is WARL. Keep the previous value if the mode is not supported.
+        if self.mpp() == 0b10 {
+            self.set_mpp(mpp);
+        }
+        self.update_sd();
+    }
+
+    fn write_sstatus(&mut self, value: u32) {
+        let mask = Self::SSTATUS_WRITE_MASK;
+        self.0 = (self.0 & !mask) | (value & mask);
+        self.update_sd();
+    }
+}
+
+/// Machine interrupt enable register
+#[derive(Debug, Clone, Copy)]
+pub struct Mie(u32);
+
+impl Mie {
+    const WRITE_MASK: u32 = Mip::SSIP | Mip::STIP | Mip::SEIP | Mip::MSIP | Mip::MTIP | Mip::MEIP;
+
+    /// Write the bits in mask. Bits not in mask keep their value.
+    fn write_masked(&mut self, value: u32, mask: u32) {
+        let mask = mask & Self::WRITE_MASK;
+        self.0 = (self.0 & !mask) | (value & mask);
+    }
+}
+
+/// Machine interrupt pending register
+#[derive(Debug, Clone, Copy)]
+pub struct Mip(u32);
+
+impl Mip {
+    pub const SSIP: u32 = 1 << 1;
+    pub const STIP: u32 = 1 << 5;
+    pub const SEIP: u32 = 1 << 9;
+    pub const MSIP: u32 = 1 << 3;
+    pub const MTIP: u32 = 1 << 7;
+    pub const MEIP: u32 = 1 << 11;
+    /// Supervisor interrupts are pending by software in M-mode.
+    const WRITE_MASK: u32 = Self::SSIP | Self::STIP | Self::SEIP;
+
+    /// Write the bits in mask. Bits not in mask keep their value.
+    fn write_masked(&mut self, value: u32, mask: u32) {
+        let mask = mask & Self::WRITE_MASK;
+        self.0 = (self.0 & !mask) | (value & mask);
+    }
+
+    /// Set or clear the pending bits in mask.
+    pub fn set(&mut self, mask: u32, pending: bool) {
+        if pending {
+            self.0 |= mask;
+        } else {
+            self.0 &= !mask;
+        }
+    }
+}
+
+/// Trap vector mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TvecMode {
+    /// All traps set pc to BASE.
+    Direct,
+    /// Asynchronous interrupts set pc to BASE+4×cause.
+    Vectored,
+}
+
+/// Trap-vector base-address register(mtvec, stvec)
+#[derive(Debug, Clone, Copy)]
+pub struct Tvec(u32);
+
+impl Tvec {
+    pub fn base(&self) -> u32 {
+        self.0 & !0b11
+    }
+
+    pub fn mode(&self) -> TvecMode {
+        match self.0 & 0b11 {
+            0 => TvecMode::Direct,
+            _ => TvecMode::Vectored,
+        }
+    }
+
+    fn write(&mut self, value: u32) {
+        // MODE values >= 2 are reserved.
+        self.0 = value & !0b10;
+    }
+}
+
+/// Trap cause register(mcause, scause)
+#[derive(Debug, Clone, Copy)]
+pub struct Cause(u32);
+
+impl Cause {
+    const INTERRUPT: u32 = 1 << 31;
+
+    pub fn new(interrupt: bool, code: u32) -> Self {
+        if interrupt {
+            Self(Self::INTERRUPT | code)
+        } else {
+            Self(code)
+        }
+    }
+
+    pub fn is_interrupt(&self) -> bool {
+        self.0 & Self::INTERRUPT != 0
+    }
+
+    /// Exception code
+    pub fn code(&self) -> u32 {
+        self.0 & !Self::INTERRUPT
+    }
+}
+
+/// Counter-enable register(mcounteren, scounteren)
+/// Controls the availability of the counters to the next-lower privileged mode.
+#[derive(Debug, Clone, Copy)]
+pub struct Counteren(u32);
+
+impl Counteren {
+    const WRITE_MASK: u32 = 0b111;
+
+    bit_field!(
+        /// cycle register is accessible
+        cy,
+        0
+    );
+    bit_field!(
+        /// time register is accessible
+        tm,
+        1
+    );
+    bit_field!(
+        /// instret register is accessible
+        ir,
+        2
+    );
+
+    fn write(&mut self, value: u32) {
+        self.0 = value & Self::WRITE_MASK;
+    }
+}
+
+/// Supervisor address translation and protection register
+#[derive(Debug, Clone, Copy)]
+pub struct Satp(u32);
+
+impl Satp {
+    const MODE: u32 = 1 << 31;
+    const ASID_SHIFT: u32 = 22;
+    const ASID: u32 = 0x1ff << Self::ASID_SHIFT;
+    const PPN: u32 = 0x3f_ffff;
+
+    /// Return whether Sv32 translation is enabled. Otherwise MODE is Bare.
+    pub fn sv32(&self) -> bool {
+        self.0 & Self::MODE != 0
+    }
+
+    /// Address space identifier
+    pub fn asid(&self) -> u32 {
+        (self.0 & Self::ASID) >> Self::ASID_SHIFT
+    }
+
+    /// Physical page number of the root page table
+    pub fn ppn(&self) -> u32 {
+        self.0 & Self::PPN
+    }
+
+    fn write(&mut self, value: u32) {
+        // Both Bare and Sv32 are supported and all 9 ASID bits are implemented.
+        self.0 = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unimplemented_csr_is_not_accessible() {
+        let mut csr = Csr::new();
+        assert_eq!(csr.read(0x7c0), Err(CsrError::NotImplemented(0x7c0)));
+        assert_eq!(csr.write(0x7c0, 1), Err(CsrError::NotImplemented(0x7c0)));
+        assert_eq!(
+            csr.check_access(0x7c0, Mode::M, false),
+            Err(CsrError::NotImplemented(0x7c0))
+        );
+    }
+
+    #[test]
+    fn access_check() {
+        let csr = Csr::new();
+        assert!(csr
+            .check_access(CsrAddr::Mhartid as usize, Mode::M, false)
+            .is_ok());
+        assert_eq!(
+            csr.check_access(CsrAddr::Mhartid as usize, Mode::M, true),
+            Err(CsrError::ReadOnly(0xf14))
+        );
+        assert_eq!(
+            csr.check_access(CsrAddr::Mstatus as usize, Mode::U, false),
+            Err(CsrError::Privilege(0x300))
+        );
+        assert_eq!(
+            csr.check_access(CsrAddr::Cycle as usize, Mode::U, false),
+            Err(CsrError::Privilege(0xc00))
+        );
+
+        let mut csr = Csr::new();
+        csr.write(CsrAddr::Mcounteren as usize, 0b001).unwrap();
+        assert!(csr
+            .check_access(CsrAddr::Cycle as usize, Mode::S, false)
+            .is_ok());
+        // U-mode also requires scounteren.
+        assert_eq!(
+            csr.check_access(CsrAddr::Cycle as usize, Mode::U, false),
+            Err(CsrError::Privilege(0xc00))
+        );
+        csr.write(CsrAddr::Scounteren as usize, 0b001).unwrap();
+        assert!(csr
+            .check_access(CsrAddr::Cycle as usize, Mode::U, false)
+            .is_ok());
+        assert!(csr
+            .check_access(CsrAddr::Cycleh as usize, Mode::U, false)
+            .is_ok());
+        assert!(csr
+            .check_access(CsrAddr::Instret as usize, Mode::U, false)
+            .is_err());
+    }
+
+    #[test]
+    fn warl_masking() {
+        let mut csr = Csr::new();
+        csr.write(CsrAddr::Mstatus as usize, u32::MAX).unwrap();
+        // FS is dirty so SD is set.
+        assert_eq!(csr.read(CsrAddr::Mstatus as usize).unwrap(), 0x807e_79aa);
+
+        // MPP=10 is reserved, previous value is kept.
+        csr.write(CsrAddr::Mstatus as usize, 0x1000).unwrap();
+        assert_eq!(csr.mstatus.mpp(), 0b11);
+
+        csr.write(CsrAddr::Mtvec as usize, 0x103).unwrap();
+        assert_eq!(csr.mtvec.base(), 0x100);
+        assert_eq!(csr.mtvec.mode(), TvecMode::Vectored);
+
+        csr.write(CsrAddr::Mepc as usize, 0x103).unwrap();
+        assert_eq!(csr.mepc, 0x102);
+
+        csr.write(CsrAddr::Mie as usize, u32::MAX).unwrap();
+        assert_eq!(csr.read(CsrAddr::Mie as usize).unwrap(), 0xaaa);
+
+        csr.write(CsrAddr::Misa as usize, 0).unwrap();
+        assert_eq!(csr.read(CsrAddr::Misa as usize).unwrap(), Csr::MISA);
+    }
+
+    #[test]
+    fn supervisor_csrs() {
+        let mut csr = Csr::new();
+        assert!(csr
+            .check_access(CsrAddr::Sstatus as usize, Mode::S, true)
+            .is_ok());
+        assert_eq!(
+            csr.check_access(CsrAddr::Mstatus as usize, Mode::S, false),
+            Err(CsrError::Privilege(0x300))
+        );
+        assert_eq!(
+            csr.check_access(CsrAddr::Sstatus as usize, Mode::U, false),
+            Err(CsrError::Privilege(0x100))
+        );
+
+        // sstatus is a restricted view of mstatus.
+        csr.write(CsrAddr::Mstatus as usize, u32::MAX).unwrap();
+        assert_eq!(csr.read(CsrAddr::Sstatus as usize).unwrap(), 0x800c_6122);
+        csr.write(CsrAddr::Sstatus as usize, 0).unwrap();
+        assert_eq!(csr.read(CsrAddr::Mstatus as usize).unwrap(), 0x0072_1888);
+
+        // Exception code 11(ecall from M-mode) can not be delegated.
+        csr.write(CsrAddr::Medeleg as usize, u32::MAX).unwrap();
+        assert_eq!(csr.read(CsrAddr::Medeleg as usize).unwrap(), 0xb3ff);
+        csr.write(CsrAddr::Mideleg as usize, u32::MAX).unwrap();
+        assert_eq!(csr.read(CsrAddr::Mideleg as usize).unwrap(), 0x222);
+
+        // sie and sip only expose delegated interrupts.
+        csr.write(CsrAddr::Mideleg as usize, Mip::STIP).unwrap();
+        csr.write(CsrAddr::Sie as usize, u32::MAX).unwrap();
+        assert_eq!(csr.read(CsrAddr::Mie as usize).unwrap(), 0x020);
+        csr.write(CsrAddr::Mip as usize, Mip::STIP | Mip::SSIP)
+            .unwrap();
+        assert_eq!(csr.read(CsrAddr::Sip as usize).unwrap(), 0x020);
+        // SSIP is not delegated so sip can not clear it.
+        csr.write(CsrAddr::Sip as usize, 0).unwrap();
+        assert_eq!(csr.read(CsrAddr::Mip as usize).unwrap(), 0x022);
+
+        csr.write(CsrAddr::Satp as usize, 0x8040_0123).unwrap();
+        assert!(csr.satp.sv32());
+        assert_eq!((csr.satp.asid(), csr.satp.ppn()), (1, 0x123));
+
+        csr.write(CsrAddr::Mstatus as usize, 1 << 20).unwrap();
+        assert!(csr.mstatus.tvm());
+        assert_eq!(
+            csr.check_access(CsrAddr::Satp as usize, Mode::S, false),
+            Err(CsrError::Privilege(0x180))
+        );
+        assert!(csr
+            .check_access(CsrAddr::Satp as usize, Mode::M, false)
+            .is_ok());
+    }
+
+    #[test]
+    fn floating_point_csrs() {
+        let mut csr = Csr::new();
+        assert_eq!(
+            csr.check_access(CsrAddr::Fcsr as usize, Mode::M, false),
+            Err(CsrError::Disabled(0x003))
+        );
+
+        // FS = Initial
+        csr.write(CsrAddr::Mstatus as usize, 1 << 13).unwrap();
+        assert!(csr
+            .check_access(CsrAddr::Fcsr as usize, Mode::U, true)
+            .is_ok());
+        csr.write(CsrAddr::Fcsr as usize, 0xfff).unwrap();
+        assert_eq!(csr.read(CsrAddr::Fflags as usize).unwrap(), 0x1f);
+        assert_eq!(csr.read(CsrAddr::Frm as usize).unwrap(), 0b111);
+        assert_eq!(csr.read(CsrAddr::Fcsr as usize).unwrap(), 0xff);
+        csr.write(CsrAddr::Frm as usize, 0b001).unwrap();
+        assert_eq!(csr.read(CsrAddr::Fcsr as usize).unwrap(), 0x3f);
+
+        // Writes make the state dirty.
+        assert_eq!(csr.mstatus.fs(), Mstatus::FS_DIRTY);
+        assert_eq!(csr.read(CsrAddr::Mstatus as usize).unwrap(), 0x8000_6000);
+        csr.write(CsrAddr::Mstatus as usize, 2 << 13).unwrap();
+        assert_eq!(csr.read(CsrAddr::Mstatus as usize).unwrap(), 0x4000);
+    }
+
+    #[test]
+    fn counters() {
+        let mut csr = Csr::new();
+        csr.mcycle = 0x1_0000_0002;
+        assert_eq!(csr.read(CsrAddr::Cycle as usize).unwrap(), 2);
+        assert_eq!(csr.read(CsrAddr::Cycleh as usize).unwrap(), 1);
+        csr.write(CsrAddr::Mcycleh as usize, 3).unwrap();
+        assert_eq!(csr.mcycle, 0x3_0000_0002);
+        assert_eq!(
+            csr.write(CsrAddr::Cycle as usize, 0),
+            Err(CsrError::ReadOnly(0xc00))
+        );
+        csr.time = 0x4_0000_0005;
+        assert_eq!(csr.read(CsrAddr::Time as usize).unwrap(), 5);
+        assert_eq!(csr.read(CsrAddr::Timeh as usize).unwrap(), 4);
+
+        // hpm counters are hardwired to zero
+        csr.write(0xb03, 1).unwrap();
+        assert_eq!(csr.read(0xb03).unwrap(), 0);
+        assert_eq!(csr.read(0xc1f).unwrap(), 0);
     }
 }
@@ -0,0 +1,1198 @@
+//! IEEE 754-2008 binary floating-point arithmetic in software.
+//! Operations compute the exact result and round it once, so results are correctly rounded in every rounding mode.
+//! Volume I: RISC-V Unprivileged ISA V20191213 P63
+
+/// Binary interchange format. Values are passed as raw bits in the lower bits of u64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+    exp_bits: u32,
+    frac_bits: u32,
+}
+
+impl Format {
+    /// binary32
+    pub const SINGLE: Self = Self {
+        exp_bits: 8,
+        frac_bits: 23,
+    };
+
+    /// binary64
+    pub const DOUBLE: Self = Self {
+        exp_bits: 11,
+        frac_bits: 52,
+    };
+
+    fn width(self) -> u32 {
+        1 + self.exp_bits + self.frac_bits
+    }
+
+    /// NaN-box the value into a 64-bit f register by setting all the upper bits.
+    pub fn nan_box(self, bits: u64) -> u64 {
+        match self.width() {
+            64 => bits,
+            width => bits | u64::MAX << width,
+        }
+    }
+
+    /// Value held in a 64-bit f register. Values which are not properly NaN-boxed are the canonical NaN.
+    pub fn unbox(self, reg: u64) -> u64 {
+        if self.width() == 64 {
+            return reg;
+        }
+        let upper = u64::MAX << self.width();
+        if reg & upper == upper {
+            reg & !upper
+        } else {
+            self.canonical_nan()
+        }
+    }
+
+    fn sign_mask(self) -> u64 {
+        1 << (self.exp_bits + self.frac_bits)
+    }
+
+    fn frac_mask(self) -> u64 {
+        (1 << self.frac_bits) - 1
+    }
+
+    /// All ones biased exponent of infinities and NaNs.
+    fn max_biased_exp(self) -> u64 {
+        (1 << self.exp_bits) - 1
+    }
+
+    fn bias(self) -> i32 {
+        (1 << (self.exp_bits - 1)) - 1
+    }
+
+    fn emin(self) -> i32 {
+        1 - self.bias()
+    }
+
+    fn emax(self) -> i32 {
+        self.bias()
+    }
+
+    /// Number of significand bits including the implicit leading bit.
+    fn precision(self) -> u32 {
+        self.frac_bits + 1
+    }
+
+    fn pack(self, sign: bool, biased_exp: u64, frac: u64) -> u64 {
+        (sign as u64) << (self.exp_bits + self.frac_bits) | biased_exp << self.frac_bits | frac
+    }
+
+    /// Canonical NaN has a positive sign and all significand bits clear except the MSB(the quiet bit).
+    pub fn canonical_nan(self) -> u64 {
+        self.pack(false, self.max_biased_exp(), 1 << (self.frac_bits - 1))
+    }
+
+    fn infinity(self, sign: bool) -> u64 {
+        self.pack(sign, self.max_biased_exp(), 0)
+    }
+
+    fn zero(self, sign: bool) -> u64 {
+        self.pack(sign, 0, 0)
+    }
+
+    fn max_finite(self, sign: bool) -> u64 {
+        self.pack(sign, self.max_biased_exp() - 1, self.frac_mask())
+    }
+
+    fn sign(self, bits: u64) -> bool {
+        bits & self.sign_mask() != 0
+    }
+
+    pub fn is_nan(self, bits: u64) -> bool {
+        matches!(self.unpack(bits), Value::NaN)
+    }
+
+    fn is_signaling_nan(self, bits: u64) -> bool {
+        self.is_nan(bits) && bits & (1 << (self.frac_bits - 1)) == 0
+    }
+
+    /// Magnitude of a with the sign of b (FSGNJ).
+    pub fn copy_sign(self, a: u64, b: u64) -> u64 {
+        (a & !self.sign_mask()) | (b & self.sign_mask())
+    }
+
+    /// Magnitude of a with the opposite sign of b (FSGNJN).
+    pub fn copy_sign_negated(self, a: u64, b: u64) -> u64 {
+        (a & !self.sign_mask()) | (!b & self.sign_mask())
+    }
+
+    /// a with the sign flipped if b is negative (FSGNJX).
+    pub fn xor_sign(self, a: u64, b: u64) -> u64 {
+        a ^ (b & self.sign_mask())
+    }
+
+    fn unpack(self, bits: u64) -> Value {
+        let sign = self.sign(bits);
+        let biased_exp = (bits >> self.frac_bits) & self.max_biased_exp();
+        let frac = bits & self.frac_mask();
+        match (biased_exp, frac) {
+            (e, 0) if e == self.max_biased_exp() => Value::Infinity { sign },
+            (e, _) if e == self.max_biased_exp() => Value::NaN,
+            (0, 0) => Value::Zero { sign },
+            (0, _) => Value::Finite(Exact {
+                sign,
+                exp: self.emin() - self.frac_bits as i32,
+                sig: frac as u128,
+            }),
+            _ => Value::Finite(Exact {
+                sign,
+                exp: biased_exp as i32 - self.bias() - self.frac_bits as i32,
+                sig: (frac | 1 << self.frac_bits) as u128,
+            }),
+        }
+    }
+
+    /// Order of non-NaN values. -0 is less than +0 if signed_zero is true, otherwise they are equal.
+    fn order_key(self, bits: u64, signed_zero: bool) -> i128 {
+        let magnitude = (bits & !self.sign_mask()) as i128;
+        if self.sign(bits) {
+            -magnitude - signed_zero as i128
+        } else {
+            magnitude
+        }
+    }
+}
+
+/// Rounding mode of the rm field and frm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// Round to nearest, ties to even
+    NearestEven,
+    /// Round towards zero
+    TowardZero,
+    /// Round down (towards -∞)
+    Down,
+    /// Round up (towards +∞)
+    Up,
+    /// Round to nearest, ties to max magnitude
+    NearestMaxMagnitude,
+}
+
+impl RoundingMode {
+    /// Return static rounding mode. None for the reserved encodings and DYN(0b111).
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        use RoundingMode::*;
+        let rm = match bits {
+            0b000 => NearestEven,
+            0b001 => TowardZero,
+            0b010 => Down,
+            0b011 => Up,
+            0b100 => NearestMaxMagnitude,
+            _ => return None,
+        };
+        Some(rm)
+    }
+}
+
+/// Accrued exception flags in fflags.
+pub struct Fflags;
+
+impl Fflags {
+    /// Inexact
+    pub const NX: u32 = 1 << 0;
+    /// Underflow
+    pub const UF: u32 = 1 << 1;
+    /// Overflow
+    pub const OF: u32 = 1 << 2;
+    /// Divide by zero
+    pub const DZ: u32 = 1 << 3;
+    /// Invalid operation
+    pub const NV: u32 = 1 << 4;
+}
+
+/// Finite nonzero value sig × 2^exp.
+#[derive(Debug, Clone, Copy)]
+struct Exact {
+    sign: bool,
+    exp: i32,
+    sig: u128,
+}
+
+impl Exact {
+    /// Exponent just above the leading one bit.
+    fn top(&self) -> i32 {
+        self.exp + 128 - self.sig.leading_zeros() as i32
+    }
+
+    /// Shift significand so that it has exactly bits bits.
+    fn normalized(self, bits: u32) -> Self {
+        let shift = bits as i32 - (128 - self.sig.leading_zeros() as i32);
+        Self {
+            exp: self.exp - shift,
+            sig: self.sig << shift,
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Value {
+    NaN,
+    Infinity { sign: bool },
+    Zero { sign: bool },
+    Finite(Exact),
+}
+
+/// Exact sum of a and b. None if it is zero.
+/// Bits of the smaller operand far below the leading bit of the larger one are collapsed into a sticky bit.
+/// This keeps the sum within 128 bits without changing the rounded result.
+fn add_exact(a: Exact, b: Exact) -> Option<Exact> {
+    // Operands have at most 106 bits(product of two binary64 significands).
+    const WIDTH: i32 = 120;
+    let (a, mut b) = if a.top() >= b.top() { (a, b) } else { (b, a) };
+    let floor = a.top() - WIDTH;
+    if b.exp < floor {
+        b.sig = shift_right_jam(b.sig, floor - b.exp);
+        b.exp = floor;
+    }
+    let exp = a.exp.min(b.exp);
+    let (x, y) = (a.sig << (a.exp - exp), b.sig << (b.exp - exp));
+    let (sign, sig) = if a.sign == b.sign {
+        (a.sign, x + y)
+    } else if x >= y {
+        (a.sign, x - y)
+    } else {
+        (b.sign, y - x)
+    };
+    (sig != 0).then_some(Exact { sign, exp, sig })
+}
+
+/// Shift right and OR the discarded bits into the least significant bit.
+fn shift_right_jam(v: u128, shift: i32) -> u128 {
+    if shift >= 128 {
+        (v != 0) as u128
+    } else {
+        (v >> shift) | (v & ((1 << shift) - 1) != 0) as u128
+    }
+}
+
+/// Integer square root rounded down.
+fn isqrt(n: u128) -> u128 {
+    if n == 0 {
+        return 0;
+    }
+    let mut x = 1 << ((128 - n.leading_zeros() + 1) / 2);
+    loop {
+        let y = (x + n / x) / 2;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+/// Floating-point operations of a format in a rounding mode.
+/// Exception flags raised by the operations are accrued in `flags`.
+#[derive(Debug)]
+pub struct Fpu {
+    fmt: Format,
+    rm: RoundingMode,
+    pub flags: u32,
+}
+
+impl Fpu {
+    pub fn new(fmt: Format, rm: RoundingMode) -> Self {
+        Self { fmt, rm, flags: 0 }
+    }
+
+    fn invalid(&mut self) -> u64 {
+        self.flags |= Fflags::NV;
+        self.fmt.canonical_nan()
+    }
+
+    /// Result of an operation with NaN operands. Signaling NaNs raise invalid operation.
+    fn propagate_nan(&mut self, operands: &[u64]) -> u64 {
+        if operands.iter().any(|&v| self.fmt.is_signaling_nan(v)) {
+            self.flags |= Fflags::NV;
+        }
+        self.fmt.canonical_nan()
+    }
+
+    /// Zero sum of operands with opposite signs. It is -0 only when rounding down.
+    fn cancellation_zero(&self) -> u64 {
+        self.fmt.zero(self.rm == RoundingMode::Down)
+    }
+
+    /// Shift sig right by shift bits(left if negative) rounding the discarded bits.
+    /// Return the rounded integer and whether it is inexact.
+    fn shift_round(&self, sign: bool, sig: u128, shift: i32) -> (u128, bool) {
+        use RoundingMode::*;
+        if shift <= 0 {
+            return (sig << -shift, false);
+        }
+        let (kept, half, sticky) = match shift {
+            129.. => (0, false, sig != 0),
+            128 => (0, sig >> 127 != 0, sig << 1 != 0),
+            _ => {
+                let rem = sig & ((1 << shift) - 1);
+                let half = 1 << (shift - 1);
+                (sig >> shift, rem & half != 0, rem & (half - 1) != 0)
+            }
+        };
+        let inexact = half || sticky;
+        let round_up = match self.rm {
+            NearestEven => half && (sticky || kept & 1 != 0),
+            TowardZero => false,
+            Down => inexact && sign,
+            Up => inexact && !sign,
+            NearestMaxMagnitude => half,
+        };
+        (kept + round_up as u128, inexact)
+    }
+
+    /// Round the exact value to the format.
+    /// Tininess is detected after rounding as RISC-V requires.
+    fn round(&mut self, v: Exact) -> u64 {
+        use RoundingMode::*;
+        let fmt = self.fmt;
+        let frac_bits = fmt.frac_bits as i32;
+        // Exponent of the leading bit
+        let exp = v.top() - 1;
+        // Subnormal results have the exponent of the smallest normal number.
+        let mut result_exp = exp.max(fmt.emin());
+        let lsb = result_exp - frac_bits;
+        let (mut sig, inexact) = self.shift_round(v.sign, v.sig, lsb - v.exp);
+        if sig >> fmt.precision() != 0 {
+            // Rounded up to the next power of two. The discarded bit is zero.
+            sig >>= 1;
+            result_exp += 1;
+        }
+
+        if result_exp > fmt.emax() {
+            self.flags |= Fflags::OF | Fflags::NX;
+            let to_infinity = match self.rm {
+                NearestEven | NearestMaxMagnitude => true,
+                TowardZero => false,
+                Down => v.sign,
+                Up => !v.sign,
+            };
+            return if to_infinity {
+                fmt.infinity(v.sign)
+            } else {
+                fmt.max_finite(v.sign)
+            };
+        }
+        if inexact {
+            self.flags |= Fflags::NX;
+            // Tiny if the result rounded with unbounded exponent range is below 2^emin.
+            let tiny = exp < fmt.emin() - 1
+                || exp == fmt.emin() - 1
+                    && self.shift_round(v.sign, v.sig, lsb - 1 - v.exp).0 >> fmt.precision() == 0;
+            if tiny {
+                self.flags |= Fflags::UF;
+            }
+        }
+
+        let biased_exp = if sig >> fmt.frac_bits == 0 {
+            0
+        } else {
+            (result_exp + fmt.bias()) as u64
+        };
+        fmt.pack(v.sign, biased_exp, sig as u64 & fmt.frac_mask())
+    }
+
+    /// Sum of non-NaN values.
+    fn sum(&mut self, a: Value, b: Value) -> u64 {
+        use Value::*;
+        match (a, b) {
+            (NaN, _) | (_, NaN) => unreachable!("NaN operands are handled by the caller"),
+            (Infinity { sign: sa }, Infinity { sign: sb }) if sa != sb => self.invalid(),
+            (Infinity { sign }, _) | (_, Infinity { sign }) => self.fmt.infinity(sign),
+            (Zero { sign: sa }, Zero { sign: sb }) if sa == sb => self.fmt.zero(sa),
+            (Zero { .. }, Zero { .. }) => self.cancellation_zero(),
+            (Zero { .. }, Finite(x)) | (Finite(x), Zero { .. }) => self.round(x),
+            (Finite(x), Finite(y)) => match add_exact(x, y) {
+                Some(v) => self.round(v),
+                None => self.cancellation_zero(),
+            },
+        }
+    }
+
+    pub fn add(&mut self, a: u64, b: u64) -> u64 {
+        if self.fmt.is_nan(a) || self.fmt.is_nan(b) {
+            return self.propagate_nan(&[a, b]);
+        }
+        self.sum(self.fmt.unpack(a), self.fmt.unpack(b))
+    }
+
+    pub fn sub(&mut self, a: u64, b: u64) -> u64 {
+        // Flipping the sign keeps a signaling NaN signaling.
+        self.add(a, b ^ self.fmt.sign_mask())
+    }
+
+    /// Exact product of non-NaN values. None if it is 0 × ∞.
+    fn product(&self, a: u64, b: u64) -> Option<Value> {
+        use Value::*;
+        let sign = self.fmt.sign(a) != self.fmt.sign(b);
+        let v = match (self.fmt.unpack(a), self.fmt.unpack(b)) {
+            (Infinity { .. }, Zero { .. }) | (Zero { .. }, Infinity { .. }) => return None,
+            (Infinity { .. }, _) | (_, Infinity { .. }) => Infinity { sign },
+            (Zero { .. }, _) | (_, Zero { .. }) => Zero { sign },
+            (Finite(x), Finite(y)) => Finite(Exact {
+                sign,
+                exp: x.exp + y.exp,
+                sig: x.sig * y.sig,
+            }),
+            (NaN, _) | (_, NaN) => unreachable!("NaN operands are handled by the caller"),
+        };
+        Some(v)
+    }
+
+    pub fn mul(&mut self, a: u64, b: u64) -> u64 {
+        if self.fmt.is_nan(a) || self.fmt.is_nan(b) {
+            return self.propagate_nan(&[a, b]);
+        }
+        match self.product(a, b) {
+            Some(Value::Finite(v)) => self.round(v),
+            Some(Value::Infinity { sign }) => self.fmt.infinity(sign),
+            Some(Value::Zero { sign }) => self.fmt.zero(sign),
+            Some(Value::NaN) | None => self.invalid(),
+        }
+    }
+
+    /// Fused multiply-add a × b + c with a single rounding.
+    /// FMSUB, FNMSUB and FNMADD negate the product or the addend.
+    pub fn mul_add(
+        &mut self,
+        a: u64,
+        b: u64,
+        c: u64,
+        negate_product: bool,
+        negate_addend: bool,
+    ) -> u64 {
+        let sign_mask = self.fmt.sign_mask();
+        let a = if negate_product { a ^ sign_mask } else { a };
+        let c = if negate_addend { c ^ sign_mask } else { c };
+        let fmt = self.fmt;
+        if fmt.is_nan(a) || fmt.is_nan(b) || fmt.is_nan(c) {
+            // 0 × ∞ is invalid even if c is a quiet NaN.
+            if !fmt.is_nan(a) && !fmt.is_nan(b) && self.product(a, b).is_none() {
+                return self.invalid();
+            }
+            return self.propagate_nan(&[a, b, c]);
+        }
+        match self.product(a, b) {
+            Some(product) => self.sum(product, fmt.unpack(c)),
+            None => self.invalid(),
+        }
+    }
+
+    pub fn div(&mut self, a: u64, b: u64) -> u64 {
+        use Value::*;
+        if self.fmt.is_nan(a) || self.fmt.is_nan(b) {
+            return self.propagate_nan(&[a, b]);
+        }
+        let sign = self.fmt.sign(a) != self.fmt.sign(b);
+        match (self.fmt.unpack(a), self.fmt.unpack(b)) {
+            (Infinity { .. }, Infinity { .. }) | (Zero { .. }, Zero { .. }) => self.invalid(),
+            (Infinity { .. }, _) => self.fmt.infinity(sign),
+            (_, Infinity { .. }) | (Zero { .. }, _) => self.fmt.zero(sign),
+            (Finite(_), Zero { .. }) => {
+                self.flags |= Fflags::DZ;
+                self.fmt.infinity(sign)
+            }
+            (Finite(x), Finite(y)) => {
+                // With both significands normalized, the quotient has at least p + 2 bits.
+                let p = self.fmt.precision();
+                let (x, y) = (x.normalized(p), y.normalized(p));
+                let shift = p as i32 + 2;
+                let n = x.sig << shift;
+                let (q, r) = (n / y.sig, n % y.sig);
+                self.round(Exact {
+                    sign,
+                    exp: x.exp - y.exp - shift - 1,
+                    sig: q << 1 | (r != 0) as u128,
+                })
+            }
+            (NaN, _) | (_, NaN) => unreachable!(),
+        }
+    }
+
+    pub fn sqrt(&mut self, a: u64) -> u64 {
+        match self.fmt.unpack(a) {
+            Value::NaN => self.propagate_nan(&[a]),
+            Value::Zero { .. } | Value::Infinity { sign: false } => a,
+            Value::Infinity { sign: true } | Value::Finite(Exact { sign: true, .. }) => {
+                self.invalid()
+            }
+            Value::Finite(x) => {
+                // Scale the significand to get at least p + 2 bits of the root with an even exponent.
+                let p = self.fmt.precision();
+                let x = x.normalized(p);
+                let mut shift = p as i32 + 5;
+                if (x.exp - shift).rem_euclid(2) != 0 {
+                    shift += 1;
+                }
+                let n = x.sig << shift;
+                let root = isqrt(n);
+                self.round(Exact {
+                    sign: false,
+                    exp: (x.exp - shift) / 2 - 1,
+                    sig: root << 1 | (root * root != n) as u128,
+                })
+            }
+        }
+    }
+
+    /// Minimum with -0 less than +0. If only one operand is NaN, the other is returned.
+    pub fn min(&mut self, a: u64, b: u64) -> u64 {
+        self.min_max(a, b, true)
+    }
+
+    /// Maximum with -0 less than +0. If only one operand is NaN, the other is returned.
+    pub fn max(&mut self, a: u64, b: u64) -> u64 {
+        self.min_max(a, b, false)
+    }
+
+    fn min_max(&mut self, a: u64, b: u64, min: bool) -> u64 {
+        let fmt = self.fmt;
+        if fmt.is_signaling_nan(a) || fmt.is_signaling_nan(b) {
+            self.flags |= Fflags::NV;
+        }
+        match (fmt.is_nan(a), fmt.is_nan(b)) {
+            (true, true) => fmt.canonical_nan(),
+            (true, false) => b,
+            (false, true) => a,
+            (false, false) => {
+                let a_is_less = fmt.order_key(a, true) < fmt.order_key(b, true);
+                if a_is_less == min {
+                    a
+                } else {
+                    b
+                }
+            }
+        }
+    }
+
+    /// Quiet comparison a == b. Only signaling NaNs raise invalid operation.
+    pub fn eq(&mut self, a: u64, b: u64) -> bool {
+        let fmt = self.fmt;
+        if fmt.is_nan(a) || fmt.is_nan(b) {
+            self.propagate_nan(&[a, b]);
+            return false;
+        }
+        fmt.order_key(a, false) == fmt.order_key(b, false)
+    }
+
+    /// Signaling comparison a < b. Any NaN raises invalid operation.
+    pub fn lt(&mut self, a: u64, b: u64) -> bool {
+        self.compare(a, b).map_or(false, |(a, b)| a < b)
+    }
+
+    /// Signaling comparison a <= b. Any NaN raises invalid operation.
+    pub fn le(&mut self, a: u64, b: u64) -> bool {
+        self.compare(a, b).map_or(false, |(a, b)| a <= b)
+    }
+
+    fn compare(&mut self, a: u64, b: u64) -> Option<(i128, i128)> {
+        let fmt = self.fmt;
+        if fmt.is_nan(a) || fmt.is_nan(b) {
+            self.flags |= Fflags::NV;
+            return None;
+        }
+        Some((fmt.order_key(a, false), fmt.order_key(b, false)))
+    }
+
+    /// Class mask of FCLASS. Exactly one bit is set.
+    pub fn classify(&self, a: u64) -> u32 {
+        let fmt = self.fmt;
+        let sign = fmt.sign(a);
+        let bit = match fmt.unpack(a) {
+            Value::Infinity { .. } => {
+                if sign {
+                    0
+                } else {
+                    7
+                }
+            }
+            Value::Finite(x) if x.sig >> fmt.frac_bits != 0 => {
+                if sign {
+                    1
+                } else {
+                    6
+                }
+            }
+            Value::Finite(_) => {
+                if sign {
+                    2
+                } else {
+                    5
+                }
+            }
+            Value::Zero { .. } => {
+                if sign {
+                    3
+                } else {
+                    4
+                }
+            }
+            Value::NaN if fmt.is_signaling_nan(a) => 8,
+            Value::NaN => 9,
+        };
+        1 << bit
+    }
+
+    /// Convert to signed 32-bit integer.
+    /// NaN and out of range values raise invalid operation and saturate. NaN converts to the maximum.
+    pub fn float_to_i32(&mut self, a: u64) -> u32 {
+        self.float_to_int(a, i32::MIN as i64, i32::MAX as i64) as u32
+    }
+
+    /// Convert to unsigned 32-bit integer.
+    /// NaN and out of range values raise invalid operation and saturate. NaN converts to the maximum.
+    pub fn float_to_u32(&mut self, a: u64) -> u32 {
+        self.float_to_int(a, 0, u32::MAX as i64) as u32
+    }
+
+    fn float_to_int(&mut self, a: u64, min: i64, max: i64) -> i64 {
+        let saturated = match self.fmt.unpack(a) {
+            Value::NaN | Value::Infinity { sign: false } => max,
+            Value::Infinity { sign: true } => min,
+            Value::Zero { .. } => return 0,
+            // Magnitudes of 2^33 or more are out of range of any 32-bit integer.
+            Value::Finite(x) if x.top() > 33 => {
+                if x.sign {
+                    min
+                } else {
+                    max
+                }
+            }
+            Value::Finite(x) => {
+                let (magnitude, inexact) = self.shift_round(x.sign, x.sig, -x.exp);
+                let v = if x.sign {
+                    -(magnitude as i64)
+                } else {
+                    magnitude as i64
+                };
+                if (min..=max).contains(&v) {
+                    if inexact {
+                        self.flags |= Fflags::NX;
+                    }
+                    return v;
+                }
+                if x.sign {
+                    min
+                } else {
+                    max
+                }
+            }
+        };
+        self.flags |= Fflags::NV;
+        saturated
+    }
+
+    /// Convert a value of format from into the format of the Fpu(FCVT.S.D, FCVT.D.S).
+    pub fn convert(&mut self, from: Format, a: u64) -> u64 {
+        match from.unpack(a) {
+            Value::NaN => {
+                if from.is_signaling_nan(a) {
+                    self.flags |= Fflags::NV;
+                }
+                self.fmt.canonical_nan()
+            }
+            Value::Infinity { sign } => self.fmt.infinity(sign),
+            Value::Zero { sign } => self.fmt.zero(sign),
+            Value::Finite(x) => self.round(x),
+        }
+    }
+
+    /// Convert signed 32-bit integer.
+    pub fn i32_to_float(&mut self, v: i32) -> u64 {
+        self.int_to_float(v < 0, v.unsigned_abs())
+    }
+
+    /// Convert unsigned 32-bit integer.
+    pub fn u32_to_float(&mut self, v: u32) -> u64 {
+        self.int_to_float(false, v)
+    }
+
+    fn int_to_float(&mut self, sign: bool, magnitude: u32) -> u64 {
+        if magnitude == 0 {
+            return self.fmt.zero(false);
+        }
+        self.round(Exact {
+            sign,
+            exp: 0,
+            sig: magnitude as u128,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RoundingMode::*;
+
+    const F: Format = Format::SINGLE;
+
+    fn bits(v: f32) -> u64 {
+        v.to_bits() as u64
+    }
+
+    /// Run op in the rounding mode and return the result with the raised flags.
+    fn with<T>(rm: RoundingMode, op: impl FnOnce(&mut Fpu) -> T) -> (T, u32) {
+        let mut fpu = Fpu::new(F, rm);
+        let v = op(&mut fpu);
+        (v, fpu.flags)
+    }
+
+    const QNAN: u64 = 0x7fc0_0000;
+    const SNAN: u64 = 0x7f80_0001;
+    const MIN_SUBNORMAL: u64 = 1;
+    const MIN_NORMAL: u64 = 0x0080_0000;
+
+    #[test]
+    fn arithmetic_matches_host() {
+        let values = [
+            0.0f32,
+            -0.0,
+            1.0,
+            -1.5,
+            3.0,
+            0.1,
+            1e-40,
+            -3.4e38,
+            f32::MAX,
+            f32::MIN_POSITIVE,
+            f32::EPSILON,
+            7.0e-45,
+            123456.79,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+        ];
+        for &a in &values {
+            for &b in &values {
+                let mut fpu = Fpu::new(F, NearestEven);
+                let cases = [
+                    (fpu.add(bits(a), bits(b)), a + b, "add"),
+                    (fpu.sub(bits(a), bits(b)), a - b, "sub"),
+                    (fpu.mul(bits(a), bits(b)), a * b, "mul"),
+                    (fpu.div(bits(a), bits(b)), a / b, "div"),
+                    (
+                        fpu.mul_add(bits(a), bits(b), bits(a), false, false),
+                        a.mul_add(b, a),
+                        "fma",
+                    ),
+                ];
+                for (actual, expected, op) in cases {
+                    let expected = if expected.is_nan() {
+                        QNAN
+                    } else {
+                        bits(expected)
+                    };
+                    assert_eq!(actual, expected, "{a:e} {op} {b:e}");
+                }
+            }
+            let expected = if a.sqrt().is_nan() {
+                QNAN
+            } else {
+                bits(a.sqrt())
+            };
+            assert_eq!(
+                Fpu::new(F, NearestEven).sqrt(bits(a)),
+                expected,
+                "sqrt {a:e}"
+            );
+        }
+    }
+
+    #[test]
+    fn double_arithmetic_matches_host() {
+        let values = [
+            0.0f64,
+            -0.0,
+            1.0,
+            -1.5,
+            0.1,
+            1e-310,
+            -1.7e308,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+            f64::EPSILON,
+            5e-324,
+            123456789.123,
+            f64::INFINITY,
+        ];
+        let bits = |v: f64| v.to_bits();
+        let nan_or_bits = |v: f64| {
+            if v.is_nan() {
+                0x7ff8_0000_0000_0000
+            } else {
+                v.to_bits()
+            }
+        };
+        for &a in &values {
+            for &b in &values {
+                let mut fpu = Fpu::new(Format::DOUBLE, NearestEven);
+                let cases = [
+                    (fpu.add(bits(a), bits(b)), a + b, "add"),
+                    (fpu.sub(bits(a), bits(b)), a - b, "sub"),
+                    (fpu.mul(bits(a), bits(b)), a * b, "mul"),
+                    (fpu.div(bits(a), bits(b)), a / b, "div"),
+                    (
+                        fpu.mul_add(bits(a), bits(b), bits(b), true, false),
+                        (-a).mul_add(b, b),
+                        "fnmsub",
+                    ),
+                ];
+                for (actual, expected, op) in cases {
+                    assert_eq!(actual, nan_or_bits(expected), "{a:e} {op} {b:e}");
+                }
+            }
+            assert_eq!(
+                Fpu::new(Format::DOUBLE, NearestEven).sqrt(bits(a)),
+                nan_or_bits(a.sqrt()),
+                "sqrt {a:e}"
+            );
+            let mut fpu = Fpu::new(F, NearestEven);
+            assert_eq!(
+                fpu.convert(Format::DOUBLE, bits(a)),
+                self::bits(a as f32),
+                "{a:e} as f32"
+            );
+        }
+    }
+
+    #[test]
+    fn nan_boxing() {
+        assert_eq!(F.nan_box(bits(1.0)), 0xffff_ffff_3f80_0000);
+        assert_eq!(F.unbox(0xffff_ffff_3f80_0000), bits(1.0));
+        assert_eq!(F.unbox(0xfffe_ffff_3f80_0000), QNAN);
+        assert_eq!(Format::DOUBLE.nan_box(1.0f64.to_bits()), 1.0f64.to_bits());
+        assert_eq!(
+            Format::DOUBLE.unbox(F.nan_box(bits(1.0))),
+            F.nan_box(bits(1.0))
+        );
+    }
+
+    #[test]
+    fn format_conversions() {
+        let d = |v: f64| v.to_bits();
+        // Single to double is exact.
+        let mut fpu = Fpu::new(Format::DOUBLE, NearestEven);
+        assert_eq!(fpu.convert(F, MIN_SUBNORMAL), d(f32::from_bits(1) as f64));
+        assert_eq!(fpu.convert(F, bits(-0.1)), d(-0.1f32 as f64));
+        assert_eq!(fpu.convert(F, QNAN), 0x7ff8_0000_0000_0000);
+        assert_eq!(fpu.flags, 0);
+        assert_eq!(fpu.convert(F, SNAN), 0x7ff8_0000_0000_0000);
+        assert_eq!(fpu.flags, Fflags::NV);
+
+        assert_eq!(
+            with(NearestEven, |f| f.convert(Format::DOUBLE, d(1e300))),
+            (bits(f32::INFINITY), Fflags::OF | Fflags::NX)
+        );
+        assert_eq!(
+            with(TowardZero, |f| f.convert(Format::DOUBLE, d(-1e300))),
+            (bits(-f32::MAX), Fflags::OF | Fflags::NX)
+        );
+        assert_eq!(
+            with(Up, |f| f.convert(Format::DOUBLE, d(1e-300))),
+            (MIN_SUBNORMAL, Fflags::UF | Fflags::NX)
+        );
+        assert_eq!(
+            with(NearestEven, |f| f.convert(Format::DOUBLE, d(0.1))),
+            (bits(0.1), Fflags::NX)
+        );
+        assert_eq!(
+            with(Down, |f| f.convert(Format::DOUBLE, d(0.1))),
+            (bits(0.1) - 1, Fflags::NX)
+        );
+        // Conversions from words to double are exact.
+        let mut fpu = Fpu::new(Format::DOUBLE, NearestEven);
+        assert_eq!(fpu.u32_to_float(u32::MAX), d(u32::MAX as f64));
+        assert_eq!(fpu.i32_to_float(i32::MIN), d(i32::MIN as f64));
+        assert_eq!(fpu.float_to_i32(d(-2147483648.5)), i32::MIN as u32);
+        assert_eq!(fpu.flags, Fflags::NX);
+    }
+
+    #[test]
+    fn rounding_modes() {
+        // 1 + 2^-24 is a tie between 1 and 1 + 2^-23.
+        let tie = |rm| with(rm, |f| f.add(bits(1.0), bits(f32::EPSILON / 2.0)));
+        assert_eq!(tie(NearestEven), (bits(1.0), Fflags::NX));
+        assert_eq!(
+            tie(NearestMaxMagnitude),
+            (bits(1.0 + f32::EPSILON), Fflags::NX)
+        );
+        assert_eq!(tie(Up), (bits(1.0 + f32::EPSILON), Fflags::NX));
+        assert_eq!(tie(TowardZero), (bits(1.0), Fflags::NX));
+
+        // -1 - 2^-30 rounds away from zero only when rounding down.
+        let below = |rm| with(rm, |f| f.sub(bits(-1.0), bits(2f32.powi(-30)))).0;
+        assert_eq!(below(Down), bits(-1.0 - f32::EPSILON));
+        assert_eq!(below(Up), bits(-1.0));
+        assert_eq!(below(NearestEven), bits(-1.0));
+
+        // x - x is -0 only when rounding down.
+        assert_eq!(with(Down, |f| f.sub(bits(2.0), bits(2.0))).0, bits(-0.0));
+        assert_eq!(with(NearestEven, |f| f.sub(bits(2.0), bits(2.0))).0, 0);
+    }
+
+    #[test]
+    fn exception_flags() {
+        let of = Fflags::OF | Fflags::NX;
+        assert_eq!(
+            with(NearestEven, |f| f.mul(bits(f32::MAX), bits(2.0))),
+            (bits(f32::INFINITY), of)
+        );
+        assert_eq!(
+            with(TowardZero, |f| f.mul(bits(f32::MAX), bits(2.0))),
+            (bits(f32::MAX), of)
+        );
+        assert_eq!(
+            with(Up, |f| f.mul(bits(-f32::MAX), bits(2.0))),
+            (bits(-f32::MAX), of)
+        );
+        assert_eq!(
+            with(NearestEven, |f| f.div(bits(1.0), 0)),
+            (bits(f32::INFINITY), Fflags::DZ)
+        );
+        assert_eq!(with(NearestEven, |f| f.div(0, 0)), (QNAN, Fflags::NV));
+        assert_eq!(
+            with(NearestEven, |f| f.sqrt(bits(-1.0))),
+            (QNAN, Fflags::NV)
+        );
+        assert_eq!(with(NearestEven, |f| f.sqrt(bits(-0.0))), (bits(-0.0), 0));
+        assert_eq!(
+            with(NearestEven, |f| f
+                .add(bits(f32::INFINITY), bits(f32::NEG_INFINITY))),
+            (QNAN, Fflags::NV)
+        );
+
+        // Exact subnormal results do not underflow.
+        assert_eq!(
+            with(NearestEven, |f| f.sub(MIN_NORMAL + 1, MIN_NORMAL)),
+            (MIN_SUBNORMAL, 0)
+        );
+        let uf = Fflags::UF | Fflags::NX;
+        assert_eq!(
+            with(NearestEven, |f| f.div(MIN_SUBNORMAL, bits(2.0))),
+            (0, uf)
+        );
+        assert_eq!(
+            with(Up, |f| f.div(MIN_SUBNORMAL, bits(2.0))),
+            (MIN_SUBNORMAL, uf)
+        );
+        // Tininess after rounding: (2^-126 - 2^-150) rounds to 2^-126 with unbounded exponent.
+        let x = MIN_NORMAL - 1;
+        assert_eq!(
+            with(NearestEven, |f| f.mul(x, bits(1.0 + f32::EPSILON))),
+            (MIN_NORMAL, Fflags::NX)
+        );
+        assert_eq!(
+            with(TowardZero, |f| f.mul(x, bits(1.0 + f32::EPSILON))),
+            (x, uf)
+        );
+    }
+
+    #[test]
+    fn nan_handling() {
+        assert_eq!(with(NearestEven, |f| f.add(QNAN, bits(1.0))), (QNAN, 0));
+        assert_eq!(
+            with(NearestEven, |f| f.add(SNAN, bits(1.0))),
+            (QNAN, Fflags::NV)
+        );
+        // Payloads are not propagated.
+        assert_eq!(
+            with(NearestEven, |f| f.mul(0xffc0_1234, bits(1.0))),
+            (QNAN, 0)
+        );
+        // 0 × ∞ + qNaN is invalid.
+        assert_eq!(
+            with(NearestEven, |f| f.mul_add(
+                0,
+                bits(f32::INFINITY),
+                QNAN,
+                false,
+                false
+            )),
+            (QNAN, Fflags::NV)
+        );
+        assert_eq!(
+            with(NearestEven, |f| f.mul_add(
+                bits(1.0),
+                bits(2.0),
+                QNAN,
+                false,
+                false
+            )),
+            (QNAN, 0)
+        );
+
+        assert_eq!(
+            with(NearestEven, |f| f.min(QNAN, bits(1.0))),
+            (bits(1.0), 0)
+        );
+        assert_eq!(
+            with(NearestEven, |f| f.max(SNAN, bits(1.0))),
+            (bits(1.0), Fflags::NV)
+        );
+        assert_eq!(with(NearestEven, |f| f.min(SNAN, QNAN)), (QNAN, Fflags::NV));
+        assert_eq!(with(NearestEven, |f| f.min(bits(-0.0), 0)), (bits(-0.0), 0));
+        assert_eq!(with(NearestEven, |f| f.max(bits(-0.0), 0)), (0, 0));
+
+        assert_eq!(with(NearestEven, |f| f.eq(QNAN, QNAN)), (false, 0));
+        assert_eq!(with(NearestEven, |f| f.eq(SNAN, QNAN)), (false, Fflags::NV));
+        assert_eq!(
+            with(NearestEven, |f| f.lt(QNAN, bits(1.0))),
+            (false, Fflags::NV)
+        );
+        assert_eq!(with(NearestEven, |f| f.eq(bits(-0.0), 0)), (true, 0));
+        assert_eq!(with(NearestEven, |f| f.le(bits(-0.0), 0)), (true, 0));
+        assert_eq!(with(NearestEven, |f| f.lt(bits(-0.0), 0)), (false, 0));
+        assert_eq!(
+            with(NearestEven, |f| f.lt(bits(-2.0), bits(-1.0))),
+            (true, 0)
+        );
+    }
+
+    #[test]
+    fn fused_multiply_add() {
+        // (1 + 2^-23)(1 - 2^-23) - 1 = -2^-46 is lost without fusing.
+        let a = bits(1.0 + f32::EPSILON);
+        let b = bits(1.0 - f32::EPSILON);
+        assert_eq!(
+            with(NearestEven, |f| f.mul_add(a, b, bits(1.0), false, true)),
+            (bits(-(2f32.powi(-46))), 0)
+        );
+        // fnmadd: -(2 × 3) - 1
+        assert_eq!(
+            with(NearestEven, |f| f.mul_add(
+                bits(2.0),
+                bits(3.0),
+                bits(1.0),
+                true,
+                true
+            ))
+            .0,
+            bits(-7.0)
+        );
+        // Exact zero sum of product and addend
+        assert_eq!(
+            with(Down, |f| f.mul_add(
+                bits(2.0),
+                bits(3.0),
+                bits(-6.0),
+                false,
+                false
+            ))
+            .0,
+            bits(-0.0)
+        );
+        // Tiny addend far below the product only affects rounding.
+        assert_eq!(
+            with(Up, |f| f.mul_add(
+                bits(1.0),
+                bits(1.0),
+                MIN_SUBNORMAL,
+                false,
+                false
+            )),
+            (bits(1.0 + f32::EPSILON), Fflags::NX)
+        );
+        assert_eq!(
+            with(TowardZero, |f| f.mul_add(
+                bits(1.0),
+                bits(1.0),
+                MIN_SUBNORMAL | 1 << 31,
+                false,
+                false
+            )),
+            (bits(1.0 - f32::EPSILON / 2.0), Fflags::NX)
+        );
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(
+            with(NearestEven, |f| f.float_to_i32(bits(-2.5))),
+            (-2i32 as u32, Fflags::NX)
+        );
+        assert_eq!(
+            with(NearestMaxMagnitude, |f| f.float_to_i32(bits(-2.5))),
+            (-3i32 as u32, Fflags::NX)
+        );
+        assert_eq!(with(Down, |f| f.float_to_i32(bits(2.5))), (2, Fflags::NX));
+        assert_eq!(
+            with(NearestEven, |f| f.float_to_i32(bits(-2147483648.0))),
+            (0x8000_0000, 0)
+        );
+        assert_eq!(
+            with(NearestEven, |f| f.float_to_i32(bits(2147483648.0))),
+            (0x7fff_ffff, Fflags::NV)
+        );
+        assert_eq!(
+            with(NearestEven, |f| f.float_to_i32(QNAN)),
+            (0x7fff_ffff, Fflags::NV)
+        );
+        assert_eq!(
+            with(NearestEven, |f| f.float_to_i32(bits(f32::NEG_INFINITY))),
+            (0x8000_0000, Fflags::NV)
+        );
+        assert_eq!(
+            with(NearestEven, |f| f.float_to_u32(bits(4294967040.0))),
+            (0xffff_ff00, 0)
+        );
+        assert_eq!(
+            with(NearestEven, |f| f.float_to_u32(bits(-1.0))),
+            (0, Fflags::NV)
+        );
+        // Rounds to zero which is in range.
+        assert_eq!(
+            with(NearestEven, |f| f.float_to_u32(bits(-0.25))),
+            (0, Fflags::NX)
+        );
+        assert_eq!(
+            with(NearestEven, |f| f.float_to_u32(bits(1e20))),
+            (u32::MAX, Fflags::NV)
+        );
+
+        assert_eq!(with(NearestEven, |f| f.i32_to_float(-1)), (bits(-1.0), 0));
+        assert_eq!(
+            with(NearestEven, |f| f.i32_to_float(i32::MIN)),
+            (bits(-2147483648.0), 0)
+        );
+        assert_eq!(
+            with(NearestEven, |f| f.i32_to_float(0x0100_0001)),
+            (bits(16777216.0), Fflags::NX)
+        );
+        assert_eq!(
+            with(Up, |f| f.u32_to_float(u32::MAX)),
+            (bits(4294967296.0), Fflags::NX)
+        );
+        assert_eq!(
+            with(TowardZero, |f| f.u32_to_float(u32::MAX)),
+            (bits(4294967040.0), Fflags::NX)
+        );
+    }
+
+    #[test]
+    fn classify_and_sign_injection() {
+        let fpu = Fpu::new(F, NearestEven);
+        let classes = [
+            bits(f32::NEG_INFINITY),
+            bits(-1.0),
+            MIN_SUBNORMAL | 1 << 31,
+            bits(-0.0),
+            0,
+            MIN_SUBNORMAL,
+            bits(1.0),
+            bits(f32::INFINITY),
+            SNAN,
+            QNAN,
+        ];
+        for (i, v) in classes.into_iter().enumerate() {
+            assert_eq!(fpu.classify(v), 1 << i, "{v:#x}");
+        }
+
+        assert_eq!(F.copy_sign(bits(1.0), bits(-2.0)), bits(-1.0));
+        assert_eq!(F.copy_sign_negated(bits(1.0), bits(-2.0)), bits(1.0));
+        assert_eq!(F.xor_sign(bits(-1.0), bits(-2.0)), bits(1.0));
+        // NaNs are not canonicalized.
+        assert_eq!(F.copy_sign(SNAN, bits(-1.0)), SNAN | 1 << 31);
+    }
+}
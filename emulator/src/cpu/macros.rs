@@ -1,3 +1,4 @@
+/// Add sign-extended immediate to register value with wrapping 32-bit arithmetic.
 macro_rules! add_imm_signed {
     ($r:expr, $imm:expr) => {
         (($r as i64) + ($imm as i64)) as u32
@@ -0,0 +1,530 @@
+use super::{
+    csr::{Csr, Satp},
+    pmp::Pmp,
+    trap::{Exception, Trap},
+    Mode,
+};
+use crate::bus::interface::{BusRead, BusWrite};
+
+/// Kind of memory access to translate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Fetch,
+    Load,
+    /// Also used by AMOs
+    Store,
+}
+
+impl Access {
+    fn page_fault(self, vaddr: u32) -> Trap {
+        let cause = match self {
+            Access::Fetch => Exception::InstructionPageFault,
+            Access::Load => Exception::LoadPageFault,
+            Access::Store => Exception::StorePageFault,
+        };
+        Trap::new(cause, vaddr)
+    }
+
+    pub fn access_fault(self, vaddr: u32) -> Trap {
+        let cause = match self {
+            Access::Fetch => Exception::InstructionAccessFault,
+            Access::Load => Exception::LoadAccessFault,
+            Access::Store => Exception::StoreAccessFault,
+        };
+        Trap::new(cause, vaddr)
+    }
+}
+
+/// State of the hart which affects address translation.
+#[derive(Debug, Clone, Copy)]
+pub struct Context<'a> {
+    /// Effective privilege mode of the access
+    pub mode: Mode,
+    pub satp: Satp,
+    /// mstatus.SUM
+    pub sum: bool,
+    /// mstatus.MXR
+    pub mxr: bool,
+    /// Page table accesses are checked by PMP.
+    pub pmp: &'a Pmp,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(mode: Mode, csr: &'a Csr) -> Self {
+        Self {
+            mode,
+            satp: csr.satp,
+            sum: csr.mstatus.sum(),
+            mxr: csr.mstatus.mxr(),
+            pmp: &csr.pmp,
+        }
+    }
+
+    /// Translation applies below M-mode when satp.MODE is Sv32.
+    fn translates(&self) -> bool {
+        self.mode != Mode::M && self.satp.sv32()
+    }
+}
+
+/// Sv32 page table entry
+/// Volume II: RISC-V Privileged Architectures V20211203 P80
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Pte(u32);
+
+impl Pte {
+    const V: u32 = 1 << 0;
+    const R: u32 = 1 << 1;
+    const W: u32 = 1 << 2;
+    const X: u32 = 1 << 3;
+    const U: u32 = 1 << 4;
+    const G: u32 = 1 << 5;
+    const A: u32 = 1 << 6;
+    const D: u32 = 1 << 7;
+
+    fn has(self, bits: u32) -> bool {
+        self.0 & bits == bits
+    }
+
+    fn ppn(self) -> u32 {
+        self.0 >> 10
+    }
+
+    fn is_leaf(self) -> bool {
+        self.0 & (Self::R | Self::X) != 0
+    }
+
+    /// Return whether the access requires setting A or D.
+    fn needs_update(self, access: Access) -> bool {
+        !self.has(Self::A) || access == Access::Store && !self.has(Self::D)
+    }
+
+    /// Return whether the leaf PTE permits the access in the context.
+    fn permits(self, ctx: &Context, access: Access) -> bool {
+        let allowed = match access {
+            Access::Fetch => self.has(Self::X),
+            Access::Load => self.has(Self::R) || ctx.mxr && self.has(Self::X),
+            Access::Store => self.has(Self::W),
+        };
+        // S-mode may access user pages only for loads and stores with SUM set.
+        let privileged = match ctx.mode {
+            Mode::U => self.has(Self::U),
+            Mode::S => !self.has(Self::U) || ctx.sum && access != Access::Fetch,
+            Mode::M => true,
+        };
+        allowed && privileged
+    }
+}
+
+/// Cached leaf PTE for a 4 KiB virtual page. Superpages are cached per 4 KiB page.
+#[derive(Debug, Clone, Copy)]
+struct TlbEntry {
+    vpn: u32,
+    asid: u32,
+    pte: Pte,
+    /// Physical address of the PTE to write A and D back.
+    pte_addr: u32,
+    /// Mapped by a level 1 PTE(4 MiB megapage)
+    superpage: bool,
+}
+
+impl TlbEntry {
+    fn matches(&self, vpn: u32, asid: u32) -> bool {
+        self.vpn == vpn && (self.asid == asid || self.pte.has(Pte::G))
+    }
+
+    /// Physical address of vaddr. None if it is beyond the 32-bit physical address space.
+    fn paddr(&self, vaddr: u32) -> Option<u32> {
+        let ppn = self.pte.ppn() as u64;
+        let paddr = if self.superpage {
+            (ppn >> 10) << 22 | (vaddr & 0x3f_ffff) as u64
+        } else {
+            ppn << 12 | (vaddr & 0xfff) as u64
+        };
+        u32::try_from(paddr).ok()
+    }
+}
+
+/// Sv32 memory management unit with a direct-mapped software TLB.
+/// Volume II: RISC-V Privileged Architectures V20211203 P79
+#[derive(Debug)]
+pub struct Mmu {
+    tlb: [Option<TlbEntry>; Self::TLB_SIZE],
+    /// Set A and D bits in PTEs on access. Otherwise accesses which require them raise page faults.
+    pub hardware_ad_update: bool,
+}
+
+impl Mmu {
+    const TLB_SIZE: usize = 64;
+
+    pub fn new() -> Self {
+        Self {
+            tlb: [None; Self::TLB_SIZE],
+            hardware_ad_update: false,
+        }
+    }
+
+    fn slot(vaddr: u32) -> usize {
+        (vaddr >> 12) as usize % Self::TLB_SIZE
+    }
+
+    fn cached(&self, ctx: &Context, vaddr: u32) -> Option<TlbEntry> {
+        self.tlb[Self::slot(vaddr)].filter(|entry| entry.matches(vaddr >> 12, ctx.satp.asid()))
+    }
+
+    /// Translate vaddr without filling the TLB or updating PTEs.
+    pub fn probe<B: BusRead>(
+        &self,
+        bus: &B,
+        ctx: &Context,
+        vaddr: u32,
+        access: Access,
+    ) -> Result<u32, Trap> {
+        if !ctx.translates() {
+            return Ok(vaddr);
+        }
+        let entry = match self.cached(ctx, vaddr) {
+            Some(entry) => entry,
+            None => walk(bus, ctx, vaddr, access)?,
+        };
+        self.check(&entry, ctx, vaddr, access)?;
+        entry.paddr(vaddr).ok_or(access.access_fault(vaddr))
+    }
+
+    /// Translate vaddr into a physical address.
+    /// A and D bits are updated on the in-memory PTE, so the walk is redone if the cached PTE lacks them.
+    pub fn translate<B: BusRead + BusWrite>(
+        &mut self,
+        bus: &mut B,
+        ctx: &Context,
+        vaddr: u32,
+        access: Access,
+    ) -> Result<u32, Trap> {
+        if !ctx.translates() {
+            return Ok(vaddr);
+        }
+        let mut entry = match self.cached(ctx, vaddr) {
+            Some(entry) if !entry.pte.needs_update(access) => entry,
+            _ => walk(bus, ctx, vaddr, access)?,
+        };
+        self.check(&entry, ctx, vaddr, access)?;
+        if entry.pte.needs_update(access) {
+            let bits = match access {
+                Access::Store => Pte::A | Pte::D,
+                _ => Pte::A,
+            };
+            entry.pte = Pte(entry.pte.0 | bits);
+            if !ctx.pmp.permits(entry.pte_addr, 4, Mode::S, Access::Store) {
+                return Err(access.access_fault(vaddr));
+            }
+            bus.write32(entry.pte_addr, entry.pte.0)
+                .map_err(|_| access.access_fault(vaddr))?;
+        }
+        self.tlb[Self::slot(vaddr)] = Some(entry);
+        entry.paddr(vaddr).ok_or(access.access_fault(vaddr))
+    }
+
+    fn check(
+        &self,
+        entry: &TlbEntry,
+        ctx: &Context,
+        vaddr: u32,
+        access: Access,
+    ) -> Result<(), Trap> {
+        if !entry.pte.permits(ctx, access)
+            || entry.pte.needs_update(access) && !self.hardware_ad_update
+        {
+            return Err(access.page_fault(vaddr));
+        }
+        Ok(())
+    }
+
+    /// Invalidate cached translations as SFENCE.VMA does.
+    /// None for vaddr or asid means all addresses or all address spaces.
+    /// Global mappings are not invalidated by asid.
+    pub fn flush(&mut self, vaddr: Option<u32>, asid: Option<u32>) {
+        for slot in self.tlb.iter_mut() {
+            let Some(entry) = slot else { continue };
+            let addr_matches = vaddr.map_or(true, |vaddr| {
+                if entry.superpage {
+                    entry.vpn >> 10 == vaddr >> 22
+                } else {
+                    entry.vpn == vaddr >> 12
+                }
+            });
+            let asid_matches =
+                asid.map_or(true, |asid| entry.asid == asid && !entry.pte.has(Pte::G));
+            if addr_matches && asid_matches {
+                *slot = None;
+            }
+        }
+    }
+}
+
+/// Walk the two-level page table and return the leaf PTE mapping vaddr.
+/// PTE accesses are checked by PMP as S-mode accesses.
+/// Volume II: RISC-V Privileged Architectures V20211203 P82
+fn walk<B: BusRead>(bus: &B, ctx: &Context, vaddr: u32, access: Access) -> Result<TlbEntry, Trap> {
+    let mut table = (ctx.satp.ppn() as u64) << 12;
+    for level in [1, 0] {
+        let vpn = (vaddr >> (12 + 10 * level)) & 0x3ff;
+        let pte_addr =
+            u32::try_from(table + 4 * vpn as u64).map_err(|_| access.access_fault(vaddr))?;
+        if !ctx.pmp.permits(pte_addr, 4, Mode::S, Access::Load) {
+            return Err(access.access_fault(vaddr));
+        }
+        let pte = bus
+            .read32(pte_addr)
+            .map(Pte)
+            .map_err(|_| access.access_fault(vaddr))?;
+        // W without R is reserved.
+        if !pte.has(Pte::V) || pte.has(Pte::W) && !pte.has(Pte::R) {
+            return Err(access.page_fault(vaddr));
+        }
+        if pte.is_leaf() {
+            // Megapages must be aligned to 4 MiB.
+            if level == 1 && pte.ppn() & 0x3ff != 0 {
+                return Err(access.page_fault(vaddr));
+            }
+            return Ok(TlbEntry {
+                vpn: vaddr >> 12,
+                asid: ctx.satp.asid(),
+                pte,
+                pte_addr,
+                superpage: level == 1,
+            });
+        }
+        table = (pte.ppn() as u64) << 12;
+    }
+    // Pointer to the next level at level 0
+    Err(access.page_fault(vaddr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bus::Bus,
+        cpu::csr::{Csr, CsrAddr},
+    };
+
+    const V: u32 = Pte::V;
+    const R: u32 = Pte::R;
+    const W: u32 = Pte::W;
+    const X: u32 = Pte::X;
+    const U: u32 = Pte::U;
+    const G: u32 = Pte::G;
+    const A: u32 = Pte::A;
+    const D: u32 = Pte::D;
+
+    /// Root page table at 0x1000 and a level 0 table at 0x2000 which maps 0x4000_0000 to 0x3000.
+    /// 0x8000_0000 is mapped to 0x0 by a megapage.
+    fn bus_with_page_table(leaf: u32) -> Bus {
+        let mut bus = Bus::new(vec![0; 0x4000]);
+        bus.write32(0x1000 + 4 * 0x100, 2 << 10 | V).unwrap();
+        bus.write32(0x2000, 3 << 10 | leaf).unwrap();
+        bus.write32(0x1000 + 4 * 0x200, V | R | W | X | A | D)
+            .unwrap();
+        // Misaligned megapage
+        bus.write32(0x1000 + 4 * 0x201, 1 << 10 | V | R | A)
+            .unwrap();
+        bus
+    }
+
+    /// Sv32, ASID 1, root page table at 0x1000. PMP grants all accesses.
+    fn csr() -> Csr {
+        let mut csr = Csr::new();
+        csr.write(CsrAddr::Satp as usize, 0x8040_0001).unwrap();
+        csr.write(CsrAddr::Pmpaddr0 as usize, u32::MAX).unwrap();
+        csr.write(CsrAddr::Pmpcfg0 as usize, 0x1f).unwrap();
+        csr
+    }
+
+    fn fault(cause: Exception, vaddr: u32) -> Result<u32, Trap> {
+        Err(Trap::new(cause, vaddr))
+    }
+
+    #[test]
+    fn translate_page_and_megapage() {
+        let mut bus = bus_with_page_table(V | R | W | A | D);
+        let mut mmu = Mmu::new();
+        let csr = csr();
+        let s = Context::new(Mode::S, &csr);
+        assert_eq!(
+            mmu.translate(&mut bus, &s, 0x4000_0abc, Access::Load),
+            Ok(0x3abc)
+        );
+        assert_eq!(
+            mmu.translate(&mut bus, &s, 0x8012_3456, Access::Store),
+            Ok(0x12_3456)
+        );
+        assert_eq!(
+            mmu.translate(&mut bus, &s, 0x8040_0000, Access::Load),
+            fault(Exception::LoadPageFault, 0x8040_0000)
+        );
+        // Unmapped
+        assert_eq!(
+            mmu.translate(&mut bus, &s, 0x4000_1000, Access::Fetch),
+            fault(Exception::InstructionPageFault, 0x4000_1000)
+        );
+
+        // No translation in M-mode or Bare mode
+        assert_eq!(
+            mmu.translate(
+                &mut bus,
+                &Context::new(Mode::M, &csr),
+                0x4000_0abc,
+                Access::Load
+            ),
+            Ok(0x4000_0abc)
+        );
+        let bare = Context {
+            satp: Csr::new().satp,
+            ..s
+        };
+        assert_eq!(
+            mmu.translate(&mut bus, &bare, 0x4000_0abc, Access::Load),
+            Ok(0x4000_0abc)
+        );
+    }
+
+    #[test]
+    fn permission_checks() {
+        let mut mmu = Mmu::new();
+        let mut bus = bus_with_page_table(V | R | A | D);
+        let csr = csr();
+        let s = Context::new(Mode::S, &csr);
+        assert_eq!(
+            mmu.translate(&mut bus, &s, 0x4000_0000, Access::Store),
+            fault(Exception::StorePageFault, 0x4000_0000)
+        );
+        assert_eq!(
+            mmu.translate(&mut bus, &s, 0x4000_0000, Access::Fetch),
+            fault(Exception::InstructionPageFault, 0x4000_0000)
+        );
+        // Supervisor pages are not accessible from U-mode.
+        assert_eq!(
+            mmu.translate(
+                &mut bus,
+                &Context::new(Mode::U, &csr),
+                0x4000_0000,
+                Access::Load
+            ),
+            fault(Exception::LoadPageFault, 0x4000_0000)
+        );
+
+        // User pages are accessible from S-mode only for loads and stores with SUM.
+        let mut bus = bus_with_page_table(V | R | X | U | A | D);
+        let mut mmu = Mmu::new();
+        assert!(mmu
+            .translate(
+                &mut bus,
+                &Context::new(Mode::U, &csr),
+                0x4000_0000,
+                Access::Fetch
+            )
+            .is_ok());
+        assert_eq!(
+            mmu.translate(&mut bus, &s, 0x4000_0000, Access::Load),
+            fault(Exception::LoadPageFault, 0x4000_0000)
+        );
+        let sum = Context { sum: true, ..s };
+        assert!(mmu
+            .translate(&mut bus, &sum, 0x4000_0000, Access::Load)
+            .is_ok());
+        assert_eq!(
+            mmu.translate(&mut bus, &sum, 0x4000_0000, Access::Fetch),
+            fault(Exception::InstructionPageFault, 0x4000_0000)
+        );
+
+        // Execute-only pages are readable with MXR.
+        let mut bus = bus_with_page_table(V | X | A | D);
+        let mut mmu = Mmu::new();
+        assert_eq!(
+            mmu.translate(&mut bus, &s, 0x4000_0000, Access::Load),
+            fault(Exception::LoadPageFault, 0x4000_0000)
+        );
+        let mxr = Context { mxr: true, ..s };
+        assert!(mmu
+            .translate(&mut bus, &mxr, 0x4000_0000, Access::Load)
+            .is_ok());
+
+        // W without R is reserved.
+        let mut bus = bus_with_page_table(V | W | A | D);
+        assert_eq!(
+            Mmu::new().translate(&mut bus, &s, 0x4000_0000, Access::Store),
+            fault(Exception::StorePageFault, 0x4000_0000)
+        );
+    }
+
+    #[test]
+    fn accessed_and_dirty_bits() {
+        let csr = csr();
+        let s = Context::new(Mode::S, &csr);
+        let mut bus = bus_with_page_table(V | R | W);
+        let mut mmu = Mmu::new();
+        assert_eq!(
+            mmu.translate(&mut bus, &s, 0x4000_0000, Access::Load),
+            fault(Exception::LoadPageFault, 0x4000_0000)
+        );
+
+        mmu.hardware_ad_update = true;
+        assert_eq!(mmu.probe(&bus, &s, 0x4000_0000, Access::Load), Ok(0x3000));
+        assert_eq!(bus.read32(0x2000).unwrap(), 3 << 10 | V | R | W);
+        mmu.translate(&mut bus, &s, 0x4000_0000, Access::Load)
+            .unwrap();
+        assert_eq!(bus.read32(0x2000).unwrap(), 3 << 10 | V | R | W | A);
+        mmu.translate(&mut bus, &s, 0x4000_0000, Access::Store)
+            .unwrap();
+        assert_eq!(bus.read32(0x2000).unwrap(), 3 << 10 | V | R | W | A | D);
+    }
+
+    #[test]
+    fn tlb_is_flushed_by_sfence() {
+        let csr = csr();
+        let s = Context::new(Mode::S, &csr);
+        let mut bus = bus_with_page_table(V | R | A);
+        let mut mmu = Mmu::new();
+        assert_eq!(
+            mmu.translate(&mut bus, &s, 0x4000_0000, Access::Load),
+            Ok(0x3000)
+        );
+
+        // Stale translation is used until SFENCE.VMA.
+        bus.write32(0x2000, 4 << 10 | V | R | A).unwrap();
+        assert_eq!(
+            mmu.translate(&mut bus, &s, 0x4000_0000, Access::Load),
+            Ok(0x3000)
+        );
+        mmu.flush(Some(0x4000_1000), None);
+        mmu.flush(None, Some(2));
+        assert_eq!(
+            mmu.translate(&mut bus, &s, 0x4000_0000, Access::Load),
+            Ok(0x3000)
+        );
+        mmu.flush(Some(0x4000_0fff), Some(1));
+        assert_eq!(
+            mmu.translate(&mut bus, &s, 0x4000_0000, Access::Load),
+            Ok(0x4000)
+        );
+
+        // Global mappings are shared by all address spaces and survive ASID flushes.
+        bus.write32(0x2000, 3 << 10 | V | R | G | A).unwrap();
+        mmu.flush(None, None);
+        mmu.translate(&mut bus, &s, 0x4000_0000, Access::Load)
+            .unwrap();
+        bus.write32(0x2000, 0).unwrap();
+        mmu.flush(None, Some(1));
+        let other = Context {
+            satp: {
+                let mut csr = Csr::new();
+                csr.write(CsrAddr::Satp as usize, 0x8080_0001).unwrap();
+                csr.satp
+            },
+            ..s
+        };
+        assert_eq!(
+            mmu.translate(&mut bus, &other, 0x4000_0000, Access::Load),
+            Ok(0x3000)
+        );
+    }
+}
@@ -2,37 +2,90 @@ mod macros;
 use macros::add_imm_signed;
 
 mod csr;
-use csr::Csr;
+use csr::{Cause, Csr, Mip, Mstatus, TvecMode};
+pub use csr::{CsrAddr, CsrError};
+
+mod float;
+use float::{Format, Fpu, RoundingMode};
+
+mod mmu;
+mod pmp;
+use mmu::{Access, Context, Mmu};
+
+mod trap;
+use trap::{Exception, Interrupt, Trap};
 
 use thiserror::Error;
 
 use crate::{
-    bus::interface::{BusRead, BusReadException, BusWrite, BusWriteException},
-    instructions::{DecodeError, Decoder, Instruction, OpCode, RegisterIdx},
+    bus::interface::{BusRead, BusReadException, BusWrite, BusWriteException, InterruptSource},
+    instructions::{compressed, Decoder, Instruction, OpCode, RegisterIdx},
 };
 
+/// RV32 hart connected to bus B.
+///
+/// `cycle` also polls the bus for interrupts and needs `InterruptSource`.
+/// `step` only needs `BusRead` and `BusWrite`.
+///
+/// ```
+/// use emulator::{bus::Bus, cpu::Cpu};
+///
+/// // addi x1, x0, 42
+/// let mut cpu = Cpu::new(Bus::new(0x02a0_0093u32.to_le_bytes().to_vec()));
+/// cpu.cycle().unwrap();
+/// assert_eq!(cpu.x(1), 42);
+/// assert_eq!(cpu.pc(), 4);
+/// ```
 #[derive(Debug)]
 pub struct Cpu<B> {
-    _mode: Mode,
+    mode: Mode,
     bus: B,
     stats: Stats,
     r: Registers,
     csr: Csr,
     decoder: Decoder,
+    mmu: Mmu,
+    /// Reservation set registered by LR. Holds naturally aligned physical word address.
+    reservation: Option<u32>,
+    /// Hart is stalled by WFI.
+    wfi: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-enum Mode {
+pub enum Mode {
     /// Machine mode
     M,
+    /// Supervisor mode
+    S,
     /// User mode
-    #[allow(unused)]
     U,
 }
 
-#[derive(Debug)]
+impl Mode {
+    /// Encoding used in mstatus.MPP
+    fn bits(self) -> u32 {
+        match self {
+            Mode::M => 0b11,
+            Mode::S => 0b01,
+            Mode::U => 0b00,
+        }
+    }
+
+    /// Mode from mstatus.MPP. Unsupported encodings fall back to the least-privileged mode.
+    fn from_bits(bits: u32) -> Self {
+        match bits {
+            0b11 => Mode::M,
+            0b01 => Mode::S,
+            _ => Mode::U,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Stats {
     pub cycle_counter: u64,
+    /// Number of instructions completed without raising an exception.
+    pub instructions_retired: u64,
 }
 
 #[derive(Debug)]
@@ -40,33 +93,246 @@ struct Registers {
     /// Program counter
     pc: u32,
     x: [u32; 32],
+    /// Floating-point registers. Single-precision values are NaN-boxed.
+    f: [u64; 32],
 }
 
 impl<B> Cpu<B> {
     pub fn new(bus: B) -> Self {
         Self {
-            _mode: Mode::M,
+            mode: Mode::M,
             bus,
-            stats: Stats { cycle_counter: 0 },
-            r: Registers { pc: 0, x: [0; 32] },
+            stats: Stats {
+                cycle_counter: 0,
+                instructions_retired: 0,
+            },
+            r: Registers {
+                pc: 0,
+                x: [0; 32],
+                f: [0; 32],
+            },
             csr: Csr::new(),
             decoder: Decoder::new(),
+            mmu: Mmu::new(),
+            reservation: None,
+            wfi: false,
+        }
+    }
+
+    /// Set program counter. Used to start execution at the entry point.
+    pub fn set_pc(&mut self, pc: u32) {
+        self.r.pc = pc;
+    }
+
+    /// Return address of the next instruction.
+    pub fn pc(&self) -> u32 {
+        self.r.pc
+    }
+
+    /// Return value of integer register x[idx].
+    ///
+    /// # Panics
+    ///
+    /// Panics if idx is not less than 32.
+    pub fn x(&self, idx: RegisterIdx) -> u32 {
+        self.r.x[idx]
+    }
+
+    /// Set value of integer register x[idx]. Writes to x0 are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if idx is not less than 32.
+    pub fn set_x(&mut self, idx: RegisterIdx, v: u32) {
+        if idx != 0 {
+            self.r.x[idx] = v;
+        }
+    }
+
+    /// Return raw bits of floating-point register f[idx]. Single-precision values are NaN-boxed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if idx is not less than 32.
+    pub fn f(&self, idx: RegisterIdx) -> u64 {
+        self.r.f[idx]
+    }
+
+    /// Set raw bits of floating-point register f[idx]. mstatus.FS is not changed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if idx is not less than 32.
+    pub fn set_f(&mut self, idx: RegisterIdx, v: u64) {
+        self.r.f[idx] = v;
+    }
+
+    /// Read CSR at addr. Privilege is not checked.
+    pub fn read_csr(&self, addr: RegisterIdx) -> Result<u32, CsrError> {
+        self.csr.read(addr)
+    }
+
+    /// Write CSR at addr as CSRRW would do. Privilege is not checked but read-only CSRs are rejected.
+    pub fn write_csr(&mut self, addr: RegisterIdx, v: u32) -> Result<(), CsrError> {
+        self.csr.check_access(addr, Mode::M, true)?;
+        self.csr.write(addr, v)
+    }
+
+    /// Return current privilege mode.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Set A and D bits of page table entries on access instead of raising page faults.
+    /// Page faults are raised by default so that software manages them.
+    pub fn set_hardware_ad_update(&mut self, enabled: bool) {
+        self.mmu.hardware_ad_update = enabled;
+    }
+
+    /// Return privilege mode used for loads and stores.
+    /// While mstatus.MPRV is set in M-mode, data accesses use the mode in mstatus.MPP.
+    /// Instruction fetches always use the current mode.
+    pub fn data_mode(&self) -> Mode {
+        if self.mode == Mode::M && self.csr.mstatus.mprv() {
+            Mode::from_bits(self.csr.mstatus.mpp())
+        } else {
+            self.mode
         }
     }
 
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut B {
+        &mut self.bus
+    }
+
+    /// Consume `Cpu` and return the bus.
+    pub fn into_bus(self) -> B {
+        self.bus
+    }
+
     pub fn state(&self) -> &Stats {
         &self.stats
     }
+
+    /// Effective privilege mode of the access.
+    fn access_mode(&self, access: Access) -> Mode {
+        match access {
+            Access::Fetch => self.mode,
+            Access::Load | Access::Store => self.data_mode(),
+        }
+    }
+
+    /// Check physical memory protection of the access of size bytes at the physical address.
+    /// Access faults report the virtual address.
+    fn check_pmp(&self, vaddr: u32, addr: u32, size: u32, access: Access) -> Result<(), Trap> {
+        if self
+            .csr
+            .pmp
+            .permits(addr, size, self.access_mode(access), access)
+        {
+            Ok(())
+        } else {
+            Err(access.access_fault(vaddr))
+        }
+    }
+}
+
+impl<B: BusRead> Cpu<B> {
+    /// Return the instruction at pc without executing it. None if it can not be fetched.
+    /// Compressed instructions are returned in the lower 16 bits.
+    /// Addresses are translated without side effects, the TLB and A bits are updated on execution.
+    pub fn peek_instruction(&self) -> Option<u32> {
+        let ctx = Context::new(self.mode, &self.csr);
+        let probe = |vaddr| -> Result<u32, Trap> {
+            let addr = self.mmu.probe(&self.bus, &ctx, vaddr, Access::Fetch)?;
+            self.check_pmp(vaddr, addr, 2, Access::Fetch)?;
+            Ok(addr)
+        };
+        let pc = self.r.pc;
+        match self.fetch(pc, probe(pc).ok()?).ok()? {
+            Fetched::Instruction(ir) => Some(ir),
+            Fetched::LowerParcel(lo) => {
+                let next = pc.wrapping_add(2);
+                let hi = self.read_parcel(next, probe(next).ok()?).ok()?;
+                Some(hi << 16 | lo)
+            }
+        }
+    }
+
+    /// Fetch instruction at pc whose first parcel is at the physical address addr.
+    /// The translation and PMP check of the first parcel are done by the caller.
+    /// A word is read at once if pc is 4-byte aligned. Otherwise, or if the word read fails because
+    /// a compressed instruction is at the end of memory or PMP region, the instruction is fetched
+    /// in 16-bit parcels. The upper parcel on the next page is left to the caller to translate.
+    fn fetch(&self, pc: u32, addr: u32) -> Result<Fetched, Trap> {
+        if pc & 0b11 == 0 && self.check_pmp(pc, addr, 4, Access::Fetch).is_ok() {
+            if let Ok(ir) = self.bus.read32(addr) {
+                return Ok(Fetched::Instruction(if compressed::is_compressed(ir) {
+                    ir & 0xffff
+                } else {
+                    ir
+                }));
+            }
+        }
+        let lo = self.read_parcel(pc, addr)?;
+        if compressed::is_compressed(lo) {
+            return Ok(Fetched::Instruction(lo));
+        }
+        let next = pc.wrapping_add(2);
+        if next & 0xfff == 0 {
+            return Ok(Fetched::LowerParcel(lo));
+        }
+        let next_addr = addr.wrapping_add(2);
+        self.check_pmp(next, next_addr, 2, Access::Fetch)?;
+        let hi = self.read_parcel(next, next_addr)?;
+        Ok(Fetched::Instruction(hi << 16 | lo))
+    }
+
+    /// Read the 16-bit parcel at the physical address addr translated from vaddr.
+    fn read_parcel(&self, vaddr: u32, addr: u32) -> Result<u32, Trap> {
+        self.bus
+            .read16(addr)
+            .map(u32::from)
+            .map_err(|e| Trap::fetch(e, vaddr))
+    }
+}
+
+/// Result of fetching the parcels of an instruction on the page of pc.
+enum Fetched {
+    Instruction(u32),
+    /// Lower parcel of an instruction whose upper parcel is on the next page.
+    LowerParcel(u32),
 }
 
+/// Host-level failures of the emulator itself.
+/// Faults caused by the guest are delivered to the guest as traps instead.
 #[derive(Error, Debug)]
 pub enum CpuError {
-    #[error("load error {0:?}")]
-    Load(#[from] BusReadException),
-    #[error("store error {0:?}")]
-    Store(#[from] BusWriteException),
-    #[error("decode error: {0:?}")]
-    Decode(DecodeError),
+    /// CSR write rejected although the instruction passed the access check.
+    #[error("csr {csr:#05x} write at pc {pc:#010x} failed after access check: {source}")]
+    CsrWrite {
+        pc: u32,
+        csr: RegisterIdx,
+        source: CsrError,
+    },
+}
+
+/// Reason why an instruction did not retire.
+#[derive(Debug)]
+enum Fault {
+    /// Exception taken by the guest.
+    Trap(Trap),
+    /// Emulator failure returned from `Cpu::cycle`.
+    Cpu(CpuError),
+}
+
+impl From<Trap> for Fault {
+    fn from(trap: Trap) -> Self {
+        Fault::Trap(trap)
+    }
 }
 
 #[derive(Debug)]
@@ -93,45 +359,232 @@ enum Effect<B> {
     },
     Load {
         effective_addr: u32,
+        /// Access size in bytes
+        size: u32,
         rd: RegisterIdx,
         load: fn(u32, &B) -> Result<u32, BusReadException>,
     },
     Store {
         effective_addr: u32,
+        size: u32,
         rs2: u32,
         store: fn(u32, u32, &mut B) -> Result<(), BusWriteException>,
     },
+    LoadReserved {
+        effective_addr: u32,
+        rd: RegisterIdx,
+    },
+    StoreConditional {
+        effective_addr: u32,
+        rd: RegisterIdx,
+        rs2: u32,
+    },
+    /// Atomically read memory into rd and write back the result of op(memory, rs2).
+    ReadModifyWrite {
+        effective_addr: u32,
+        rd: RegisterIdx,
+        rs2: u32,
+        op: fn(u32, u32) -> u32,
+    },
     Csr {
         rd: RegisterIdx,
         rd_value: u32,
         csr: RegisterIdx,
-        csr_value: u32,
+        /// None if the instruction does not write csr
+        csr_value: Option<u32>,
+    },
+    LoadFloat {
+        effective_addr: u32,
+        size: u32,
+        rd: RegisterIdx,
+        load: fn(u32, &B) -> Result<u64, BusReadException>,
+    },
+    StoreFloat {
+        effective_addr: u32,
+        size: u32,
+        rs2: u64,
+        store: fn(u32, u64, &mut B) -> Result<(), BusWriteException>,
+    },
+    /// Write f[rd] and accrue exception flags into fflags.
+    UpdateFloatRegister {
+        rd: RegisterIdx,
+        value: u64,
+        flags: u32,
+    },
+    /// Write x[rd] and accrue exception flags into fflags.
+    UpdateRegisterWithFlags {
+        rd: RegisterIdx,
+        value: u32,
+        flags: u32,
     },
+    /// Return from machine mode trap handler
+    Mret,
+    /// Return from supervisor mode trap handler
+    Sret,
+    /// Stall until an interrupt becomes pending
+    Wfi,
+    /// Invalidate cached translations. None means all addresses or all address spaces.
+    SfenceVma {
+        vaddr: Option<u32>,
+        asid: Option<u32>,
+    },
+    Nop,
 }
 
 impl<B> Cpu<B>
 where
-    B: BusRead + BusWrite,
+    B: BusRead + BusWrite + InterruptSource,
 {
     /// Emulate cpu clock cycle.
+    /// Advance devices and latch their interrupt lines into mip and mtime into the time CSR,
+    /// then step the hart.
+    /// Err is returned only when the emulator breaks one of its own invariants.
+    pub fn cycle(&mut self) -> Result<(), CpuError> {
+        self.bus.tick();
+        let irq = self.bus.interrupts();
+        self.csr.mip.set(Mip::MSIP, irq.msip);
+        self.csr.mip.set(Mip::MTIP, irq.mtip);
+        self.csr.mip.set(Mip::MEIP, irq.meip);
+        self.csr.time = self.bus.mtime();
+        self.step()
+    }
+}
+
+impl<B> Cpu<B>
+where
+    B: BusRead + BusWrite,
+{
+    /// Emulate cpu clock cycle without polling the bus for interrupts.
+    /// Take interrupt already pending in mip if any. Device interrupt lines are not sampled.
     /// Decode instruction from pc.
     /// Process instruction and update state.
-    pub fn cycle(&mut self) -> Result<(), CpuError> {
+    /// Exceptions raised by the instruction are taken as traps.
+    /// Err is returned only when the emulator breaks one of its own invariants.
+    pub fn step(&mut self) -> Result<(), CpuError> {
         self.stats.cycle_counter = self.stats.cycle_counter.wrapping_add(1);
+        self.csr.mcycle = self.csr.mcycle.wrapping_add(1);
+
+        if self.wfi {
+            if !self.csr.has_local_interrupt() {
+                return Ok(());
+            }
+            self.wfi = false;
+        }
+        if let Some(interrupt) = self.pending_interrupt() {
+            self.interrupt(interrupt);
+            return Ok(());
+        }
+
+        match self.execute() {
+            Ok(()) => {
+                self.stats.instructions_retired = self.stats.instructions_retired.wrapping_add(1);
+                self.csr.minstret = self.csr.minstret.wrapping_add(1);
+            }
+            Err(Fault::Trap(trap)) => self.trap(trap),
+            Err(Fault::Cpu(err)) => return Err(err),
+        }
+        Ok(())
+    }
 
-        self.next_instruction()
-            .and_then(|ir| self.process(ir))
-            .and_then(|effect| self.apply(effect))
+    /// Fetch, decode and execute the instruction at pc.
+    fn execute(&mut self) -> Result<(), Fault> {
+        let ir = self.next_instruction()?;
+        let effect = self.process(ir)?;
+        self.apply(effect, ir.size())
     }
 
     /// Read and decode next instruction.
-    fn next_instruction(&self) -> Result<Instruction, CpuError> {
-        let ir = self.bus.read32(self.r.pc).map_err(CpuError::Load)?;
-        self.decoder.try_decode(ir).map_err(CpuError::Decode)
+    /// Each page is translated once before it is read, so faults are raised without touching the bus.
+    fn next_instruction(&mut self) -> Result<Instruction, Trap> {
+        let pc = self.r.pc;
+        let addr = self.translate(pc, 2, Access::Fetch)?;
+        let ir = match self.fetch(pc, addr)? {
+            Fetched::Instruction(ir) => ir,
+            Fetched::LowerParcel(lo) => {
+                let next = pc.wrapping_add(2);
+                let addr = self.translate(next, 2, Access::Fetch)?;
+                self.read_parcel(next, addr)? << 16 | lo
+            }
+        };
+        self.decoder
+            .try_decode(ir)
+            .map_err(|_| Trap::new(Exception::IllegalInstruction, ir))
+    }
+
+    /// Return the highest priority interrupt which is pending and enabled.
+    /// Interrupts not delegated by mideleg are globally enabled when mstatus.MIE is set or the hart runs below M-mode.
+    /// Delegated interrupts are globally enabled when mstatus.SIE is set in S-mode or the hart runs in U-mode.
+    /// Volume II: RISC-V Privileged Architectures V20211203 P31
+    fn pending_interrupt(&self) -> Option<Interrupt> {
+        let mstatus = self.csr.mstatus;
+        let m_enabled = self.mode != Mode::M || mstatus.mie();
+        let s_enabled = self.mode == Mode::U || self.mode == Mode::S && mstatus.sie();
+        let pending = self.csr.local_interrupts();
+        let enabled = match (m_enabled, s_enabled) {
+            (true, true) => pending,
+            (true, false) => pending & !self.csr.mideleg,
+            (false, true) => pending & self.csr.mideleg,
+            (false, false) => 0,
+        };
+        Interrupt::PRIORITY
+            .into_iter()
+            .find(|&interrupt| enabled & (1 << interrupt as u32) != 0)
+    }
+
+    /// Take trap for synchronous exception.
+    fn trap(&mut self, trap: Trap) {
+        self.take_trap(Cause::new(false, trap.code()), trap.tval);
+    }
+
+    /// Take trap for interrupt. xepc points to the instruction which has not been executed yet.
+    fn interrupt(&mut self, interrupt: Interrupt) {
+        self.take_trap(Cause::new(true, interrupt as u32), 0);
+    }
+
+    /// Save the current state and transfer control to the trap handler.
+    /// Traps are handled in S-mode if they are delegated by medeleg/mideleg and taken below M-mode.
+    /// Traps never transition to a less-privileged mode.
+    fn take_trap(&mut self, cause: Cause, tval: u32) {
+        self.reservation = None;
+
+        let deleg = if cause.is_interrupt() {
+            self.csr.mideleg
+        } else {
+            self.csr.medeleg
+        };
+        let tvec = if self.mode != Mode::M && deleg & (1 << cause.code()) != 0 {
+            self.csr.sepc = self.r.pc;
+            self.csr.scause = cause;
+            self.csr.stval = tval;
+
+            let mstatus = &mut self.csr.mstatus;
+            mstatus.set_spie(mstatus.sie());
+            mstatus.set_sie(false);
+            mstatus.set_spp(self.mode == Mode::S);
+            self.mode = Mode::S;
+            self.csr.stvec
+        } else {
+            self.csr.mepc = self.r.pc;
+            self.csr.mcause = cause;
+            self.csr.mtval = tval;
+
+            let mstatus = &mut self.csr.mstatus;
+            mstatus.set_mpie(mstatus.mie());
+            mstatus.set_mie(false);
+            mstatus.set_mpp(self.mode.bits());
+            self.mode = Mode::M;
+            self.csr.mtvec
+        };
+
+        // Synchronous exceptions always jump to BASE even in vectored mode.
+        self.r.pc = match tvec.mode() {
+            TvecMode::Vectored if cause.is_interrupt() => tvec.base() + 4 * cause.code(),
+            _ => tvec.base(),
+        };
     }
 
     /// Return side effects resulting from processing instruction.
-    fn process(&mut self, ir: Instruction) -> Result<Effect<B>, CpuError> {
+    fn process(&mut self, ir: Instruction) -> Result<Effect<B>, Trap> {
         use OpCode::*;
         let effect = match ir.op_code {
             Lui => Effect::UpdateRegister {
@@ -140,7 +593,7 @@ where
             },
             Auipc => Effect::UpdateRegister {
                 rd: ir.rd(),
-                imm: ir.imm() + self.r.pc,
+                imm: ir.imm().wrapping_add(self.r.pc),
             },
             Jal => Effect::Jal {
                 rd: ir.rd(),
@@ -159,26 +612,213 @@ where
             Bgeu => self.branch_with_unsigned(|r1, r2| r1 >= r2, ir),
             Blt => self.branch_with_signed(|r1, r2| r1 < r2, ir),
             Bge => self.branch_with_signed(|r1, r2| r1 >= r2, ir),
-            Lb => self.load_with(|addr, bus| bus.read8(addr).map(|v| v as i8 as u32), ir),
-            Lh => self.load_with(|addr, bus| bus.read16(addr).map(|v| v as i16 as u32), ir),
-            Lw => self.load_with(|addr, bus| bus.read32(addr), ir),
-            Lbu => self.load_with(|addr, bus| bus.read8(addr).map(|v| v as u32), ir),
-            Lhu => self.load_with(|addr, bus| bus.read16(addr).map(|v| v as u32), ir),
-            Sb => self.store_with(|addr, val, bus| bus.write8(addr, val as u8), ir),
-            Sh => self.store_with(|addr, val, bus| bus.write16(addr, val as u16), ir),
-            Sw => self.store_with(|addr, val, bus| bus.write32(addr, val), ir),
-            Csrrw => self.csr_with(|_csr, rs1| rs1, ir, false),
-            Csrrs => self.csr_with(|csr, rs1| csr | rs1, ir, false),
-            Csrrc => self.csr_with(|csr, rs1| csr & (!rs1), ir, false),
-            Csrrwi => self.csr_with(|_csr, rs1| rs1, ir, true),
-            Csrrsi => self.csr_with(|csr, rs1| csr | rs1, ir, true),
-            Csrrci => self.csr_with(|csr, rs1| csr & (!rs1), ir, true),
+            Lb => self.load_with(1, |addr, bus| bus.read8(addr).map(|v| v as i8 as u32), ir),
+            Lh => self.load_with(2, |addr, bus| bus.read16(addr).map(|v| v as i16 as u32), ir),
+            Lw => self.load_with(4, |addr, bus| bus.read32(addr), ir),
+            Lbu => self.load_with(1, |addr, bus| bus.read8(addr).map(|v| v as u32), ir),
+            Lhu => self.load_with(2, |addr, bus| bus.read16(addr).map(|v| v as u32), ir),
+            Sb => self.store_with(1, |addr, val, bus| bus.write8(addr, val as u8), ir),
+            Sh => self.store_with(2, |addr, val, bus| bus.write16(addr, val as u16), ir),
+            Sw => self.store_with(4, |addr, val, bus| bus.write32(addr, val), ir),
+            Addi => self.op_imm_with(|rs1, imm| rs1.wrapping_add(imm), ir),
+            Slti => self.op_imm_with(|rs1, imm| ((rs1 as i32) < (imm as i32)) as u32, ir),
+            Sltiu => self.op_imm_with(|rs1, imm| (rs1 < imm) as u32, ir),
+            Xori => self.op_imm_with(|rs1, imm| rs1 ^ imm, ir),
+            Ori => self.op_imm_with(|rs1, imm| rs1 | imm, ir),
+            Andi => self.op_imm_with(|rs1, imm| rs1 & imm, ir),
+            Slli => self.shift_imm_with(|rs1, shamt| rs1 << shamt, ir),
+            Srli => self.shift_imm_with(|rs1, shamt| rs1 >> shamt, ir),
+            Srai => self.shift_imm_with(|rs1, shamt| ((rs1 as i32) >> shamt) as u32, ir),
+            Add => self.op_with(|rs1, rs2| rs1.wrapping_add(rs2), ir),
+            Sub => self.op_with(|rs1, rs2| rs1.wrapping_sub(rs2), ir),
+            Sll => self.op_with(|rs1, rs2| rs1 << (rs2 & 0x1f), ir),
+            Slt => self.op_with(|rs1, rs2| ((rs1 as i32) < (rs2 as i32)) as u32, ir),
+            Sltu => self.op_with(|rs1, rs2| (rs1 < rs2) as u32, ir),
+            Xor => self.op_with(|rs1, rs2| rs1 ^ rs2, ir),
+            Srl => self.op_with(|rs1, rs2| rs1 >> (rs2 & 0x1f), ir),
+            Sra => self.op_with(|rs1, rs2| ((rs1 as i32) >> (rs2 & 0x1f)) as u32, ir),
+            Or => self.op_with(|rs1, rs2| rs1 | rs2, ir),
+            And => self.op_with(|rs1, rs2| rs1 & rs2, ir),
+            Mul => self.op_with(|rs1, rs2| rs1.wrapping_mul(rs2), ir),
+            Mulh => self.op_with(
+                |rs1, rs2| ((rs1 as i32 as i64 * rs2 as i32 as i64) >> 32) as u32,
+                ir,
+            ),
+            Mulhsu => self.op_with(
+                |rs1, rs2| ((rs1 as i32 as i64 * rs2 as i64) >> 32) as u32,
+                ir,
+            ),
+            Mulhu => self.op_with(|rs1, rs2| ((rs1 as u64 * rs2 as u64) >> 32) as u32, ir),
+            Div => self.op_with(
+                |rs1, rs2| match rs2 {
+                    0 => u32::MAX,
+                    _ => (rs1 as i32).wrapping_div(rs2 as i32) as u32,
+                },
+                ir,
+            ),
+            Divu => self.op_with(|rs1, rs2| rs1.checked_div(rs2).unwrap_or(u32::MAX), ir),
+            Rem => self.op_with(
+                |rs1, rs2| match rs2 {
+                    0 => rs1,
+                    _ => (rs1 as i32).wrapping_rem(rs2 as i32) as u32,
+                },
+                ir,
+            ),
+            Remu => self.op_with(|rs1, rs2| rs1.checked_rem(rs2).unwrap_or(rs1), ir),
+            Fence | FenceI => Effect::Nop,
+            LrW => Effect::LoadReserved {
+                effective_addr: self.read(ir.rs1()),
+                rd: ir.rd(),
+            },
+            ScW => Effect::StoreConditional {
+                effective_addr: self.read(ir.rs1()),
+                rd: ir.rd(),
+                rs2: self.read(ir.rs2()),
+            },
+            AmoswapW => self.amo_with(|_mem, rs2| rs2, ir),
+            AmoaddW => self.amo_with(|mem, rs2| mem.wrapping_add(rs2), ir),
+            AmoxorW => self.amo_with(|mem, rs2| mem ^ rs2, ir),
+            AmoandW => self.amo_with(|mem, rs2| mem & rs2, ir),
+            AmoorW => self.amo_with(|mem, rs2| mem | rs2, ir),
+            AmominW => self.amo_with(|mem, rs2| (mem as i32).min(rs2 as i32) as u32, ir),
+            AmomaxW => self.amo_with(|mem, rs2| (mem as i32).max(rs2 as i32) as u32, ir),
+            AmominuW => self.amo_with(|mem, rs2| mem.min(rs2), ir),
+            AmomaxuW => self.amo_with(|mem, rs2| mem.max(rs2), ir),
+            Flw => self.load_float_with(
+                4,
+                |addr, bus| bus.read32(addr).map(|v| Format::SINGLE.nan_box(v as u64)),
+                ir,
+            )?,
+            Fsw => self.store_float_with(4, |addr, val, bus| bus.write32(addr, val as u32), ir)?,
+            FmaddS => self.fused_with(Format::SINGLE, false, false, ir)?,
+            FmsubS => self.fused_with(Format::SINGLE, false, true, ir)?,
+            FnmsubS => self.fused_with(Format::SINGLE, true, false, ir)?,
+            FnmaddS => self.fused_with(Format::SINGLE, true, true, ir)?,
+            FaddS => self.float_op_with(Format::SINGLE, Fpu::add, ir)?,
+            FsubS => self.float_op_with(Format::SINGLE, Fpu::sub, ir)?,
+            FmulS => self.float_op_with(Format::SINGLE, Fpu::mul, ir)?,
+            FdivS => self.float_op_with(Format::SINGLE, Fpu::div, ir)?,
+            FsqrtS => self.float_op_with(Format::SINGLE, |fpu, a, _| fpu.sqrt(a), ir)?,
+            FsgnjS => {
+                self.float_op_with(Format::SINGLE, |_, a, b| Format::SINGLE.copy_sign(a, b), ir)?
+            }
+            FsgnjnS => self.float_op_with(
+                Format::SINGLE,
+                |_, a, b| Format::SINGLE.copy_sign_negated(a, b),
+                ir,
+            )?,
+            FsgnjxS => {
+                self.float_op_with(Format::SINGLE, |_, a, b| Format::SINGLE.xor_sign(a, b), ir)?
+            }
+            FminS => self.float_op_with(Format::SINGLE, Fpu::min, ir)?,
+            FmaxS => self.float_op_with(Format::SINGLE, Fpu::max, ir)?,
+            FcvtWS => {
+                self.float_to_int_with(Format::SINGLE, |fpu, a, _| fpu.float_to_i32(a), ir)?
+            }
+            FcvtWuS => {
+                self.float_to_int_with(Format::SINGLE, |fpu, a, _| fpu.float_to_u32(a), ir)?
+            }
+            // The lower bits are moved as is even if the value is not NaN-boxed.
+            FmvXW => {
+                self.check_fs(ir)?;
+                Effect::UpdateRegister {
+                    rd: ir.rd(),
+                    imm: self.r.f[ir.rs1()] as u32,
+                }
+            }
+            FeqS => self.float_to_int_with(Format::SINGLE, |fpu, a, b| fpu.eq(a, b) as u32, ir)?,
+            FltS => self.float_to_int_with(Format::SINGLE, |fpu, a, b| fpu.lt(a, b) as u32, ir)?,
+            FleS => self.float_to_int_with(Format::SINGLE, |fpu, a, b| fpu.le(a, b) as u32, ir)?,
+            FclassS => self.float_to_int_with(Format::SINGLE, |fpu, a, _| fpu.classify(a), ir)?,
+            FcvtSW => {
+                self.int_to_float_with(Format::SINGLE, |fpu, v| fpu.i32_to_float(v as i32), ir)?
+            }
+            FcvtSWu => self.int_to_float_with(Format::SINGLE, Fpu::u32_to_float, ir)?,
+            FmvWX => self.int_to_float_with(Format::SINGLE, |_, v| v as u64, ir)?,
+            Fld => self.load_float_with(8, |addr, bus| bus.read64(addr), ir)?,
+            Fsd => self.store_float_with(8, |addr, val, bus| bus.write64(addr, val), ir)?,
+            FmaddD => self.fused_with(Format::DOUBLE, false, false, ir)?,
+            FmsubD => self.fused_with(Format::DOUBLE, false, true, ir)?,
+            FnmsubD => self.fused_with(Format::DOUBLE, true, false, ir)?,
+            FnmaddD => self.fused_with(Format::DOUBLE, true, true, ir)?,
+            FaddD => self.float_op_with(Format::DOUBLE, Fpu::add, ir)?,
+            FsubD => self.float_op_with(Format::DOUBLE, Fpu::sub, ir)?,
+            FmulD => self.float_op_with(Format::DOUBLE, Fpu::mul, ir)?,
+            FdivD => self.float_op_with(Format::DOUBLE, Fpu::div, ir)?,
+            FsqrtD => self.float_op_with(Format::DOUBLE, |fpu, a, _| fpu.sqrt(a), ir)?,
+            FsgnjD => {
+                self.float_op_with(Format::DOUBLE, |_, a, b| Format::DOUBLE.copy_sign(a, b), ir)?
+            }
+            FsgnjnD => self.float_op_with(
+                Format::DOUBLE,
+                |_, a, b| Format::DOUBLE.copy_sign_negated(a, b),
+                ir,
+            )?,
+            FsgnjxD => {
+                self.float_op_with(Format::DOUBLE, |_, a, b| Format::DOUBLE.xor_sign(a, b), ir)?
+            }
+            FminD => self.float_op_with(Format::DOUBLE, Fpu::min, ir)?,
+            FmaxD => self.float_op_with(Format::DOUBLE, Fpu::max, ir)?,
+            FcvtSD => self.convert_with(Format::DOUBLE, Format::SINGLE, ir)?,
+            FcvtDS => self.convert_with(Format::SINGLE, Format::DOUBLE, ir)?,
+            FeqD => self.float_to_int_with(Format::DOUBLE, |fpu, a, b| fpu.eq(a, b) as u32, ir)?,
+            FltD => self.float_to_int_with(Format::DOUBLE, |fpu, a, b| fpu.lt(a, b) as u32, ir)?,
+            FleD => self.float_to_int_with(Format::DOUBLE, |fpu, a, b| fpu.le(a, b) as u32, ir)?,
+            FclassD => self.float_to_int_with(Format::DOUBLE, |fpu, a, _| fpu.classify(a), ir)?,
+            FcvtWD => {
+                self.float_to_int_with(Format::DOUBLE, |fpu, a, _| fpu.float_to_i32(a), ir)?
+            }
+            FcvtWuD => {
+                self.float_to_int_with(Format::DOUBLE, |fpu, a, _| fpu.float_to_u32(a), ir)?
+            }
+            FcvtDW => {
+                self.int_to_float_with(Format::DOUBLE, |fpu, v| fpu.i32_to_float(v as i32), ir)?
+            }
+            FcvtDWu => self.int_to_float_with(Format::DOUBLE, Fpu::u32_to_float, ir)?,
+            Csrrw => self.csr_with(|_csr, rs1| rs1, ir, false)?,
+            Csrrs => self.csr_with(|csr, rs1| csr | rs1, ir, false)?,
+            Csrrc => self.csr_with(|csr, rs1| csr & (!rs1), ir, false)?,
+            Csrrwi => self.csr_with(|_csr, rs1| rs1, ir, true)?,
+            Csrrsi => self.csr_with(|csr, rs1| csr | rs1, ir, true)?,
+            Csrrci => self.csr_with(|csr, rs1| csr & (!rs1), ir, true)?,
+            Ecall => {
+                let cause = match self.mode {
+                    Mode::M => Exception::EnvironmentCallFromMMode,
+                    Mode::S => Exception::EnvironmentCallFromSMode,
+                    Mode::U => Exception::EnvironmentCallFromUMode,
+                };
+                return Err(Trap::new(cause, 0));
+            }
+            Ebreak => return Err(Trap::new(Exception::Breakpoint, self.r.pc)),
+            Mret if self.mode == Mode::M => Effect::Mret,
+            // SRET is illegal in U-mode, and in S-mode when mstatus.TSR is set.
+            Sret if self.mode == Mode::M || self.mode == Mode::S && !self.csr.mstatus.tsr() => {
+                Effect::Sret
+            }
+            // WFI is illegal in U-mode, and in S-mode when mstatus.TW is set. It never times out.
+            Wfi if self.mode == Mode::U || self.mode == Mode::S && self.csr.mstatus.tw() => {
+                return Err(Trap::new(Exception::IllegalInstruction, ir.raw()))
+            }
+            Wfi => Effect::Wfi,
+            // SFENCE.VMA is illegal in U-mode, and in S-mode when mstatus.TVM is set.
+            SfenceVma
+                if self.mode == Mode::M || self.mode == Mode::S && !self.csr.mstatus.tvm() =>
+            {
+                Effect::SfenceVma {
+                    vaddr: (ir.rs1() != 0).then(|| self.read(ir.rs1())),
+                    asid: (ir.rs2() != 0).then(|| self.read(ir.rs2()) & 0x1ff),
+                }
+            }
+            Mret | Sret | SfenceVma => {
+                return Err(Trap::new(Exception::IllegalInstruction, ir.raw()))
+            }
         };
         Ok(effect)
     }
 
     /// Apply side effect to update state.
-    fn apply(&mut self, effect: Effect<B>) -> Result<(), CpuError> {
+    /// size is the length of the instruction which is used to compute the next pc.
+    fn apply(&mut self, effect: Effect<B>, size: u32) -> Result<(), Fault> {
         use Effect::*;
         let do_inc = match effect {
             UpdateRegister { rd, imm } => {
@@ -186,8 +826,9 @@ where
                 true
             }
             Jal { rd, pc, imm } => {
-                self.write(rd, pc + 4);
-                self.r.pc = (pc as i64 + imm as i64) as u32;
+                let target = jump_target(add_imm_signed!(pc, imm))?;
+                self.write(rd, pc.wrapping_add(size));
+                self.r.pc = target;
                 false
             }
             Jalr {
@@ -196,31 +837,98 @@ where
                 offset,
                 base,
             } => {
-                self.write(rd, pc + 4);
-                let target = (base as i64 + offset as i64) as u32;
-                self.r.pc = target & !1;
+                let target = jump_target(add_imm_signed!(base, offset) & !1)?;
+                self.write(rd, pc.wrapping_add(size));
+                self.r.pc = target;
                 false
             }
-            Branch { do_branch, pc, imm } => do_branch
-                .then(|| {
-                    self.r.pc = (pc as i64 + imm as i64) as u32;
-                })
-                .is_none(),
+            Branch { do_branch, pc, imm } => {
+                if do_branch {
+                    self.r.pc = jump_target(add_imm_signed!(pc, imm))?;
+                }
+                !do_branch
+            }
             Load {
                 effective_addr,
+                size,
                 rd,
                 load,
             } => {
-                let v = load(effective_addr, &self.bus)?;
+                if effective_addr & (size - 1) != 0 {
+                    return Err(Trap::new(Exception::LoadAddressMisaligned, effective_addr).into());
+                }
+                let addr = self.translate(effective_addr, size, Access::Load)?;
+                let v = load(addr, &self.bus).map_err(|e| Trap::load(e, effective_addr))?;
                 self.write(rd, v);
                 true
             }
             Store {
                 effective_addr,
+                size,
                 rs2,
                 store,
             } => {
-                store(effective_addr, rs2, &mut self.bus)?;
+                if effective_addr & (size - 1) != 0 {
+                    return Err(Trap::new(Exception::StoreAddressMisaligned, effective_addr).into());
+                }
+                let addr = self.translate(effective_addr, size, Access::Store)?;
+                store(addr, rs2, &mut self.bus).map_err(|e| Trap::store(e, effective_addr))?;
+                self.invalidate_reservation(addr, size);
+                true
+            }
+            LoadReserved { effective_addr, rd } => {
+                if effective_addr & 3 != 0 {
+                    return Err(Trap::new(Exception::LoadAddressMisaligned, effective_addr).into());
+                }
+                let addr = self.translate(effective_addr, 4, Access::Load)?;
+                let v = self
+                    .bus
+                    .read32(addr)
+                    .map_err(|e| Trap::load(e, effective_addr))?;
+                self.reservation = Some(addr);
+                self.write(rd, v);
+                true
+            }
+            StoreConditional {
+                effective_addr,
+                rd,
+                rs2,
+            } => {
+                if effective_addr & 3 != 0 {
+                    return Err(Trap::new(Exception::StoreAddressMisaligned, effective_addr).into());
+                }
+                let addr = self.translate(effective_addr, 4, Access::Store)?;
+                // Regardless of success or failure, executing an SC instruction invalidates any reservation.
+                if self.reservation.take() == Some(addr) {
+                    self.bus
+                        .write32(addr, rs2)
+                        .map_err(|e| Trap::store(e, effective_addr))?;
+                    self.write(rd, 0);
+                } else {
+                    self.write(rd, 1);
+                }
+                true
+            }
+            ReadModifyWrite {
+                effective_addr,
+                rd,
+                rs2,
+                op,
+            } => {
+                if effective_addr & 3 != 0 {
+                    return Err(Trap::new(Exception::StoreAddressMisaligned, effective_addr).into());
+                }
+                let addr = self.translate(effective_addr, 4, Access::Store)?;
+                // AMOs raise store/AMO access faults even if the read part fails.
+                let v = self
+                    .bus
+                    .read32(addr)
+                    .map_err(|_| Trap::new(Exception::StoreAccessFault, effective_addr))?;
+                self.bus
+                    .write32(addr, op(v, rs2))
+                    .map_err(|e| Trap::store(e, effective_addr))?;
+                self.invalidate_reservation(addr, 4);
+                self.write(rd, v);
                 true
             }
             Csr {
@@ -229,13 +937,92 @@ where
                 csr,
                 csr_value,
             } => {
+                if let Some(csr_value) = csr_value {
+                    // Access was already checked on process.
+                    self.csr.write(csr, csr_value).map_err(|source| {
+                        Fault::Cpu(CpuError::CsrWrite {
+                            pc: self.r.pc,
+                            csr,
+                            source,
+                        })
+                    })?;
+                }
                 self.write(rd, rd_value);
-                self.csr.write(csr, csr_value);
                 true
             }
+            LoadFloat {
+                effective_addr,
+                size,
+                rd,
+                load,
+            } => {
+                if effective_addr & (size - 1) != 0 {
+                    return Err(Trap::new(Exception::LoadAddressMisaligned, effective_addr).into());
+                }
+                let addr = self.translate(effective_addr, size, Access::Load)?;
+                let v = load(addr, &self.bus).map_err(|e| Trap::load(e, effective_addr))?;
+                self.write_float(rd, v);
+                true
+            }
+            StoreFloat {
+                effective_addr,
+                size,
+                rs2,
+                store,
+            } => {
+                if effective_addr & (size - 1) != 0 {
+                    return Err(Trap::new(Exception::StoreAddressMisaligned, effective_addr).into());
+                }
+                let addr = self.translate(effective_addr, size, Access::Store)?;
+                store(addr, rs2, &mut self.bus).map_err(|e| Trap::store(e, effective_addr))?;
+                self.invalidate_reservation(addr, size);
+                true
+            }
+            UpdateFloatRegister { rd, value, flags } => {
+                self.write_float(rd, value);
+                self.accrue_flags(flags);
+                true
+            }
+            UpdateRegisterWithFlags { rd, value, flags } => {
+                self.write(rd, value);
+                self.accrue_flags(flags);
+                true
+            }
+            Mret => {
+                let mstatus = &mut self.csr.mstatus;
+                self.mode = Mode::from_bits(mstatus.mpp());
+                // Returning to a less-privileged mode clears MPRV.
+                if self.mode != Mode::M {
+                    mstatus.set_mprv(false);
+                }
+                mstatus.set_mie(mstatus.mpie());
+                mstatus.set_mpie(true);
+                mstatus.set_mpp(Mode::U.bits());
+                self.r.pc = self.csr.mepc;
+                false
+            }
+            Sret => {
+                let mstatus = &mut self.csr.mstatus;
+                self.mode = if mstatus.spp() { Mode::S } else { Mode::U };
+                mstatus.set_mprv(false);
+                mstatus.set_sie(mstatus.spie());
+                mstatus.set_spie(true);
+                mstatus.set_spp(false);
+                self.r.pc = self.csr.sepc;
+                false
+            }
+            Wfi => {
+                self.wfi = true;
+                true
+            }
+            SfenceVma { vaddr, asid } => {
+                self.mmu.flush(vaddr, asid);
+                true
+            }
+            Nop => true,
         };
 
-        do_inc.then(|| self.r.pc += 4);
+        do_inc.then(|| self.r.pc = self.r.pc.wrapping_add(size));
 
         Ok(())
     }
@@ -262,12 +1049,14 @@ where
 
     fn load_with(
         &self,
+        size: u32,
         load: fn(u32, &B) -> Result<u32, BusReadException>,
         ir: Instruction,
     ) -> Effect<B> {
-        let effective_addr = add_imm_signed!(ir.rs1(), ir.imm_signed());
+        let effective_addr = add_imm_signed!(self.read(ir.rs1()), ir.imm_signed());
         Effect::Load {
             effective_addr,
+            size,
             rd: ir.rd(),
             load,
         }
@@ -275,82 +1064,1985 @@ where
 
     fn store_with(
         &self,
+        size: u32,
         store: fn(u32, u32, &mut B) -> Result<(), BusWriteException>,
         ir: Instruction,
     ) -> Effect<B> {
-        let effective_addr = add_imm_signed!(ir.rs1(), ir.imm_signed());
+        let effective_addr = add_imm_signed!(self.read(ir.rs1()), ir.imm_signed());
         Effect::Store {
             effective_addr,
+            size,
             rs2: self.read(ir.rs2()),
             store,
         }
     }
 
-    fn csr_with<F: Fn(u32, u32) -> u32>(&self, f: F, ir: Instruction, imm: bool) -> Effect<B> {
+    /// Register-immediate operation. imm is sign-extended.
+    fn op_imm_with<F: Fn(u32, u32) -> u32>(&self, f: F, ir: Instruction) -> Effect<B> {
+        Effect::UpdateRegister {
+            rd: ir.rd(),
+            imm: f(self.read(ir.rs1()), ir.imm_signed() as u32),
+        }
+    }
+
+    fn shift_imm_with<F: Fn(u32, u32) -> u32>(&self, f: F, ir: Instruction) -> Effect<B> {
+        Effect::UpdateRegister {
+            rd: ir.rd(),
+            imm: f(self.read(ir.rs1()), ir.shamt()),
+        }
+    }
+
+    /// Register-register operation.
+    fn op_with<F: Fn(u32, u32) -> u32>(&self, f: F, ir: Instruction) -> Effect<B> {
+        Effect::UpdateRegister {
+            rd: ir.rd(),
+            imm: f(self.read(ir.rs1()), self.read(ir.rs2())),
+        }
+    }
+
+    fn amo_with(&self, op: fn(u32, u32) -> u32, ir: Instruction) -> Effect<B> {
+        Effect::ReadModifyWrite {
+            effective_addr: self.read(ir.rs1()),
+            rd: ir.rd(),
+            rs2: self.read(ir.rs2()),
+            op,
+        }
+    }
+
+    /// Translate virtual address of the access of size bytes into a physical address
+    /// and check physical memory protection of it.
+    fn translate(&mut self, vaddr: u32, size: u32, access: Access) -> Result<u32, Trap> {
+        let ctx = Context::new(self.access_mode(access), &self.csr);
+        let addr = self.mmu.translate(&mut self.bus, &ctx, vaddr, access)?;
+        self.check_pmp(vaddr, addr, size, access)?;
+        Ok(addr)
+    }
+
+    /// Invalidate reservation set if the naturally aligned store of size bytes at addr overlaps with it.
+    fn invalidate_reservation(&mut self, addr: u32, size: u32) {
+        if self
+            .reservation
+            .map_or(false, |reserved| reserved.wrapping_sub(addr & !3) < size)
+        {
+            self.reservation = None;
+        }
+    }
+
+    /// CSRRW(I) always writes csr. CSRRS(I)/CSRRC(I) do not write csr if rs1 is x0 or uimm is zero.
+    fn csr_with<F: Fn(u32, u32) -> u32>(
+        &self,
+        f: F,
+        ir: Instruction,
+        imm: bool,
+    ) -> Result<Effect<B>, Trap> {
         let csr_addr = ir.csr();
-        let csr_val = self.csr.read(csr_addr);
+        let write = matches!(ir.op_code, OpCode::Csrrw | OpCode::Csrrwi) || ir.rs1() != 0;
+        let illegal = |_| Trap::new(Exception::IllegalInstruction, ir.raw());
+        self.csr
+            .check_access(csr_addr, self.mode, write)
+            .map_err(illegal)?;
+        let csr_val = self.csr.read(csr_addr).map_err(illegal)?;
         let rs1 = if imm {
             ir.rs1() as u32
         } else {
             self.read(ir.rs1())
         };
-        let new_csr_val = f(csr_val, rs1);
+        let new_csr_val = write.then(|| f(csr_val, rs1));
 
-        Effect::Csr {
+        Ok(Effect::Csr {
             rd: ir.rd(),
             rd_value: csr_val,
             csr: csr_addr,
             csr_value: new_csr_val,
-        }
+        })
     }
 
-    /// Write value to rd register
-    /// Write to x0 register are ignored
-    fn write(&mut self, rd: usize, v: u32) {
-        if rd != 0 {
-            self.r.x[rd] = v;
+    /// Floating-point instructions are illegal while mstatus.FS is Off.
+    fn check_fs(&self, ir: Instruction) -> Result<(), Trap> {
+        if self.csr.mstatus.fs() == Mstatus::FS_OFF {
+            Err(Trap::new(Exception::IllegalInstruction, ir.raw()))
+        } else {
+            Ok(())
         }
     }
 
-    /// Read from register
-    fn read(&self, rs: usize) -> u32 {
-        self.r.x[rs]
+    /// Return Fpu for the format with the rounding mode of the instruction. Dynamic rounding mode refers to frm.
+    /// Instructions without rm field are also fine as their funct3 is always a valid rounding mode.
+    fn fpu(&self, fmt: Format, ir: Instruction) -> Result<Fpu, Trap> {
+        self.check_fs(ir)?;
+        let rm = match ir.rm() {
+            0b111 => self.csr.frm,
+            rm => rm,
+        };
+        let rm = RoundingMode::from_bits(rm)
+            .ok_or_else(|| Trap::new(Exception::IllegalInstruction, ir.raw()))?;
+        Ok(Fpu::new(fmt, rm))
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::bus::Bus;
 
-    #[test]
-    fn should_increment_cycle_counter() {
-        let bus = Bus::new(vec![0; 1024]);
-        let mut c = Cpu::new(bus);
-        c.cycle().ok();
-        assert_eq!(c.state().cycle_counter, 1);
+    /// Value of f[rs] in the format. Single-precision values which are not NaN-boxed are the canonical NaN.
+    fn read_float(&self, fmt: Format, rs: RegisterIdx) -> u64 {
+        fmt.unbox(self.r.f[rs])
     }
 
-    #[test]
-    fn instruction_lui() {
-        let lui: u32 = (0b1 << 12) | (0b01 << 7) | 0b00110111;
-        let ram = lui.to_le_bytes().into();
-        let bus = Bus::new(ram);
-        let mut c = Cpu::new(bus);
-        c.cycle().unwrap();
-        // LUI filling in the lowest 12 bits with zeros.
-        assert_eq!(c.r.x[1], 4096);
+    fn load_float_with(
+        &self,
+        size: u32,
+        load: fn(u32, &B) -> Result<u64, BusReadException>,
+        ir: Instruction,
+    ) -> Result<Effect<B>, Trap> {
+        self.check_fs(ir)?;
+        Ok(Effect::LoadFloat {
+            effective_addr: add_imm_signed!(self.read(ir.rs1()), ir.imm_signed()),
+            size,
+            rd: ir.rd(),
+            load,
+        })
     }
 
-    #[test]
-    fn instruction_auipc() {
+    fn store_float_with(
+        &self,
+        size: u32,
+        store: fn(u32, u64, &mut B) -> Result<(), BusWriteException>,
+        ir: Instruction,
+    ) -> Result<Effect<B>, Trap> {
+        self.check_fs(ir)?;
+        Ok(Effect::StoreFloat {
+            effective_addr: add_imm_signed!(self.read(ir.rs1()), ir.imm_signed()),
+            size,
+            rs2: self.r.f[ir.rs2()],
+            store,
+        })
+    }
+
+    /// Floating-point operation on f[rs1] and f[rs2] whose result is written to f[rd].
+    fn float_op_with<F: Fn(&mut Fpu, u64, u64) -> u64>(
+        &self,
+        fmt: Format,
+        f: F,
+        ir: Instruction,
+    ) -> Result<Effect<B>, Trap> {
+        let mut fpu = self.fpu(fmt, ir)?;
+        let value = f(
+            &mut fpu,
+            self.read_float(fmt, ir.rs1()),
+            self.read_float(fmt, ir.rs2()),
+        );
+        Ok(Effect::UpdateFloatRegister {
+            rd: ir.rd(),
+            value: fmt.nan_box(value),
+            flags: fpu.flags,
+        })
+    }
+
+    /// Fused multiply-add. (-)(f[rs1] * f[rs2]) (+/-) f[rs3] is rounded only once.
+    fn fused_with(
+        &self,
+        fmt: Format,
+        negate_product: bool,
+        negate_addend: bool,
+        ir: Instruction,
+    ) -> Result<Effect<B>, Trap> {
+        let mut fpu = self.fpu(fmt, ir)?;
+        let value = fpu.mul_add(
+            self.read_float(fmt, ir.rs1()),
+            self.read_float(fmt, ir.rs2()),
+            self.read_float(fmt, ir.rs3()),
+            negate_product,
+            negate_addend,
+        );
+        Ok(Effect::UpdateFloatRegister {
+            rd: ir.rd(),
+            value: fmt.nan_box(value),
+            flags: fpu.flags,
+        })
+    }
+
+    /// Convert f[rs1] from a format into another and write the result to f[rd].
+    fn convert_with(&self, from: Format, to: Format, ir: Instruction) -> Result<Effect<B>, Trap> {
+        let mut fpu = self.fpu(to, ir)?;
+        let value = fpu.convert(from, self.read_float(from, ir.rs1()));
+        Ok(Effect::UpdateFloatRegister {
+            rd: ir.rd(),
+            value: to.nan_box(value),
+            flags: fpu.flags,
+        })
+    }
+
+    /// Floating-point operation on f[rs1] and f[rs2] whose result is written to x[rd].
+    fn float_to_int_with<F: Fn(&mut Fpu, u64, u64) -> u32>(
+        &self,
+        fmt: Format,
+        f: F,
+        ir: Instruction,
+    ) -> Result<Effect<B>, Trap> {
+        let mut fpu = self.fpu(fmt, ir)?;
+        let value = f(
+            &mut fpu,
+            self.read_float(fmt, ir.rs1()),
+            self.read_float(fmt, ir.rs2()),
+        );
+        Ok(Effect::UpdateRegisterWithFlags {
+            rd: ir.rd(),
+            value,
+            flags: fpu.flags,
+        })
+    }
+
+    /// Operation on x[rs1] whose result is written to f[rd].
+    fn int_to_float_with<F: Fn(&mut Fpu, u32) -> u64>(
+        &self,
+        fmt: Format,
+        f: F,
+        ir: Instruction,
+    ) -> Result<Effect<B>, Trap> {
+        let mut fpu = self.fpu(fmt, ir)?;
+        let value = f(&mut fpu, self.read(ir.rs1()));
+        Ok(Effect::UpdateFloatRegister {
+            rd: ir.rd(),
+            value: fmt.nan_box(value),
+            flags: fpu.flags,
+        })
+    }
+
+    /// Write value to f[rd] and mark the floating-point state dirty.
+    fn write_float(&mut self, rd: usize, v: u64) {
+        self.r.f[rd] = v;
+        self.csr.mstatus.set_fs(Mstatus::FS_DIRTY);
+    }
+
+    /// Accrue exception flags into fflags.
+    fn accrue_flags(&mut self, flags: u32) {
+        if flags != 0 {
+            self.csr.fflags |= flags;
+            self.csr.mstatus.set_fs(Mstatus::FS_DIRTY);
+        }
+    }
+
+    /// Write value to rd register
+    /// Write to x0 register are ignored
+    fn write(&mut self, rd: usize, v: u32) {
+        if rd != 0 {
+            self.r.x[rd] = v;
+        }
+    }
+
+    /// Read from register
+    fn read(&self, rs: usize) -> u32 {
+        self.r.x[rs]
+    }
+}
+
+/// Return target if it is aligned to the instruction boundary (IALIGN=16).
+fn jump_target(target: u32) -> Result<u32, Trap> {
+    if target & 0b1 != 0 {
+        Err(Trap::new(Exception::InstructionAddressMisaligned, target))
+    } else {
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bus::{clint::Clint, Bus},
+        instructions::{asm, encode},
+    };
+    use OpCode::*;
+
+    #[test]
+    fn should_increment_cycle_counter() {
+        let bus = Bus::new(vec![0; 1024]);
+        let mut c = Cpu::new(bus);
+        c.cycle().ok();
+        assert_eq!(c.state().cycle_counter, 1);
+    }
+
+    #[test]
+    fn instruction_lui() {
+        let mut c = TestCpu::program(&[encode::u(Lui, 1, 1).unwrap()]).build();
+        c.cycle().unwrap();
+        // LUI filling in the lowest 12 bits with zeros.
+        assert_eq!(c.r.x[1], 4096);
+    }
+
+    #[test]
+    fn instruction_auipc() {
         // Since this instruction implicitly performs an addition to the current pc
         // it is preferable to have an initial pc other than 0
-        let lui: u32 = (0b1 << 12) | (0b01 << 7) | 0b0010111;
-        let ram = lui.to_le_bytes().into();
-        let bus = Bus::new(ram);
-        let mut c = Cpu::new(bus);
+        let mut c = TestCpu::program(&[encode::u(Auipc, 1, 1).unwrap()]).build();
         c.cycle().unwrap();
         assert_eq!(c.r.x[1], 4096);
     }
+
+    /// Builder of the cpu under test. Its PMP grants all accesses to S-mode and U-mode as
+    /// firmware sets it up.
+    struct TestCpu {
+        ram: Vec<u8>,
+        done: Option<u32>,
+        timer: Option<u32>,
+    }
+
+    impl TestCpu {
+        fn program(program: &[u32]) -> Self {
+            TestCpu {
+                ram: program.iter().flat_map(|ir| ir.to_le_bytes()).collect(),
+                done: None,
+                timer: None,
+            }
+        }
+
+        /// Assemble src at 0 and remember the address of the label `done` if any.
+        fn asm(src: &str) -> Self {
+            let program = asm::assemble(src, 0).unwrap();
+            TestCpu {
+                done: program.label("done"),
+                ram: program.bytes,
+                timer: None,
+            }
+        }
+
+        /// Place data at 0x40 in RAM of 0x80 bytes.
+        fn data(mut self, data: &[u8]) -> Self {
+            self.ram.resize(0x40, 0);
+            self.ram.extend_from_slice(data);
+            self.ram.resize(0x80, 0);
+            self
+        }
+
+        /// Grow RAM to 0x4000 bytes with Sv32 page tables.
+        /// Root table at 0x1000 maps 0x0-0x3fffff to itself by a supervisor megapage and
+        /// 0x4000_0000 to the read-only page 0x3000 through the level 0 table at 0x2000.
+        fn page_table(mut self) -> Self {
+            self.ram.resize(0x4000, 0);
+            let ptes = [
+                (0x1000, 0b1100_1111),
+                (0x1000 + 4 * 0x100, 2 << 10 | 0b1),
+                (0x2000, 3 << 10 | 0b1100_0011),
+                (0x3000, 0x1234_5678_u32),
+            ];
+            for (addr, pte) in ptes {
+                self.ram[addr..addr + 4].copy_from_slice(&pte.to_le_bytes());
+            }
+            self
+        }
+
+        /// Map CLINT whose timer interrupt becomes pending after `cycles` and enable it.
+        fn timer(mut self, cycles: u32) -> Self {
+            self.timer = Some(cycles);
+            self
+        }
+
+        fn build(self) -> Cpu<Bus> {
+            let mut c = Cpu::new(Bus::new(self.ram));
+            c.csr.write(CsrAddr::Pmpaddr0 as usize, u32::MAX).unwrap();
+            c.csr.write(CsrAddr::Pmpcfg0 as usize, 0x1f).unwrap();
+            if let Some(cycles) = self.timer {
+                c.bus.map(Clint::BASE, Clint::new()).unwrap();
+                c.bus.write32(Clint::BASE + 0x4004, 0).unwrap();
+                c.bus.write32(Clint::BASE + 0x4000, cycles).unwrap();
+                c.csr.write(CsrAddr::Mtvec as usize, 0x100).unwrap();
+                c.csr.write(CsrAddr::Mie as usize, 1 << 7).unwrap();
+                c.csr.mstatus.set_mie(true);
+            }
+            c
+        }
+
+        /// Run the program until pc reaches the label `done`.
+        fn run_until_done(self) -> Cpu<Bus> {
+            let done = self.done.expect("label done");
+            let mut c = self.build();
+            run_until(&mut c, done, 100);
+            c
+        }
+    }
+
+    /// Cycle until pc reaches the address or panic after limit cycles.
+    fn run_until(c: &mut Cpu<Bus>, pc: u32, limit: usize) {
+        for _ in 0..limit {
+            if c.pc() == pc {
+                return;
+            }
+            c.cycle().unwrap();
+        }
+        panic!("pc did not reach {pc:#x}, currently {:#x}", c.pc());
+    }
+
+    #[test]
+    fn loop_sums_array() {
+        let mut c = TestCpu::asm(
+            "
+                    la a0, data
+                    li a1, 4          # count
+                    li a2, 0          # sum
+            loop:   lw t0, 0(a0)
+                    add a2, a2, t0
+                    addi a0, a0, 4
+                    addi a1, a1, -1
+                    bnez a1, loop
+            done:   j done
+            data:   .word 1, 20, 300, 4000
+            ",
+        )
+        .build();
+        run_until(&mut c, 0x24, 100);
+        assert_eq!(c.x(12), 4321);
+        assert_eq!(c.x(11), 0);
+    }
+
+    #[test]
+    fn call_and_return() {
+        let mut c = TestCpu::asm(
+            "
+                    li sp, 0x100
+                    li a0, 5
+                    call square
+            done:   j done
+            square: addi sp, sp, -4
+                    sw ra, 0(sp)
+                    mul a0, a0, a0
+                    lw ra, 0(sp)
+                    addi sp, sp, 4
+                    ret
+            .zero 0x100
+            ",
+        )
+        .build();
+        run_until(&mut c, 0xc, 100);
+        assert_eq!(c.x(10), 25);
+        assert_eq!(c.x(2), 0x100);
+        assert_eq!(c.x(1), 0xc);
+    }
+
+    #[test]
+    fn instruction_addi() {
+        let mut c = TestCpu::program(&[
+            encode::i(Addi, 1, 0, -1).unwrap(),
+            encode::i(Addi, 2, 1, 2).unwrap(),
+        ])
+        .build();
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[1], u32::MAX);
+        assert_eq!(c.r.x[2], 1);
+    }
+
+    #[test]
+    fn instruction_slti_sltiu() {
+        let mut c = TestCpu::program(&[
+            encode::i(Slti, 2, 1, 1).unwrap(),
+            encode::i(Sltiu, 3, 1, 1).unwrap(),
+        ])
+        .build();
+        c.r.x[1] = -1_i32 as u32;
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[2], 1);
+        assert_eq!(c.r.x[3], 0);
+    }
+
+    #[test]
+    fn instruction_shift_imm() {
+        let mut c = TestCpu::program(&[
+            encode::i(Slli, 2, 1, 4).unwrap(),
+            encode::i(Srli, 3, 1, 4).unwrap(),
+            encode::i(Srai, 4, 1, 4).unwrap(),
+        ])
+        .build();
+        c.r.x[1] = 0x8000_00f0;
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[2], 0x0000_0f00);
+        assert_eq!(c.r.x[3], 0x0800_000f);
+        assert_eq!(c.r.x[4], 0xf800_000f);
+    }
+
+    #[test]
+    fn instruction_add_sub() {
+        let mut c = TestCpu::program(&[
+            encode::r(Add, 3, 1, 2).unwrap(),
+            encode::r(Sub, 4, 1, 2).unwrap(),
+        ])
+        .build();
+        c.r.x[1] = u32::MAX;
+        c.r.x[2] = 2;
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[3], 1);
+        assert_eq!(c.r.x[4], u32::MAX - 2);
+    }
+
+    #[test]
+    fn instruction_slt_sltu_sra() {
+        let mut c = TestCpu::program(&[
+            encode::r(Slt, 3, 1, 2).unwrap(),
+            encode::r(Sltu, 4, 1, 2).unwrap(),
+            encode::r(Sra, 5, 1, 2).unwrap(),
+        ])
+        .build();
+        c.r.x[1] = 0x8000_0000;
+        // only the lower 5 bits are used as shift amount
+        c.r.x[2] = 0x21;
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[3], 1);
+        assert_eq!(c.r.x[4], 0);
+        assert_eq!(c.r.x[5], 0xc000_0000);
+    }
+
+    #[test]
+    fn instruction_store() {
+        let mut c = TestCpu::program(&[
+            encode::s(Sw, 1, 0, 16).unwrap(),
+            encode::s(Sh, 1, 0, 20).unwrap(),
+            encode::s(Sb, 1, 0, 22).unwrap(),
+            0,
+            0,
+            0,
+        ])
+        .build();
+        c.r.x[1] = 0x1234_5678;
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.bus.read32(16).unwrap(), 0x1234_5678);
+        assert_eq!(c.bus.read32(20).unwrap(), 0x0078_5678);
+    }
+
+    #[test]
+    fn fence_i_executes_modified_code() {
+        let mut c = TestCpu::asm(
+            "
+                    sw x1, 8(x0)
+                    fence.i
+                    .word 0
+            ",
+        )
+        .build();
+        c.r.x[1] = encode::i(Addi, 2, 0, 5).unwrap();
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[2], 5);
+        assert_eq!(c.r.pc, 12);
+    }
+
+    #[test]
+    fn load_uses_base_register_value() {
+        let mut c = TestCpu::asm("lw x2, -4(x1)")
+            .data(&[0, 0, 0, 0, 0x78, 0x56, 0x34, 0x12])
+            .build();
+        c.r.x[1] = 0x48;
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[2], 0x1234_5678);
+    }
+
+    #[test]
+    fn load_byte_sign_extension() {
+        let mut c = TestCpu::asm(
+            "
+                    lb x2, 0(x1)
+                    lbu x3, 0(x1)
+                    lb x4, 1(x1)
+            ",
+        )
+        .data(&[0x80, 0x7f])
+        .build();
+        c.r.x[1] = 0x40;
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[2], 0xffff_ff80);
+        assert_eq!(c.r.x[3], 0x0000_0080);
+        assert_eq!(c.r.x[4], 0x0000_007f);
+    }
+
+    #[test]
+    fn load_halfword_sign_extension() {
+        let mut c = TestCpu::asm(
+            "
+                    lh x2, 2(x1)
+                    lhu x3, 2(x1)
+                    lh x4, 0(x1)
+            ",
+        )
+        .data(&[0xff, 0x7f, 0x00, 0x80])
+        .build();
+        c.r.x[1] = 0x40;
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[2], 0xffff_8000);
+        assert_eq!(c.r.x[3], 0x0000_8000);
+        assert_eq!(c.r.x[4], 0x0000_7fff);
+    }
+
+    #[test]
+    fn store_uses_base_register_value() {
+        let mut c = TestCpu::asm(
+            "
+                    sw x2, 4(x1)
+                    sh x2, -2(x1)
+                    sb x2, -3(x1)
+            ",
+        )
+        .data(&[])
+        .build();
+        c.r.x[1] = 0x44;
+        c.r.x[2] = 0x1234_5678;
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.bus.read32(0x48).unwrap(), 0x1234_5678);
+        assert_eq!(c.bus.read32(0x40).unwrap(), 0x5678_7800);
+    }
+
+    #[test]
+    fn effective_address_wraps_around() {
+        // lw x2, -4(x1) with x1 = 0 accesses 0xffff_fffc
+        let mut c = TestCpu::asm("lw x2, -4(x1)").data(&[]).build();
+        c.cycle().unwrap();
+        assert_trap(&c, Exception::LoadAccessFault, 0xffff_fffc);
+
+        // lw x2, 4(x1) with x1 = 0xffff_fffc accesses 0
+        let ir = encode::i(Lw, 2, 1, 4).unwrap();
+        let mut c = TestCpu::program(&[ir]).data(&[]).build();
+        c.r.x[1] = 0xffff_fffc;
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[2], ir);
+    }
+
+    #[test]
+    fn auipc_and_jal_wrap_around_at_top_of_address_space() {
+        let mut c = TestCpu::program(&[]).build();
+        c.r.pc = 0xffff_fffc;
+        let ir = c
+            .decoder
+            .try_decode(encode::u(Auipc, 1, 1).unwrap())
+            .unwrap();
+        let effect = c.process(ir).unwrap();
+        c.apply(effect, ir.size()).unwrap();
+        assert_eq!(c.r.x[1], 0x0000_0ffc);
+        assert_eq!(c.r.pc, 0);
+
+        c.r.pc = 0xffff_fffc;
+        let ir = c.decoder.try_decode(encode::j(1, 8).unwrap()).unwrap();
+        let effect = c.process(ir).unwrap();
+        c.apply(effect, ir.size()).unwrap();
+        assert_eq!(c.r.x[1], 0);
+        assert_eq!(c.r.pc, 4);
+    }
+
+    /// Execute M extension instruction for each (rs1, rs2, expected rd) case.
+    fn assert_mul_div(op_code: OpCode, cases: &[(u32, u32, u32)]) {
+        for &(rs1, rs2, expected) in cases {
+            let mut c = TestCpu::program(&[encode::r(op_code, 3, 1, 2).unwrap()]).build();
+            c.r.x[1] = rs1;
+            c.r.x[2] = rs2;
+            c.cycle().unwrap();
+            assert_eq!(
+                c.r.x[3], expected,
+                "{op_code:?} rs1: {rs1:#x} rs2: {rs2:#x}"
+            );
+        }
+    }
+
+    #[test]
+    fn instruction_mul() {
+        assert_mul_div(
+            Mul,
+            &[
+                (3, 7, 21),
+                (-3_i32 as u32, 7, -21_i32 as u32),
+                (0x8000_0000, 2, 0),
+            ],
+        );
+        assert_mul_div(
+            Mulh,
+            &[
+                (-1_i32 as u32, -1_i32 as u32, 0),
+                (0x8000_0000, 0x8000_0000, 0x4000_0000),
+                (-2_i32 as u32, 0x4000_0000, u32::MAX),
+            ],
+        );
+        assert_mul_div(
+            Mulhsu,
+            &[
+                (-1_i32 as u32, u32::MAX, u32::MAX),
+                (0x8000_0000, 0x8000_0000, 0xc000_0000),
+            ],
+        );
+        assert_mul_div(
+            Mulhu,
+            &[(u32::MAX, u32::MAX, 0xffff_fffe), (0x8000_0000, 2, 1)],
+        );
+    }
+
+    #[test]
+    fn instruction_div() {
+        assert_mul_div(
+            Div,
+            &[
+                (20, 6, 3),
+                (-20_i32 as u32, 6, -3_i32 as u32),
+                (20, 0, u32::MAX),
+                (0x8000_0000, -1_i32 as u32, 0x8000_0000),
+            ],
+        );
+        assert_mul_div(Divu, &[(-20_i32 as u32, 6, 0x2aaa_aaa7), (20, 0, u32::MAX)]);
+    }
+
+    #[test]
+    fn instruction_rem() {
+        assert_mul_div(
+            Rem,
+            &[
+                (20, 6, 2),
+                (-20_i32 as u32, 6, -2_i32 as u32),
+                (20, -6_i32 as u32, 2),
+                (20, 0, 20),
+                (0x8000_0000, -1_i32 as u32, 0),
+            ],
+        );
+        assert_mul_div(Remu, &[(-20_i32 as u32, 6, 2), (20, 0, 20)]);
+    }
+
+    #[test]
+    fn lr_sc_succeeds_with_reservation() {
+        let mut c = TestCpu::asm(
+            "
+                    lr.w x3, (x1)
+                    sc.w x4, x2, (x1)
+            ",
+        )
+        .data(&[1, 0, 0, 0])
+        .build();
+        c.r.x[1] = 0x40;
+        c.r.x[2] = 2;
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[3], 1);
+        assert_eq!(c.reservation, Some(0x40));
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[4], 0);
+        assert_eq!(c.reservation, None);
+        assert_eq!(c.bus.read32(0x40).unwrap(), 2);
+    }
+
+    #[test]
+    fn sc_fails_without_reservation() {
+        let mut c = TestCpu::asm("sc.w x4, x2, (x1)")
+            .data(&[1, 0, 0, 0])
+            .build();
+        c.r.x[1] = 0x40;
+        c.r.x[2] = 2;
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[4], 1);
+        assert_eq!(c.bus.read32(0x40).unwrap(), 1);
+    }
+
+    #[test]
+    fn store_invalidates_reservation() {
+        let mut c = TestCpu::asm(
+            "
+                    lr.w x3, (x1)
+                    sb x2, 1(x1)
+                    sc.w x4, x2, (x1)
+            ",
+        )
+        .data(&[])
+        .build();
+        c.r.x[1] = 0x40;
+        c.r.x[2] = 2;
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.reservation, None);
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[4], 1);
+    }
+
+    #[test]
+    fn amo_read_modify_write() {
+        let cases: &[(OpCode, u32, u32, u32)] = &[
+            // (op_code, memory, rs2, expected memory)
+            (AmoswapW, 5, 7, 7),
+            (AmoaddW, 5, u32::MAX, 4),
+            (AmoxorW, 0b1100, 0b1010, 0b0110),
+            (AmoandW, 0b1100, 0b1010, 0b1000),
+            (AmoorW, 0b1100, 0b1010, 0b1110),
+            (AmominW, -1_i32 as u32, 1, -1_i32 as u32),
+            (AmomaxW, -1_i32 as u32, 1, 1),
+            (AmominuW, -1_i32 as u32, 1, 1),
+            (AmomaxuW, -1_i32 as u32, 1, -1_i32 as u32),
+        ];
+        for &(op_code, mem, rs2, expected) in cases {
+            let ir = encode::amo(op_code, 3, 1, 2, false, false).unwrap();
+            let mut c = TestCpu::program(&[ir]).data(&mem.to_le_bytes()).build();
+            c.r.x[1] = 0x40;
+            c.r.x[2] = rs2;
+            c.cycle().unwrap();
+            assert_eq!(c.r.x[3], mem, "{op_code:?}");
+            assert_eq!(c.bus.read32(0x40).unwrap(), expected, "{op_code:?}");
+        }
+    }
+
+    #[test]
+    fn misaligned_amo_is_store_fault() {
+        let mut c = TestCpu::asm("amoadd.w x3, x2, (x1)").data(&[]).build();
+        c.r.x[1] = 0x42;
+        c.cycle().unwrap();
+        assert_trap(&c, Exception::StoreAddressMisaligned, 0x42);
+        assert_eq!(c.r.x[3], 0);
+    }
+
+    /// Assert that the last cycle took a trap from pc 0 to the handler at mtvec.
+    fn assert_trap(c: &Cpu<Bus>, cause: Exception, tval: u32) {
+        assert_eq!(c.csr.read(CsrAddr::Mcause as usize).unwrap(), cause as u32);
+        assert_eq!(c.csr.read(CsrAddr::Mtval as usize).unwrap(), tval);
+        assert_eq!(c.csr.read(CsrAddr::Mepc as usize).unwrap(), 0);
+        assert_eq!(c.r.pc, c.csr.read(CsrAddr::Mtvec as usize).unwrap() & !0b11);
+    }
+
+    #[test]
+    fn illegal_instruction_trap() {
+        let mut c = TestCpu::program(&[0xffff_ffff]).build();
+        c.csr.write(CsrAddr::Mtvec as usize, 0x100).unwrap();
+        let mut mstatus = c.csr.mstatus;
+        mstatus.set_mie(true);
+        c.csr.mstatus = mstatus;
+        c.cycle().unwrap();
+        assert_trap(&c, Exception::IllegalInstruction, 0xffff_ffff);
+
+        let mstatus = c.csr.mstatus;
+        assert!(!mstatus.mie());
+        assert!(mstatus.mpie());
+        assert_eq!(mstatus.mpp(), Mode::M.bits());
+        assert_eq!(c.mode, Mode::M);
+    }
+
+    #[test]
+    fn instruction_access_fault_trap() {
+        let mut c = TestCpu::program(&[]).build();
+        c.r.pc = 0x1000;
+        c.csr.write(CsrAddr::Mtvec as usize, 0x100).unwrap();
+        c.cycle().unwrap();
+        assert_eq!(
+            c.csr.read(CsrAddr::Mcause as usize).unwrap(),
+            Exception::InstructionAccessFault as u32
+        );
+        assert_eq!(c.csr.read(CsrAddr::Mtval as usize).unwrap(), 0x1000);
+        assert_eq!(c.csr.read(CsrAddr::Mepc as usize).unwrap(), 0x1000);
+        assert_eq!(c.r.pc, 0x100);
+    }
+
+    #[test]
+    fn ecall_and_ebreak_trap() {
+        let mut c = TestCpu::asm("ecall").build();
+        c.csr.write(CsrAddr::Mtvec as usize, 0x100).unwrap();
+        c.cycle().unwrap();
+        assert_trap(&c, Exception::EnvironmentCallFromMMode, 0);
+
+        // ecall from user mode
+        let mut c = TestCpu::asm("ecall").build();
+        c.mode = Mode::U;
+        c.cycle().unwrap();
+        assert_trap(&c, Exception::EnvironmentCallFromUMode, 0);
+        assert_eq!(c.csr.mstatus.mpp(), Mode::U.bits());
+        assert_eq!(c.mode, Mode::M);
+
+        let mut c = TestCpu::asm("ebreak").build();
+        c.cycle().unwrap();
+        assert_trap(&c, Exception::Breakpoint, 0);
+    }
+
+    #[test]
+    fn vectored_mode_exception_jumps_to_base() {
+        let mut c = TestCpu::asm("ecall").build();
+        c.csr.write(CsrAddr::Mtvec as usize, 0x101).unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 0x100);
+    }
+
+    #[test]
+    fn instruction_mret() {
+        let mut c = TestCpu::asm("mret").build();
+        c.csr.write(CsrAddr::Mepc as usize, 0x40).unwrap();
+        let mut mstatus = c.csr.mstatus;
+        mstatus.set_mpie(true);
+        mstatus.set_mpp(Mode::M.bits());
+        c.csr.mstatus = mstatus;
+        c.cycle().unwrap();
+
+        let mstatus = c.csr.mstatus;
+        assert_eq!(c.r.pc, 0x40);
+        assert_eq!(c.mode, Mode::M);
+        assert!(mstatus.mie());
+        assert!(mstatus.mpie());
+        assert_eq!(mstatus.mpp(), Mode::U.bits());
+    }
+
+    #[test]
+    fn mret_returns_to_previous_mode() {
+        let mut c = TestCpu::asm("mret").build();
+        c.csr.write(CsrAddr::Mepc as usize, 0x40).unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.mode, Mode::U);
+        assert!(!c.csr.mstatus.mie());
+
+        // Returning to a less-privileged mode clears MPRV.
+        let mut c = TestCpu::asm("mret").build();
+        c.csr.mstatus.set_mprv(true);
+        c.csr.mstatus.set_mpp(Mode::S.bits());
+        c.cycle().unwrap();
+        assert_eq!(c.mode, Mode::S);
+        assert!(!c.csr.mstatus.mprv());
+    }
+
+    #[test]
+    fn data_mode_follows_mprv() {
+        let mut c = TestCpu::program(&[]).build();
+        c.csr.mstatus.set_mpp(Mode::U.bits());
+        assert_eq!(c.data_mode(), Mode::M);
+        c.csr.mstatus.set_mprv(true);
+        assert_eq!(c.data_mode(), Mode::U);
+        // MPRV only takes effect in M-mode.
+        c.mode = Mode::S;
+        assert_eq!(c.data_mode(), Mode::S);
+    }
+
+    #[test]
+    fn user_mode_program() {
+        let c = TestCpu::asm(
+            "
+                    la t0, trap
+                    csrw mtvec, t0
+                    la t0, user
+                    csrw mepc, t0
+                    mret                # MPP is U after reset
+            user:   li a0, 1
+                    csrr a1, mstatus    # illegal in U-mode
+                    wfi                 # illegal in U-mode
+                    ecall
+            trap:   csrr t1, mcause
+                    li t2, 8
+                    beq t1, t2, done
+                    slli s0, s0, 4
+                    or s0, s0, t1
+                    csrr t0, mepc
+                    addi t0, t0, 4
+                    csrw mepc, t0
+                    mret
+            done:   j done
+            ",
+        )
+        .run_until_done();
+        assert_eq!(c.x(10), 1);
+        assert_eq!(c.x(11), 0);
+        // Two illegal instructions, then ecall from U-mode.
+        assert_eq!(c.x(8), 0x22);
+        assert_eq!(c.mode, Mode::M);
+        assert_eq!(c.csr.mstatus.mpp(), Mode::U.bits());
+    }
+
+    #[test]
+    fn delegated_trap_is_taken_in_supervisor_mode() {
+        let mut c = TestCpu::asm("ecall").build();
+        c.mode = Mode::U;
+        c.csr.write(CsrAddr::Mtvec as usize, 0x100).unwrap();
+        c.csr.write(CsrAddr::Stvec as usize, 0x201).unwrap();
+        c.csr.write(CsrAddr::Medeleg as usize, 1 << 8).unwrap();
+        c.csr.mstatus.set_sie(true);
+        c.cycle().unwrap();
+
+        // Synchronous exceptions jump to BASE even in vectored mode.
+        assert_eq!(c.r.pc, 0x200);
+        assert_eq!(c.mode, Mode::S);
+        assert_eq!(
+            c.csr.scause.code(),
+            Exception::EnvironmentCallFromUMode as u32
+        );
+        assert_eq!(c.csr.sepc, 0);
+        let mstatus = c.csr.mstatus;
+        assert!(!mstatus.sie());
+        assert!(mstatus.spie());
+        assert!(!mstatus.spp());
+        assert_eq!(c.csr.mcause.code(), 0);
+
+        // Traps taken in M-mode are never delegated.
+        let mut c = TestCpu::asm("ecall").build();
+        c.csr.write(CsrAddr::Mtvec as usize, 0x100).unwrap();
+        c.csr.write(CsrAddr::Medeleg as usize, 1 << 11).unwrap();
+        c.cycle().unwrap();
+        assert_trap(&c, Exception::EnvironmentCallFromMMode, 0);
+        assert_eq!(c.mode, Mode::M);
+
+        // ecall from S-mode is not delegated unless medeleg says so.
+        let mut c = TestCpu::asm("ecall").build();
+        c.mode = Mode::S;
+        c.csr.write(CsrAddr::Medeleg as usize, 1 << 8).unwrap();
+        c.cycle().unwrap();
+        assert_trap(&c, Exception::EnvironmentCallFromSMode, 0);
+        assert_eq!(c.csr.mstatus.mpp(), Mode::S.bits());
+    }
+
+    #[test]
+    fn instruction_sret() {
+        let mut c = TestCpu::asm("sret").build();
+        c.mode = Mode::S;
+        c.csr.write(CsrAddr::Sepc as usize, 0x40).unwrap();
+        c.csr.mstatus.set_spie(true);
+        c.csr.mstatus.set_spp(true);
+        c.cycle().unwrap();
+
+        let mstatus = c.csr.mstatus;
+        assert_eq!(c.r.pc, 0x40);
+        assert_eq!(c.mode, Mode::S);
+        assert!(mstatus.sie());
+        assert!(mstatus.spie());
+        assert!(!mstatus.spp());
+
+        // SPP=U
+        let mut c = TestCpu::asm("sret").build();
+        c.cycle().unwrap();
+        assert_eq!(c.mode, Mode::U);
+    }
+
+    #[test]
+    fn privileged_instruction_is_illegal_in_lower_mode() {
+        for (op_code, mode) in [(Mret, Mode::S), (Sret, Mode::U), (Wfi, Mode::U)] {
+            let ir = encode::encode(op_code, Default::default()).unwrap();
+            let mut c = TestCpu::program(&[ir]).build();
+            c.mode = mode;
+            c.cycle().unwrap();
+            assert_trap(&c, Exception::IllegalInstruction, ir);
+        }
+
+        // sret with mstatus.TSR, wfi with mstatus.TW
+        for op_code in [Sret, Wfi] {
+            let ir = encode::encode(op_code, Default::default()).unwrap();
+            let mut c = TestCpu::program(&[ir]).build();
+            c.mode = Mode::S;
+            let (tsr, tw) = (1 << 22, 1 << 21);
+            c.csr.write(CsrAddr::Mstatus as usize, tsr | tw).unwrap();
+            c.cycle().unwrap();
+            assert_trap(&c, Exception::IllegalInstruction, ir);
+        }
+    }
+
+    #[test]
+    fn compressed_instructions() {
+        let mut c = TestCpu::asm(
+            "
+                    .half 0x4515        # c.li a0, 5
+                    addi a0, a0, 1      # 32-bit instruction at pc 2
+                    .half 0x2021        # c.jal 8
+                    .half 0x0001        # c.nop
+            done:   j done
+                    .half 0x0505        # c.addi a0, 1
+                    .half 0x8082        # c.ret
+            ",
+        )
+        .build();
+        c.cycle().unwrap();
+        assert_eq!((c.r.pc, c.x(10)), (2, 5));
+        c.cycle().unwrap();
+        assert_eq!((c.r.pc, c.x(10)), (6, 6));
+        c.cycle().unwrap();
+        // Link register points to the next 16-bit instruction.
+        assert_eq!((c.r.pc, c.x(1)), (0xe, 8));
+        run_until(&mut c, 0xa, 10);
+        assert_eq!(c.x(10), 7);
+        assert_eq!(c.state().instructions_retired, 6);
+    }
+
+    #[test]
+    fn compressed_instruction_at_end_of_memory() {
+        let mut c = TestCpu::asm(
+            "
+                    nop
+                    .half 0x4505        # c.li a0, 1 and no more memory
+            ",
+        )
+        .build();
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.x(10), 1);
+        assert_eq!(c.r.pc, 6);
+        c.cycle().unwrap();
+        assert_eq!(
+            c.csr.mcause.code(),
+            Exception::InstructionAccessFault as u32
+        );
+        assert_eq!((c.csr.mtval, c.csr.mepc), (6, 6));
+
+        // 32-bit instruction whose upper half is outside of memory.
+        let mut c = TestCpu::asm(".half 0x0001, 0x0013").build();
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(
+            c.csr.mcause.code(),
+            Exception::InstructionAccessFault as u32
+        );
+        assert_eq!((c.csr.mtval, c.csr.mepc), (4, 2));
+    }
+
+    #[test]
+    fn illegal_compressed_instruction_trap() {
+        // c.nop, then c.lwsp x0 which is reserved.
+        let mut c = TestCpu::asm(".half 0x0001, 0x4002").build();
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.csr.mcause.code(), Exception::IllegalInstruction as u32);
+        assert_eq!((c.csr.mtval, c.csr.mepc), (0x4002, 2));
+    }
+
+    #[test]
+    fn jump_to_halfword_boundary() {
+        // IALIGN=16 with the C extension, jal x1, 6 does not trap.
+        let mut c = TestCpu::asm("jal x1, 6").build();
+        c.csr.write(CsrAddr::Mtvec as usize, 0x100).unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 6);
+        assert_eq!(c.r.x[1], 4);
+    }
+
+    #[test]
+    fn instruction_csr() {
+        let mut c = TestCpu::asm(
+            "
+                    csrrw x2, mscratch, x1
+                    csrrs x3, mscratch, x0
+                    csrrci x4, mscratch, 0b11
+            ",
+        )
+        .build();
+        c.r.x[1] = 0xf;
+        c.csr.mscratch = 1;
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[2], 1);
+        assert_eq!(c.r.x[3], 0xf);
+        assert_eq!(c.r.x[4], 0xf);
+        assert_eq!(c.csr.mscratch, 0xc);
+    }
+
+    #[test]
+    fn read_only_csr_can_be_read_without_write() {
+        let mut c = TestCpu::asm("csrrs x2, mhartid, x0").build();
+        c.r.x[2] = 1;
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[2], 0);
+        assert_eq!(c.r.pc, 4);
+    }
+
+    #[test]
+    fn illegal_csr_access_trap() {
+        let cases = [
+            (Csrrw, CsrAddr::Mhartid as usize, 1),
+            // not implemented
+            (Csrrs, 0x7c0, 0),
+            (Csrrsi, CsrAddr::Cycle as usize, 1),
+        ];
+        for (op_code, csr, src) in cases {
+            let ir = encode::csr(op_code, 2, csr, src).unwrap();
+            let mut c = TestCpu::program(&[ir]).build();
+            c.csr.write(CsrAddr::Mtvec as usize, 0x100).unwrap();
+            c.cycle().unwrap();
+            assert_trap(&c, Exception::IllegalInstruction, ir);
+        }
+
+        // csrrs x2, mstatus, x0 from user mode
+        let ir = encode::csr(Csrrs, 2, CsrAddr::Mstatus as usize, 0).unwrap();
+        let mut c = TestCpu::program(&[ir]).build();
+        c.mode = Mode::U;
+        c.cycle().unwrap();
+        assert_trap(&c, Exception::IllegalInstruction, ir);
+    }
+
+    #[test]
+    fn rejected_csr_write_after_access_check_is_cpu_error() {
+        let mut c = TestCpu::program(&[]).build();
+        let effect = Effect::Csr {
+            rd: 2,
+            rd_value: 1,
+            csr: CsrAddr::Mhartid as usize,
+            csr_value: Some(1),
+        };
+        let err = c.apply(effect, 4).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Fault::Cpu(CpuError::CsrWrite {
+                    pc: 0,
+                    csr: 0xf14,
+                    source: CsrError::ReadOnly(0xf14),
+                })
+            ),
+            "{err:?}"
+        );
+        assert_eq!(c.r.x[2], 0);
+        assert_eq!(c.r.pc, 0);
+    }
+
+    #[test]
+    fn minstret_counts_retired_instructions() {
+        let mut c = TestCpu::asm(
+            "
+                    nop
+                    .word 0             # illegal
+            ",
+        )
+        .build();
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.csr.mcycle, 2);
+        assert_eq!(c.csr.minstret, 1);
+    }
+
+    #[test]
+    fn time_reads_timer() {
+        let mut c = TestCpu::asm(
+            "
+                    csrrs x10, time, x0
+                    csrrs x11, timeh, x0
+            ",
+        )
+        .timer(u32::MAX)
+        .build();
+        c.bus.write32(Clint::BASE + 0xbff8, 0xffff_fff0).unwrap();
+        c.bus.write32(Clint::BASE + 0xbffc, 5).unwrap();
+        c.bus.write32(Clint::BASE + 0x4004, u32::MAX).unwrap();
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        // mtime is incremented before each instruction.
+        assert_eq!(c.r.x[10], 0xffff_fff1);
+        assert_eq!(c.r.x[11], 5);
+    }
+
+    #[test]
+    fn timer_interrupt() {
+        let addi = encode::i(Addi, 1, 1, 1).unwrap();
+        let mut c = TestCpu::program(&[addi, addi, addi]).timer(2).build();
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 4);
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 0x100);
+        assert_eq!(c.r.x[1], 1);
+        assert_eq!(c.csr.read(CsrAddr::Mcause as usize).unwrap(), 0x8000_0007);
+        assert_eq!(c.csr.mepc, 4);
+        assert_ne!(c.csr.read(CsrAddr::Mip as usize).unwrap() & Mip::MTIP, 0);
+        assert!(!c.csr.mstatus.mie());
+        assert!(c.csr.mstatus.mpie());
+    }
+
+    #[test]
+    fn interrupt_is_masked_by_mstatus_mie() {
+        let addi = encode::i(Addi, 1, 1, 1).unwrap();
+        let mut c = TestCpu::program(&[addi, addi, addi]).timer(0).build();
+        c.csr.mstatus.set_mie(false);
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 8);
+        assert_ne!(c.csr.read(CsrAddr::Mip as usize).unwrap() & Mip::MTIP, 0);
+
+        // Machine interrupts are always enabled in user mode.
+        c.mode = Mode::U;
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 0x100);
+        assert_eq!(c.mode, Mode::M);
+    }
+
+    #[test]
+    fn vectored_interrupt() {
+        let mut c = TestCpu::program(&[0]).timer(0).build();
+        c.csr.write(CsrAddr::Mtvec as usize, 0x101).unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 0x100 + 4 * 7);
+    }
+
+    #[test]
+    fn software_interrupt() {
+        let mut c = TestCpu::program(&[0]).build();
+        c.csr.write(CsrAddr::Mtvec as usize, 0x100).unwrap();
+        c.csr.write(CsrAddr::Mie as usize, 1 << 3).unwrap();
+        c.csr.mstatus.set_mie(true);
+        c.bus.map(Clint::BASE, Clint::new()).unwrap();
+        c.bus.write32(Clint::BASE, 1).unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 0x100);
+        assert_eq!(c.csr.read(CsrAddr::Mcause as usize).unwrap(), 0x8000_0003);
+    }
+
+    /// Flat memory which is neither a device nor an interrupt source.
+    struct Memory(Vec<u8>);
+
+    impl BusRead for Memory {
+        fn read8(&self, addr: u32) -> Result<u8, BusReadException> {
+            let byte = self.0.get(addr as usize);
+            byte.copied().ok_or(BusReadException::LoadAccessFault)
+        }
+
+        fn read16(&self, addr: u32) -> Result<u16, BusReadException> {
+            Ok(u16::from_le_bytes([
+                self.read8(addr)?,
+                self.read8(addr + 1)?,
+            ]))
+        }
+
+        fn read32(&self, addr: u32) -> Result<u32, BusReadException> {
+            Ok(self.read16(addr)? as u32 | (self.read16(addr + 2)? as u32) << 16)
+        }
+    }
+
+    impl BusWrite for Memory {
+        fn write8(&mut self, addr: u32, v: u8) -> Result<(), BusWriteException> {
+            let byte = self.0.get_mut(addr as usize);
+            *byte.ok_or(BusWriteException::StoreAccessFault)? = v;
+            Ok(())
+        }
+
+        fn write16(&mut self, addr: u32, v: u16) -> Result<(), BusWriteException> {
+            self.write8(addr, v as u8)?;
+            self.write8(addr + 1, (v >> 8) as u8)
+        }
+
+        fn write32(&mut self, addr: u32, v: u32) -> Result<(), BusWriteException> {
+            self.write16(addr, v as u16)?;
+            self.write16(addr + 2, (v >> 16) as u16)
+        }
+    }
+
+    #[test]
+    fn step_without_interrupt_source() {
+        let program = asm::assemble("addi x1, x0, 42", 0).unwrap();
+        let mut c = Cpu::new(Memory(program.bytes));
+        c.write_csr(CsrAddr::Mtvec as usize, 0x100).unwrap();
+        c.step().unwrap();
+        assert_eq!(c.x(1), 42);
+
+        // Interrupts already pending in mip are taken.
+        c.write_csr(CsrAddr::Mie as usize, Mip::SSIP).unwrap();
+        c.write_csr(CsrAddr::Mip as usize, Mip::SSIP).unwrap();
+        c.csr.mstatus.set_mie(true);
+        c.step().unwrap();
+        assert_eq!(c.pc(), 0x100);
+        assert_eq!(c.read_csr(CsrAddr::Mcause as usize), Ok(0x8000_0001));
+    }
+
+    #[test]
+    fn virtual_memory_program() {
+        let c = TestCpu::asm(
+            "
+                    li t0, 0x80000001   # Sv32, root page table at 0x1000
+                    csrw satp, t0
+                    la t0, done
+                    csrw mtvec, t0
+                    la t0, supervisor
+                    csrw mepc, t0
+                    li t0, 0x800        # MPP = S
+                    csrs mstatus, t0
+                    mret
+            supervisor:
+                    li t0, 0x40000000
+                    lw a0, 0(t0)
+            store:  sw a0, 4(t0)        # read-only page
+            done:   j done
+            ",
+        )
+        .page_table()
+        .run_until_done();
+        assert_eq!(c.x(10), 0x1234_5678);
+        assert_eq!(c.csr.mcause.code(), Exception::StorePageFault as u32);
+        assert_eq!(c.csr.mtval, 0x4000_0004);
+        assert_eq!(c.csr.mstatus.mpp(), Mode::S.bits());
+        assert_eq!(c.bus.read32(0x3004).unwrap(), 0);
+
+        // Page faults taken in S-mode are delegated.
+        let mut c = TestCpu::asm("jalr zero, 0(t0)").page_table().build();
+        c.csr.write(CsrAddr::Satp as usize, 0x8000_0001).unwrap();
+        c.csr.write(CsrAddr::Medeleg as usize, 1 << 12).unwrap();
+        c.csr.write(CsrAddr::Stvec as usize, 0x100).unwrap();
+        c.mode = Mode::S;
+        c.set_x(5, 0x5000_0000);
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.mode, Mode::S);
+        assert_eq!(c.r.pc, 0x100);
+        assert_eq!(c.csr.scause.code(), Exception::InstructionPageFault as u32);
+        assert_eq!(c.csr.stval, 0x5000_0000);
+        assert_eq!(c.csr.sepc, 0x5000_0000);
+    }
+
+    #[test]
+    fn instruction_fetch_across_pages() {
+        let mut c = TestCpu::asm("nop").page_table().build();
+        // 0x4000_0000 maps the executable page 0x0 without A and 0x4000_1000 is not mapped.
+        c.bus.write32(0x2000, 0b0000_1001).unwrap();
+        c.csr.write(CsrAddr::Satp as usize, 0x8000_0001).unwrap();
+        c.mmu.hardware_ad_update = true;
+        // Lower parcel of addi a0, a0, 1
+        c.bus.write16(0xffe, 0x0513).unwrap();
+        c.mode = Mode::S;
+        c.r.pc = 0x4000_0ffe;
+        c.cycle().unwrap();
+        assert_eq!(c.csr.mcause.code(), Exception::InstructionPageFault as u32);
+        assert_eq!(c.csr.mtval, 0x4000_1000);
+        assert_eq!(c.csr.mepc, 0x4000_0ffe);
+        assert_eq!(c.bus.read32(0x2000).unwrap(), 0b0100_1001);
+
+        // Compressed instruction at the end of the page does not access the next page.
+        // c.addi a0, 1
+        c.bus.write16(0xffe, 0x0505).unwrap();
+        c.mode = Mode::S;
+        c.r.pc = 0x4000_0ffe;
+        c.cycle().unwrap();
+        assert_eq!(c.x(10), 1);
+        assert_eq!(c.r.pc, 0x4000_1000);
+    }
+
+    #[test]
+    fn mprv_translates_machine_mode_loads() {
+        let mut c = TestCpu::asm("lw a0, 0(t0)").page_table().build();
+        c.csr.write(CsrAddr::Satp as usize, 0x8000_0001).unwrap();
+        c.set_x(5, 0x4000_0000);
+        c.csr.mstatus.set_mprv(true);
+        c.csr.mstatus.set_mpp(Mode::S.bits());
+        c.cycle().unwrap();
+        assert_eq!(c.x(10), 0x1234_5678);
+        assert_eq!(c.r.pc, 4);
+    }
+
+    #[test]
+    fn instruction_sfence_vma() {
+        // sfence.vma a0, a1
+        let ir = encode::r(SfenceVma, 0, 10, 11).unwrap();
+        let mut c = TestCpu::program(&[ir]).build();
+        c.mode = Mode::S;
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 4);
+
+        let mut c = TestCpu::program(&[ir]).build();
+        c.mode = Mode::U;
+        c.cycle().unwrap();
+        assert_trap(&c, Exception::IllegalInstruction, ir);
+
+        let mut c = TestCpu::program(&[ir]).build();
+        c.mode = Mode::S;
+        c.csr.write(CsrAddr::Mstatus as usize, 1 << 20).unwrap();
+        c.cycle().unwrap();
+        assert_trap(&c, Exception::IllegalInstruction, ir);
+    }
+
+    #[test]
+    fn pmp_program() {
+        let c = TestCpu::asm(
+            "
+                    la t0, trap
+                    csrw mtvec, t0
+                    li t0, 0x40         # 0x0-0xff by TOR
+                    csrw pmpaddr0, t0
+                    csrw pmpaddr1, t0   # 0x100-0x103 by NA4
+                    li t0, 0x41         # 0x100-0x10f by NAPOT
+                    csrw pmpaddr2, t0
+                    li t0, 0x1b110d     # RW, R and RX
+                    csrw pmpcfg0, t0
+                    la t0, user
+                    csrw mepc, t0
+                    mret
+            user:   lw a0, 0x100(zero)
+                    sw a0, 0x104(zero)
+                    sw a0, 0x100(zero)  # store access fault
+                    lw a1, 0x110(zero)  # load access fault
+                    ecall
+            trap:   csrr t1, mcause
+                    li t2, 8
+                    beq t1, t2, done
+                    slli s0, s0, 4
+                    or s0, s0, t1
+                    csrr s1, mtval
+                    csrr t0, mepc
+                    addi t0, t0, 4
+                    csrw mepc, t0
+                    mret
+            done:   j done
+                    .zero 0x100
+            ",
+        )
+        .run_until_done();
+        assert_eq!(c.x(8), 0x75);
+        assert_eq!(c.x(9), 0x110);
+        assert_eq!(c.x(11), 0);
+    }
+
+    #[test]
+    fn locked_pmp_entry_applies_to_machine_mode() {
+        // sw a0, 0(a1)
+        let mut c = TestCpu::program(&[encode::s(Sw, 10, 11, 0).unwrap()])
+            .data(&[])
+            .build();
+        c.csr.write(CsrAddr::Mtvec as usize, 0x100).unwrap();
+        c.csr.write(CsrAddr::Pmpaddr0 as usize, 0x40 >> 2).unwrap();
+        c.csr.write(CsrAddr::Pmpaddr1 as usize, u32::MAX).unwrap();
+        // Locked read-only NA4 entry at 0x40 followed by the RWX entry for the whole memory
+        c.csr
+            .write(CsrAddr::Pmpcfg0 as usize, 0x1f << 8 | 0x91)
+            .unwrap();
+        c.r.x[11] = 0x40;
+        c.cycle().unwrap();
+        assert_trap(&c, Exception::StoreAccessFault, 0x40);
+
+        // Locked entries can not be changed until reset.
+        c.csr.write(CsrAddr::Pmpcfg0 as usize, 0x1f).unwrap();
+        c.csr.write(CsrAddr::Pmpaddr0 as usize, 0).unwrap();
+        assert_eq!(c.csr.read(CsrAddr::Pmpcfg0 as usize).unwrap(), 0x91);
+        assert_eq!(c.csr.read(CsrAddr::Pmpaddr0 as usize).unwrap(), 0x10);
+    }
+
+    #[test]
+    fn page_table_walk_is_checked_by_pmp() {
+        let mut c = TestCpu::asm("lw a0, 0(a1)").page_table().build();
+        c.mode = Mode::S;
+        c.r.x[11] = 0x4000_0000;
+        c.csr.write(CsrAddr::Mtvec as usize, 0x100).unwrap();
+        // Deny the root page table at 0x1000 by a higher priority NAPOT entry.
+        c.csr
+            .write(CsrAddr::Pmpaddr0 as usize, 0x1000 >> 2 | 0x1ff)
+            .unwrap();
+        c.csr.write(CsrAddr::Pmpaddr1 as usize, u32::MAX).unwrap();
+        c.csr
+            .write(CsrAddr::Pmpcfg0 as usize, 0x1f << 8 | 0x18)
+            .unwrap();
+        c.cycle().unwrap();
+        assert_trap(&c, Exception::LoadAccessFault, 0x4000_0000);
+    }
+
+    #[test]
+    fn delegated_interrupt() {
+        let mut c = TestCpu::program(&[encode::i(Addi, 0, 0, 0).unwrap(); 4]).build();
+        c.csr.write(CsrAddr::Stvec as usize, 0x201).unwrap();
+        c.csr.write(CsrAddr::Mideleg as usize, 1 << 5).unwrap();
+        c.csr.write(CsrAddr::Mie as usize, 1 << 5).unwrap();
+        c.csr.write(CsrAddr::Mip as usize, 1 << 5).unwrap();
+
+        // Delegated interrupts are never taken in M-mode.
+        c.csr.mstatus.set_mie(true);
+        c.csr.mstatus.set_sie(true);
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 4);
+
+        // Masked by mstatus.SIE in S-mode.
+        c.mode = Mode::S;
+        c.csr.mstatus.set_sie(false);
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 8);
+
+        // Always enabled in U-mode.
+        c.mode = Mode::U;
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 0x200 + 4 * 5);
+        assert_eq!(c.mode, Mode::S);
+        assert_eq!(c.csr.read(CsrAddr::Scause as usize).unwrap(), 0x8000_0005);
+        assert_eq!(c.csr.sepc, 8);
+        assert!(!c.csr.mstatus.spp());
+    }
+
+    #[test]
+    fn wfi_stalls_until_interrupt() {
+        let mut c = TestCpu::asm(
+            "
+                    wfi
+                    .word 0
+            ",
+        )
+        .timer(4)
+        .build();
+        c.csr.mstatus.set_mie(false);
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 4);
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 4);
+        assert_eq!(c.csr.minstret, 1);
+        // Interrupt is pending but globally disabled, execution resumes after wfi.
+        c.cycle().unwrap();
+        assert!(!c.wfi);
+        assert_eq!(c.r.pc, 0x100);
+        assert_eq!(c.csr.mcause.code(), Exception::IllegalInstruction as u32);
+    }
+
+    #[test]
+    fn floating_point_program() {
+        let c = TestCpu::asm(
+            "
+                    li t0, 0x2000       # mstatus.FS = Initial
+                    csrs mstatus, t0
+                    li a0, 3
+                    fcvt.s.w fa0, a0
+                    li a1, 4
+                    fcvt.s.w fa1, a1
+                    fmul.s fa2, fa0, fa0
+                    fmadd.s fa2, fa1, fa1, fa2
+                    fsqrt.s fa3, fa2
+                    fcvt.w.s a2, fa3
+                    la a3, data
+                    fsw fa3, 0(a3)
+                    flw fa4, 4(a3)
+                    flt.s a4, fa4, fa3
+                    fmv.x.w a5, fa4
+            done:   j done
+            data:   .word 0, 0xbf800000
+            ",
+        )
+        .run_until_done();
+        assert_eq!(c.x(12), 5);
+        assert_eq!(c.bus().read32(c.x(13)).unwrap(), 0x40a0_0000);
+        assert_eq!(c.f(14), 0xffff_ffff_bf80_0000);
+        assert_eq!((c.x(14), c.x(15)), (1, 0xbf80_0000));
+        assert_eq!(c.csr.fflags, 0);
+        assert_eq!(c.csr.mstatus.fs(), Mstatus::FS_DIRTY);
+    }
+
+    #[test]
+    fn double_precision_program() {
+        let c = TestCpu::asm(
+            "
+                    li t0, 0x2000
+                    csrs mstatus, t0
+                    la a0, data
+                    fld fa0, 0(a0)          # 0.1
+                    fld fa1, 8(a0)          # 3.0
+                    fmul.d fa2, fa0, fa1
+                    fsd fa2, 16(a0)
+                    fcvt.s.d fa3, fa0       # inexact
+                    fcvt.d.s fa4, fa3
+                    feq.d a1, fa4, fa0
+                    li a2, -7
+                    fcvt.d.w fa5, a2
+                    fcvt.w.d a3, fa5
+                    fdiv.d fa6, fa1, fa5
+                    fclass.d a4, fa6
+            done:   j done
+                    .align 3
+            data:   .word 0x9999999a, 0x3fb99999, 0x00000000, 0x40080000, 0, 0
+            ",
+        )
+        .run_until_done();
+        let data = c.x(10);
+        let stored = (c.bus().read32(data + 20).unwrap() as u64) << 32
+            | c.bus().read32(data + 16).unwrap() as u64;
+        assert_eq!(stored, (0.1f64 * 3.0).to_bits());
+        assert_eq!(c.f(13), 0xffff_ffff_0000_0000 | 0.1f32.to_bits() as u64);
+        assert_eq!(c.f(14), (0.1f32 as f64).to_bits());
+        assert_eq!((c.x(11), c.x(13)), (0, -7i32 as u32));
+        assert_eq!(c.f(16), (3.0f64 / -7.0).to_bits());
+        // Negative normal number
+        assert_eq!(c.x(14), 1 << 1);
+        assert_eq!(c.csr.fflags, 0b00001);
+    }
+
+    #[test]
+    fn doubleword_access_fault_is_precise() {
+        let mut c = TestCpu::asm(
+            "
+                    li t0, 0x2000
+                    csrs mstatus, t0
+                    fsd fa0, 0(a0)
+                    fld fa1, 0(a0)
+                    .word 0             # the last word of ram
+            ",
+        )
+        .build();
+        // The lower word of the doubleword at 0x10 is the last word of ram.
+        c.r.x[10] = 0x10;
+        c.r.f[10] = 0x1234_5678_9abc_def0;
+        c.r.f[11] = 1;
+        for _ in 0..3 {
+            c.cycle().unwrap();
+        }
+        assert_eq!(
+            c.csr.read(CsrAddr::Mcause as usize).unwrap(),
+            Exception::StoreAccessFault as u32
+        );
+        assert_eq!(c.csr.read(CsrAddr::Mtval as usize).unwrap(), 0x10);
+        assert_eq!(c.bus().read32(0x10).unwrap(), 0);
+
+        c.r.pc = 0xc;
+        c.cycle().unwrap();
+        assert_eq!(
+            c.csr.read(CsrAddr::Mcause as usize).unwrap(),
+            Exception::LoadAccessFault as u32
+        );
+        assert_eq!(c.r.f[11], 1);
+    }
+
+    #[test]
+    fn float_store_invalidates_overlapping_reservation() {
+        let c = TestCpu::asm(
+            "
+                    li t0, 0x2000
+                    csrs mstatus, t0
+                    la a0, data
+                    addi a1, a0, 4
+                    lr.w t1, (a1)
+                    fsw fa0, 0(a0)      # the word below the reservation
+                    sc.w a2, t1, (a1)
+                    lr.w t1, (a1)
+                    fsd fa0, 0(a0)      # both words
+                    sc.w a3, t1, (a1)
+            done:   j done
+                    .align 3
+            data:   .word 0, 0
+            ",
+        )
+        .run_until_done();
+        assert_eq!(c.x(12), 0);
+        assert_eq!(c.x(13), 1);
+    }
+
+    #[test]
+    fn misaligned_access_is_checked_before_translation() {
+        // Misaligned accesses trap before page faults of the read-only page and the unmapped page next to it.
+        let mut c = TestCpu::asm(
+            "
+                    fld fa0, 8(a0)
+                    fsd fa0, 0(a0)
+                    lw a1, 10(a0)
+            ",
+        )
+        .page_table()
+        .build();
+        c.csr.write(CsrAddr::Satp as usize, 0x8000_0001).unwrap();
+        c.csr.mstatus.set_fs(Mstatus::FS_DIRTY);
+        c.set_x(10, 0x4000_0ffc);
+        for (pc, cause, tval) in [
+            (0, Exception::LoadAddressMisaligned, 0x4000_1004),
+            (4, Exception::StoreAddressMisaligned, 0x4000_0ffc),
+            (8, Exception::LoadAddressMisaligned, 0x4000_1006),
+        ] {
+            c.mode = Mode::S;
+            c.r.pc = pc;
+            c.cycle().unwrap();
+            assert_eq!(c.csr.mcause.code(), cause as u32);
+            assert_eq!(c.csr.mtval, tval);
+            assert_eq!(c.csr.mepc, pc);
+        }
+    }
+
+    #[test]
+    fn nan_boxing() {
+        let c = TestCpu::asm(
+            "
+                    li t0, 0x2000
+                    csrs mstatus, t0
+                    la a0, data
+                    flw fa0, 0(a0)
+                    fld fa1, 0(a0)          # not a NaN-boxed single-precision value
+                    fadd.s fa2, fa1, fa0
+                    fmv.x.w a1, fa1
+                    fsw fa1, 8(a0)
+                    fmv.d fa3, fa0
+                    fclass.s a2, fa3
+                    fmv.w.x fa4, a1
+            done:   j done
+                    .align 3
+            data:   .word 0x3f800000, 0x7ff00000, 0
+            ",
+        )
+        .run_until_done();
+        assert_eq!(c.f(10), 0xffff_ffff_3f80_0000);
+        // Improperly NaN-boxed inputs are treated as the canonical NaN.
+        assert_eq!(c.f(12), 0xffff_ffff_7fc0_0000);
+        // FMV.X.W and FSW move the lower bits as is.
+        assert_eq!(c.x(11), 0x3f80_0000);
+        assert_eq!(c.bus().read32(c.x(10) + 8).unwrap(), 0x3f80_0000);
+        // Positive normal number
+        assert_eq!(c.x(12), 1 << 6);
+        assert_eq!(c.f(14), 0xffff_ffff_3f80_0000);
+    }
+
+    #[test]
+    fn floating_point_disabled_trap() {
+        // fadd.s fa0, fa0, fa0 while mstatus.FS is Off.
+        let ir = encode::fp(FaddS, 10, 10, 10, 0b111).unwrap();
+        let mut c = TestCpu::program(&[ir]).build();
+        c.cycle().unwrap();
+        assert_trap(&c, Exception::IllegalInstruction, ir);
+
+        // csrr a0, fflags
+        let ir = encode::csr(Csrrs, 10, CsrAddr::Fflags as usize, 0).unwrap();
+        let mut c = TestCpu::program(&[ir]).build();
+        c.cycle().unwrap();
+        assert_trap(&c, Exception::IllegalInstruction, ir);
+    }
+
+    #[test]
+    fn floating_point_rounding_mode() {
+        let c = TestCpu::asm(
+            "
+                    li t0, 0x2000
+                    csrs mstatus, t0
+                    li a0, 5
+                    fcvt.s.w fa0, a0
+                    li a0, 2
+                    fcvt.s.w fa1, a0
+                    fdiv.s fa0, fa0, fa1    # 2.5
+                    fcvt.w.s a1, fa0        # dynamic, frm is rne
+                    csrwi frm, 3            # rup
+                    fcvt.w.s a2, fa0
+                    fcvt.w.s a3, fa0, rtz
+            done:   j done
+            ",
+        )
+        .run_until_done();
+        assert_eq!((c.x(11), c.x(12), c.x(13)), (2, 3, 2));
+        // Conversions of 2.5 are inexact.
+        assert_eq!(c.csr.read(CsrAddr::Fcsr as usize).unwrap(), 3 << 5 | 1);
+
+        // Dynamic rounding mode is illegal if frm holds an invalid value.
+        let ir = encode::fp(FaddS, 10, 10, 10, 0b111).unwrap();
+        let mut c = TestCpu::program(&[ir]).build();
+        c.csr.mstatus.set_fs(Mstatus::FS_DIRTY);
+        c.csr.frm = 0b101;
+        c.cycle().unwrap();
+        assert_trap(&c, Exception::IllegalInstruction, ir);
+    }
+
+    #[test]
+    fn floating_point_exception_flags_accrue() {
+        let c = TestCpu::asm(
+            "
+                    li t0, 0x2000
+                    csrs mstatus, t0
+                    li a0, 1
+                    fcvt.s.w fa0, a0
+                    fmv.w.x fa1, zero
+                    fdiv.s fa2, fa0, fa1    # 1 / 0 raises DZ
+                    li a0, 3
+                    fcvt.s.w fa1, a0
+                    fdiv.s fa3, fa0, fa1    # 1 / 3 raises NX
+                    csrr a1, fflags
+                    fsqrt.s fa4, fa2
+                    fsqrt.s fa5, fa0        # exact, flags are kept
+                    csrrw a2, fflags, zero  # swap flags
+                    csrr a3, mstatus
+            done:   j done
+            ",
+        )
+        .run_until_done();
+        assert_eq!(c.f(12), 0xffff_ffff_7f80_0000);
+        assert_eq!(c.x(11), 0b01001);
+        assert_eq!(c.x(12), 0b01001);
+        assert_eq!(c.csr.fflags, 0);
+        // FS is Dirty and SD summarizes it.
+        assert_eq!(c.x(13) & 0x8000_6000, 0x8000_6000);
+    }
+
+    #[test]
+    fn branch_offset() {
+        let mut c = TestCpu::asm(
+            "
+                    beq x0, x0, 12
+                    .zero 20
+                    bne x1, x0, -20
+                    beq x0, x0, 6
+            ",
+        )
+        .build();
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 12);
+
+        // Offset bits 1 to 3 are also used by backward branches.
+        c.r.pc = 0x18;
+        c.r.x[1] = 1;
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 0x4);
+
+        // Offset bit 1 targets a halfword boundary with compressed instructions.
+        c.r.pc = 0x1c;
+        c.cycle().unwrap();
+        assert_eq!(c.r.pc, 0x22);
+    }
+
+    #[test]
+    fn write_to_x0_is_ignored() {
+        let mut c = TestCpu::asm("addi x0, x0, 1").build();
+        c.cycle().unwrap();
+        assert_eq!(c.r.x[0], 0);
+    }
+
+    /// Bus implemented outside of the crate. 16 bytes of memory with word access only.
+    struct WordBus([u32; 4]);
+
+    impl BusRead for WordBus {
+        fn read8(&self, _addr: u32) -> Result<u8, BusReadException> {
+            Err(BusReadException::LoadAccessFault)
+        }
+        fn read16(&self, _addr: u32) -> Result<u16, BusReadException> {
+            Err(BusReadException::LoadAccessFault)
+        }
+        fn read32(&self, addr: u32) -> Result<u32, BusReadException> {
+            self.0
+                .get(addr as usize / 4)
+                .copied()
+                .ok_or(BusReadException::LoadAccessFault)
+        }
+    }
+
+    impl BusWrite for WordBus {
+        fn write8(&mut self, _addr: u32, _v: u8) -> Result<(), BusWriteException> {
+            Err(BusWriteException::StoreAccessFault)
+        }
+        fn write16(&mut self, _addr: u32, _v: u16) -> Result<(), BusWriteException> {
+            Err(BusWriteException::StoreAccessFault)
+        }
+        fn write32(&mut self, addr: u32, v: u32) -> Result<(), BusWriteException> {
+            let word = self
+                .0
+                .get_mut(addr as usize / 4)
+                .ok_or(BusWriteException::StoreAccessFault)?;
+            *word = v;
+            Ok(())
+        }
+    }
+
+    impl InterruptSource for WordBus {}
+
+    #[test]
+    fn embedding_api() {
+        let mut c = Cpu::new(WordBus([
+            encode::r(Add, 1, 1, 2).unwrap(),
+            encode::s(Sw, 1, 0, 12).unwrap(),
+            encode::csr(Csrrw, 0, CsrAddr::Mscratch as usize, 1).unwrap(),
+            0,
+        ]));
+        c.set_x(1, 40);
+        c.set_x(2, 2);
+        c.set_x(0, 1);
+        assert_eq!(c.x(0), 0);
+
+        c.cycle().unwrap();
+        assert_eq!(c.x(1), 42);
+        c.cycle().unwrap();
+        assert_eq!(c.bus().0[3], 42);
+        c.cycle().unwrap();
+        assert_eq!(c.read_csr(CsrAddr::Mscratch as usize), Ok(42));
+        assert_eq!(c.pc(), 12);
+        assert_eq!(c.mode(), Mode::M);
+        assert_eq!(c.state().instructions_retired, 3);
+
+        c.write_csr(CsrAddr::Mtvec as usize, 0x4).unwrap();
+        assert_eq!(
+            c.write_csr(CsrAddr::Mvendorid as usize, 1),
+            Err(CsrError::ReadOnly(CsrAddr::Mvendorid as usize))
+        );
+        assert_eq!(c.read_csr(0x7ff), Err(CsrError::NotImplemented(0x7ff)));
+
+        // Fetch outside of the bus traps.
+        c.set_pc(16);
+        c.cycle().unwrap();
+        assert_eq!(c.pc(), 4);
+        assert_eq!(c.into_bus().0[3], 42);
+    }
 }
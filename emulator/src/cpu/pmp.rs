@@ -0,0 +1,234 @@
+use super::{mmu::Access, Mode};
+
+/// Address-matching mode of a PMP entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Matching {
+    Off,
+    /// Top of range. The previous pmpaddr is the bottom.
+    Tor,
+    /// Naturally aligned four-byte region
+    Na4,
+    /// Naturally aligned power-of-two region, 8 bytes or larger
+    Napot,
+}
+
+/// Physical memory protection with 16 entries and 4-byte granularity.
+/// Volume II: RISC-V Privileged Architectures V20211203 P57
+#[derive(Debug)]
+pub struct Pmp {
+    /// pmp{i}cfg fields. pmpcfg0 holds entries 0-3.
+    cfg: [u8; Self::ENTRIES],
+    /// pmpaddr{i} holding bits 33:2 of the address.
+    addr: [u32; Self::ENTRIES],
+}
+
+impl Pmp {
+    pub const ENTRIES: usize = 16;
+    const R: u8 = 1 << 0;
+    const W: u8 = 1 << 1;
+    const X: u8 = 1 << 2;
+    const A_SHIFT: u8 = 3;
+    const A: u8 = 0b11 << Self::A_SHIFT;
+    /// Locked entries can not be written and also apply to M-mode.
+    const L: u8 = 1 << 7;
+
+    pub fn new() -> Self {
+        Self {
+            cfg: [0; Self::ENTRIES],
+            addr: [0; Self::ENTRIES],
+        }
+    }
+
+    /// Value of pmpcfg{idx}, which packs the configuration of entries 4*idx to 4*idx+3.
+    pub fn cfg(&self, idx: usize) -> u32 {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&self.cfg[4 * idx..4 * idx + 4]);
+        u32::from_le_bytes(bytes)
+    }
+
+    /// Write pmpcfg{idx}. Fields of locked entries are not changed.
+    pub fn write_cfg(&mut self, idx: usize, value: u32) {
+        for (i, cfg) in value.to_le_bytes().into_iter().enumerate() {
+            let entry = 4 * idx + i;
+            if self.locked(entry) {
+                continue;
+            }
+            // Bits 6:5 are reserved. R=0 and W=1 is reserved, W is cleared.
+            let mut cfg = cfg & !0x60;
+            if cfg & Self::R == 0 {
+                cfg &= !Self::W;
+            }
+            self.cfg[entry] = cfg;
+        }
+    }
+
+    pub fn addr(&self, idx: usize) -> u32 {
+        self.addr[idx]
+    }
+
+    /// Write pmpaddr{idx}. Ignored if the entry is locked or is the bottom of a locked TOR entry.
+    pub fn write_addr(&mut self, idx: usize, value: u32) {
+        let next = idx + 1;
+        if self.locked(idx)
+            || next < Self::ENTRIES && self.locked(next) && self.matching(next) == Matching::Tor
+        {
+            return;
+        }
+        self.addr[idx] = value;
+    }
+
+    fn locked(&self, entry: usize) -> bool {
+        self.cfg[entry] & Self::L != 0
+    }
+
+    fn matching(&self, entry: usize) -> Matching {
+        match (self.cfg[entry] & Self::A) >> Self::A_SHIFT {
+            0 => Matching::Off,
+            1 => Matching::Tor,
+            2 => Matching::Na4,
+            _ => Matching::Napot,
+        }
+    }
+
+    /// Byte range [start, end) of the entry. None if the entry is off.
+    fn range(&self, entry: usize) -> Option<(u64, u64)> {
+        let addr = self.addr[entry] as u64;
+        let range = match self.matching(entry) {
+            Matching::Off => return None,
+            Matching::Tor => {
+                let bottom = match entry {
+                    0 => 0,
+                    _ => self.addr[entry - 1] as u64,
+                };
+                (bottom << 2, addr << 2)
+            }
+            Matching::Na4 => (addr << 2, (addr << 2) + 4),
+            Matching::Napot => {
+                // Trailing ones encode the size.
+                let ones = self.addr[entry].trailing_ones();
+                let base = (addr & !((1 << ones) - 1)) << 2;
+                (base, base + (8 << ones))
+            }
+        };
+        Some(range)
+    }
+
+    /// Return whether the access of size bytes at the physical address is permitted.
+    /// The lowest-numbered entry matching any byte of the access determines the result, and it
+    /// must match all bytes. M-mode accesses succeed if no entry matches, S and U-mode ones fail.
+    pub fn permits(&self, addr: u32, size: u32, mode: Mode, access: Access) -> bool {
+        let (start, end) = (addr as u64, addr as u64 + size as u64);
+        for entry in 0..Self::ENTRIES {
+            let Some((bottom, top)) = self.range(entry) else {
+                continue;
+            };
+            if end <= bottom || top <= start {
+                continue;
+            }
+            if start < bottom || top < end {
+                return false;
+            }
+            let cfg = self.cfg[entry];
+            if mode == Mode::M && cfg & Self::L == 0 {
+                return true;
+            }
+            let required = match access {
+                Access::Fetch => Self::X,
+                Access::Load => Self::R,
+                Access::Store => Self::W,
+            };
+            return cfg & required != 0;
+        }
+        mode == Mode::M
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const R: u32 = Pmp::R as u32;
+    const W: u32 = Pmp::W as u32;
+    const X: u32 = Pmp::X as u32;
+    const L: u32 = Pmp::L as u32;
+    const TOR: u32 = 1 << 3;
+    const NA4: u32 = 2 << 3;
+    const NAPOT: u32 = 3 << 3;
+
+    #[test]
+    fn address_matching() {
+        let mut pmp = Pmp::new();
+        // 0x1000-0x1fff by TOR, 0x2000-0x2003 by NA4 and 0x8000_0000-0x8000_ffff by NAPOT
+        pmp.write_addr(0, 0x1000 >> 2);
+        pmp.write_addr(1, 0x2000 >> 2);
+        pmp.write_addr(2, 0x2000 >> 2);
+        pmp.write_addr(3, (0x8000_0000 | 0x7fff) >> 2);
+        pmp.write_cfg(
+            0,
+            (NAPOT | R) << 24 | (NA4 | R | W) << 16 | (TOR | R | X) << 8,
+        );
+        assert_eq!(pmp.cfg(0), 0x19_13_0d_00);
+
+        let u = |addr, size, access| pmp.permits(addr, size, Mode::U, access);
+        assert!(u(0x1000, 4, Access::Fetch));
+        assert!(u(0x1ffc, 4, Access::Load));
+        assert!(!u(0x1ffc, 4, Access::Store));
+        assert!(!u(0x0ffc, 4, Access::Load));
+        assert!(u(0x2000, 4, Access::Store));
+        assert!(!u(0x2004, 1, Access::Load));
+        assert!(u(0x8000_fffc, 4, Access::Load));
+        assert!(!u(0x8001_0000, 4, Access::Load));
+        assert!(!u(0x8000_0000, 4, Access::Fetch));
+
+        // Accesses partially matching an entry fail.
+        assert!(!u(0x1ffc, 8, Access::Load));
+
+        // M-mode accesses succeed if no entry matches, or the matching one is not locked.
+        assert!(pmp.permits(0x1000_0000, 4, Mode::M, Access::Store));
+        assert!(pmp.permits(0x1000, 4, Mode::M, Access::Store));
+        assert!(!pmp.permits(0x1000_0000, 4, Mode::S, Access::Load));
+
+        // NAPOT covering the whole address space
+        pmp.write_addr(4, u32::MAX);
+        pmp.write_cfg(1, NAPOT | R | W | X);
+        assert!(pmp.permits(0x1000_0000, 4, Mode::U, Access::Store));
+        assert!(pmp.permits(0xffff_fffc, 4, Mode::U, Access::Fetch));
+    }
+
+    #[test]
+    fn priority() {
+        let mut pmp = Pmp::new();
+        pmp.write_addr(0, 0x1000 >> 2);
+        pmp.write_addr(1, (0x1000 | 0xfff) >> 2);
+        pmp.write_cfg(0, (NAPOT | R | W) << 8 | NA4);
+        assert!(!pmp.permits(0x1000, 4, Mode::S, Access::Load));
+        assert!(pmp.permits(0x1004, 4, Mode::S, Access::Load));
+    }
+
+    #[test]
+    fn lock() {
+        let mut pmp = Pmp::new();
+        pmp.write_addr(0, 0x1000 >> 2);
+        pmp.write_addr(1, 0x2000 >> 2);
+        pmp.write_cfg(0, (L | TOR | R) << 8);
+
+        // Locked entries also apply to M-mode.
+        assert!(pmp.permits(0x1000, 4, Mode::M, Access::Load));
+        assert!(!pmp.permits(0x1000, 4, Mode::M, Access::Store));
+
+        // Locked entries and the bottom of locked TOR entries can not be written.
+        pmp.write_cfg(0, NA4 | R);
+        pmp.write_addr(0, 0);
+        pmp.write_addr(1, 0);
+        assert_eq!(pmp.cfg(0), (L | TOR | R) << 8 | NA4 | R);
+        assert_eq!((pmp.addr(0), pmp.addr(1)), (0x1000 >> 2, 0x2000 >> 2));
+    }
+
+    #[test]
+    fn warl_fields() {
+        let mut pmp = Pmp::new();
+        // Reserved bits and W without R are cleared.
+        pmp.write_cfg(3, 0x60 | W | X);
+        assert_eq!(pmp.cfg(3), X);
+    }
+}
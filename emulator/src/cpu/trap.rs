@@ -0,0 +1,94 @@
+use crate::bus::interface::{BusReadException, BusWriteException};
+
+/// Synchronous exception codes written to mcause or scause.
+/// Volume II: RISC-V Privileged Architectures V20211203 P39
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exception {
+    InstructionAddressMisaligned = 0,
+    InstructionAccessFault = 1,
+    IllegalInstruction = 2,
+    Breakpoint = 3,
+    LoadAddressMisaligned = 4,
+    LoadAccessFault = 5,
+    /// Also raised by misaligned AMOs
+    StoreAddressMisaligned = 6,
+    /// Also raised by AMOs
+    StoreAccessFault = 7,
+    EnvironmentCallFromUMode = 8,
+    EnvironmentCallFromSMode = 9,
+    EnvironmentCallFromMMode = 11,
+    InstructionPageFault = 12,
+    LoadPageFault = 13,
+    /// Also raised by AMOs
+    StorePageFault = 15,
+}
+
+/// Interrupt codes written to mcause or scause with the interrupt bit set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    SupervisorSoftware = 1,
+    MachineSoftware = 3,
+    SupervisorTimer = 5,
+    MachineTimer = 7,
+    SupervisorExternal = 9,
+    MachineExternal = 11,
+}
+
+impl Interrupt {
+    /// Interrupts in the order of priority. Machine-level interrupts come first.
+    pub const PRIORITY: [Self; 6] = [
+        Self::MachineExternal,
+        Self::MachineSoftware,
+        Self::MachineTimer,
+        Self::SupervisorExternal,
+        Self::SupervisorSoftware,
+        Self::SupervisorTimer,
+    ];
+}
+
+/// Trap represents an architectural exception which transfers control to the trap handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trap {
+    pub cause: Exception,
+    /// Exception-specific information written to mtval or stval.
+    /// Faulting address for address exceptions, faulting instruction bits for illegal instructions.
+    pub tval: u32,
+}
+
+impl Trap {
+    pub fn new(cause: Exception, tval: u32) -> Self {
+        Self { cause, tval }
+    }
+
+    /// Trap for a failed instruction fetch from pc.
+    pub fn fetch(e: BusReadException, pc: u32) -> Self {
+        let cause = match e {
+            BusReadException::LoadAddressMisaligned => Exception::InstructionAddressMisaligned,
+            BusReadException::LoadAccessFault => Exception::InstructionAccessFault,
+        };
+        Self::new(cause, pc)
+    }
+
+    /// Trap for a failed load from addr.
+    pub fn load(e: BusReadException, addr: u32) -> Self {
+        let cause = match e {
+            BusReadException::LoadAddressMisaligned => Exception::LoadAddressMisaligned,
+            BusReadException::LoadAccessFault => Exception::LoadAccessFault,
+        };
+        Self::new(cause, addr)
+    }
+
+    /// Trap for a failed store or AMO to addr.
+    pub fn store(e: BusWriteException, addr: u32) -> Self {
+        let cause = match e {
+            BusWriteException::StoreAddressMisaligned => Exception::StoreAddressMisaligned,
+            BusWriteException::StoreAccessFault => Exception::StoreAccessFault,
+        };
+        Self::new(cause, addr)
+    }
+
+    /// Value written to mcause or scause.
+    pub fn code(&self) -> u32 {
+        self.cause as u32
+    }
+}
@@ -0,0 +1,415 @@
+//! ELF32 executable loader.
+//!
+//! Tool Interface Standard (TIS) Executable and Linking Format (ELF) Specification Version 1.2
+//! RISC-V ELF Specification
+
+use thiserror::Error;
+
+use crate::bus::interface::{BusWrite, BusWriteException};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ElfError {
+    #[error("not an ELF file")]
+    InvalidMagic,
+    #[error("unsupported ELF class {class}, expected ELFCLASS32")]
+    UnsupportedClass { class: u8 },
+    #[error("unsupported data encoding {encoding}, expected ELFDATA2LSB")]
+    UnsupportedEncoding { encoding: u8 },
+    #[error("unsupported machine {machine}, expected EM_RISCV")]
+    UnsupportedMachine { machine: u16 },
+    #[error("unsupported file type {file_type}, expected ET_EXEC")]
+    UnsupportedType { file_type: u16 },
+    #[error("file is truncated at {offset:#x}")]
+    Truncated { offset: usize },
+    #[error(
+        "segment at {vaddr:#010x} has file size {filesz:#x} larger than memory size {memsz:#x}"
+    )]
+    InvalidSegment { vaddr: u32, filesz: u32, memsz: u32 },
+    #[error("failed to load segment byte at {addr:#010x}: {exception}")]
+    Load {
+        addr: u32,
+        exception: BusWriteException,
+    },
+}
+
+const ELFCLASS32: u8 = 1;
+const ELFDATA2LSB: u8 = 1;
+const ET_EXEC: u16 = 2;
+const EM_RISCV: u16 = 243;
+const PT_LOAD: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+
+/// Loadable segment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    /// Physical address the segment is loaded at.
+    pub addr: u32,
+    /// Contents from the file. Remaining `mem_size - data.len()` bytes are zero filled(.bss).
+    pub data: Vec<u8>,
+    pub mem_size: u32,
+}
+
+/// Entry of the symbol table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub addr: u32,
+    pub size: u32,
+}
+
+/// Parsed ELF32 RISC-V executable.
+#[derive(Debug, Clone)]
+pub struct Elf {
+    entry: u32,
+    segments: Vec<Segment>,
+    /// Symbols sorted by address.
+    symbols: Vec<Symbol>,
+}
+
+impl Elf {
+    /// Parse executable. Symbol table is retained if the file has one.
+    pub fn parse(bytes: &[u8]) -> Result<Self, ElfError> {
+        let r = Reader(bytes);
+
+        if bytes.get(..4) != Some(b"\x7fELF") {
+            return Err(ElfError::InvalidMagic);
+        }
+        let class = r.u8(4)?;
+        if class != ELFCLASS32 {
+            return Err(ElfError::UnsupportedClass { class });
+        }
+        let encoding = r.u8(5)?;
+        if encoding != ELFDATA2LSB {
+            return Err(ElfError::UnsupportedEncoding { encoding });
+        }
+        let file_type = r.u16(16)?;
+        if file_type != ET_EXEC {
+            return Err(ElfError::UnsupportedType { file_type });
+        }
+        let machine = r.u16(18)?;
+        if machine != EM_RISCV {
+            return Err(ElfError::UnsupportedMachine { machine });
+        }
+
+        let entry = r.u32(24)?;
+        let phoff = r.u32(28)? as usize;
+        let shoff = r.u32(32)? as usize;
+        let phentsize = r.u16(42)? as usize;
+        let phnum = r.u16(44)? as usize;
+        let shentsize = r.u16(46)? as usize;
+        let shnum = r.u16(48)? as usize;
+
+        let mut segments = Vec::new();
+        for i in 0..phnum {
+            let ph = phoff + i * phentsize;
+            if r.u32(ph)? != PT_LOAD {
+                continue;
+            }
+            let offset = r.u32(ph + 4)? as usize;
+            let vaddr = r.u32(ph + 8)?;
+            let paddr = r.u32(ph + 12)?;
+            let filesz = r.u32(ph + 16)?;
+            let memsz = r.u32(ph + 20)?;
+            if filesz > memsz {
+                return Err(ElfError::InvalidSegment {
+                    vaddr,
+                    filesz,
+                    memsz,
+                });
+            }
+            if memsz == 0 {
+                continue;
+            }
+            segments.push(Segment {
+                addr: paddr,
+                data: r.bytes(offset, filesz as usize)?.to_vec(),
+                mem_size: memsz,
+            });
+        }
+
+        let mut symbols = Vec::new();
+        for i in 0..shnum {
+            let sh = shoff + i * shentsize;
+            if r.u32(sh + 4)? != SHT_SYMTAB {
+                continue;
+            }
+            let offset = r.u32(sh + 16)? as usize;
+            let size = r.u32(sh + 20)? as usize;
+            let link = r.u32(sh + 24)? as usize;
+            let entsize = r.u32(sh + 36)? as usize;
+            let strtab = shoff + link * shentsize;
+            let str_offset = r.u32(strtab + 16)? as usize;
+            let str_size = r.u32(strtab + 20)? as usize;
+            let strings = r.bytes(str_offset, str_size)?;
+
+            // Skip the undefined symbol at index 0.
+            for sym in (offset..offset + size).step_by(entsize.max(1)).skip(1) {
+                let name = r.u32(sym)? as usize;
+                let name = strings.get(name..).unwrap_or_default();
+                let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+                if name.is_empty() {
+                    continue;
+                }
+                symbols.push(Symbol {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    addr: r.u32(sym + 4)?,
+                    size: r.u32(sym + 8)?,
+                });
+            }
+        }
+        symbols.sort_by_key(|sym| sym.addr);
+
+        Ok(Self {
+            entry,
+            segments,
+            symbols,
+        })
+    }
+
+    /// Return entry point address.
+    pub fn entry(&self) -> u32 {
+        self.entry
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// Drop the symbol table.
+    pub fn strip(&mut self) {
+        self.symbols = Vec::new();
+    }
+
+    /// Lookup symbol by name.
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|sym| sym.name == name)
+    }
+
+    /// Return symbol containing addr and offset of addr from it.
+    /// Zero sized symbols(labels) match when there is no other candidate.
+    pub fn symbolize(&self, addr: u32) -> Option<(&Symbol, u32)> {
+        let idx = self.symbols.partition_point(|sym| sym.addr <= addr);
+        self.symbols[..idx]
+            .iter()
+            .rev()
+            .find(|sym| sym.size == 0 || addr - sym.addr < sym.size)
+            .map(|sym| (sym, addr - sym.addr))
+    }
+
+    /// Write segments to the bus and zero fill the remaining memory(.bss).
+    /// Memory for each segment must be mapped beforehand.
+    pub fn load<B: BusWrite>(&self, bus: &mut B) -> Result<(), ElfError> {
+        for segment in &self.segments {
+            let data = segment
+                .data
+                .iter()
+                .copied()
+                .chain(std::iter::repeat(0))
+                .take(segment.mem_size as usize);
+            for (i, v) in data.enumerate() {
+                let addr = segment.addr.wrapping_add(i as u32);
+                bus.write8(addr, v)
+                    .map_err(|exception| ElfError::Load { addr, exception })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Little endian bounds checked reader.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8], ElfError> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.0.get(offset..end))
+            .ok_or(ElfError::Truncated { offset })
+    }
+
+    fn u8(&self, offset: usize) -> Result<u8, ElfError> {
+        Ok(self.bytes(offset, 1)?[0])
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, ElfError> {
+        Ok(u16::from_le_bytes(
+            self.bytes(offset, 2)?.try_into().unwrap(),
+        ))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, ElfError> {
+        Ok(u32::from_le_bytes(
+            self.bytes(offset, 4)?.try_into().unwrap(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::{interface::BusRead, Bus};
+
+    /// Build executable with one text segment, one data segment with .bss and a symbol table.
+    fn executable() -> Vec<u8> {
+        let text: Vec<u8> = [0x0000_0013u32, 0x0000_0073]
+            .iter()
+            .flat_map(|ir| ir.to_le_bytes())
+            .collect();
+        let data = vec![0xaa, 0xbb, 0xcc, 0xdd];
+        let strtab = b"\0_start\0counter\0".to_vec();
+
+        let mut symtab = vec![0; 16];
+        for (name, addr, size, info) in [(1u32, 0x100u32, 8u32, 0x12u8), (8, 0x200, 8, 0x11)] {
+            symtab.extend(name.to_le_bytes());
+            symtab.extend(addr.to_le_bytes());
+            symtab.extend(size.to_le_bytes());
+            symtab.extend([info, 0, 1, 0]);
+        }
+
+        let text_off = 52 + 2 * 32;
+        let data_off = text_off + text.len();
+        let symtab_off = data_off + data.len();
+        let strtab_off = symtab_off + symtab.len();
+        let shoff = strtab_off + strtab.len();
+
+        let mut elf = b"\x7fELF".to_vec();
+        elf.extend([ELFCLASS32, ELFDATA2LSB, 1]);
+        elf.resize(16, 0);
+        elf.extend(ET_EXEC.to_le_bytes());
+        elf.extend(EM_RISCV.to_le_bytes());
+        elf.extend(1u32.to_le_bytes());
+        elf.extend(0x104u32.to_le_bytes()); // e_entry
+        elf.extend(52u32.to_le_bytes()); // e_phoff
+        elf.extend((shoff as u32).to_le_bytes());
+        elf.extend(0u32.to_le_bytes()); // e_flags
+        for v in [52u16, 32, 2, 40, 3, 0] {
+            elf.extend(v.to_le_bytes());
+        }
+
+        for (offset, addr, filesz, memsz) in [
+            (text_off, 0x100u32, text.len() as u32, text.len() as u32),
+            (data_off, 0x200, data.len() as u32, 8),
+        ] {
+            for v in [PT_LOAD, offset as u32, addr, addr, filesz, memsz, 0b111, 4] {
+                elf.extend(v.to_le_bytes());
+            }
+        }
+        elf.extend(text);
+        elf.extend(data);
+        elf.extend(&symtab);
+        elf.extend(&strtab);
+
+        // null, .symtab, .strtab
+        elf.extend([0; 40]);
+        for v in [
+            0,
+            SHT_SYMTAB,
+            0,
+            0,
+            symtab_off as u32,
+            symtab.len() as u32,
+            2,
+            1,
+            4,
+            16,
+        ] {
+            elf.extend(v.to_le_bytes());
+        }
+        for v in [
+            0,
+            3,
+            0,
+            0,
+            strtab_off as u32,
+            strtab.len() as u32,
+            0,
+            0,
+            1,
+            0,
+        ] {
+            elf.extend(v.to_le_bytes());
+        }
+        elf
+    }
+
+    #[test]
+    fn load_executable() {
+        let elf = Elf::parse(&executable()).unwrap();
+        assert_eq!(elf.entry(), 0x104);
+        assert_eq!(elf.segments().len(), 2);
+
+        let mut bus = Bus::new(vec![0xff; 0x300]);
+        elf.load(&mut bus).unwrap();
+        assert_eq!(bus.read32(0x100).unwrap(), 0x0000_0013);
+        assert_eq!(bus.read32(0x104).unwrap(), 0x0000_0073);
+        assert_eq!(bus.read32(0x200).unwrap(), 0xddcc_bbaa);
+        // .bss
+        assert_eq!(bus.read32(0x204).unwrap(), 0);
+        assert_eq!(bus.read32(0x208).unwrap(), 0xffff_ffff);
+    }
+
+    #[test]
+    fn load_to_unmapped_memory() {
+        let elf = Elf::parse(&executable()).unwrap();
+        let mut bus = Bus::new(vec![0; 0x204]);
+        assert_eq!(
+            elf.load(&mut bus),
+            Err(ElfError::Load {
+                addr: 0x204,
+                exception: BusWriteException::StoreAccessFault
+            })
+        );
+    }
+
+    #[test]
+    fn symbols() {
+        let mut elf = Elf::parse(&executable()).unwrap();
+        assert_eq!(elf.symbol("counter").unwrap().addr, 0x200);
+        let (sym, offset) = elf.symbolize(0x104).unwrap();
+        assert_eq!((sym.name.as_str(), offset), ("_start", 4));
+        assert!(elf.symbolize(0x108).is_none());
+        assert!(elf.symbolize(0x0).is_none());
+
+        elf.strip();
+        assert!(elf.symbol("_start").is_none());
+    }
+
+    #[test]
+    fn reject_unsupported_file() {
+        let elf = executable();
+        assert_eq!(
+            Elf::parse(b"#!/bin/sh").unwrap_err(),
+            ElfError::InvalidMagic
+        );
+
+        let mut elf64 = elf.clone();
+        elf64[4] = 2;
+        assert_eq!(
+            Elf::parse(&elf64).unwrap_err(),
+            ElfError::UnsupportedClass { class: 2 }
+        );
+
+        let mut x86 = elf.clone();
+        x86[18..20].copy_from_slice(&3u16.to_le_bytes());
+        assert_eq!(
+            Elf::parse(&x86).unwrap_err(),
+            ElfError::UnsupportedMachine { machine: 3 }
+        );
+
+        let mut relocatable = elf.clone();
+        relocatable[16..18].copy_from_slice(&1u16.to_le_bytes());
+        assert_eq!(
+            Elf::parse(&relocatable).unwrap_err(),
+            ElfError::UnsupportedType { file_type: 1 }
+        );
+
+        assert!(matches!(
+            Elf::parse(&elf[..100]).unwrap_err(),
+            ElfError::Truncated { .. }
+        ));
+    }
+}
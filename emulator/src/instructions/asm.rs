@@ -0,0 +1,1088 @@
+//! Small two-pass assembler for test programs.
+//!
+//! Supported syntax:
+//! * one statement per line, comments start with `#`
+//! * labels `name:` which may precede a statement on the same line
+//! * registers `x0`-`x31` or ABI names(`fp` is an alias of `s0`), `f0`-`f31` or their ABI names
+//! * optional rounding mode operand `rne`, `rtz`, `rdn`, `rup`, `rmm` or `dyn`(default)
+//! * immediates in decimal, `0x` hexadecimal, `0b` binary or character literals `'a'`
+//! * branch and jump targets are labels or pc-relative offsets
+//! * directives `.word`, `.half`, `.byte`, `.zero n` and `.align n`(2^n bytes)
+//! * pseudo-instructions `nop`, `li`, `la`, `mv`, `not`, `neg`, `seqz`, `snez`, `sltz`, `sgtz`,
+//!   `beqz`, `bnez`, `blez`, `bgez`, `bltz`, `bgtz`, `bgt`, `ble`, `bgtu`, `bleu`, `j`, `jal`,
+//!   `jr`, `jalr`, `ret`, `call`, `tail`, `csrr`, `csrw`, `csrs`, `csrc`, `csrwi`, `csrsi`, `csrci`,
+//!   `fmv.s`, `fneg.s`, `fabs.s`, `fmv.d`, `fneg.d`, `fabs.d`
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use super::{
+    disasm::{ABI_NAMES, FP_ABI_NAMES},
+    encode::{self, EncodeError},
+    OpCode, RegisterIdx,
+};
+use crate::cpu::CsrAddr;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AsmError {
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error("line {line}: undefined label {label}")]
+    UndefinedLabel { line: usize, label: String },
+    #[error("line {line}: label {label} is already defined")]
+    DuplicateLabel { line: usize, label: String },
+    #[error("line {line}: {source}")]
+    Encode { line: usize, source: EncodeError },
+}
+
+/// Assembled image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    /// Address of the first byte.
+    pub base: u32,
+    pub bytes: Vec<u8>,
+    labels: HashMap<String, u32>,
+}
+
+impl Program {
+    /// Return address of the label.
+    pub fn label(&self, name: &str) -> Option<u32> {
+        self.labels.get(name).copied()
+    }
+}
+
+/// Assemble source into an image located at base.
+pub fn assemble(src: &str, base: u32) -> Result<Program, AsmError> {
+    // Pass 1: collect statements and label addresses.
+    let mut statements = Vec::new();
+    let mut labels = HashMap::new();
+    let mut addr = base;
+    for (i, line) in src.lines().enumerate() {
+        let line_no = i + 1;
+        let mut rest = line.split('#').next().unwrap_or_default().trim();
+        while let Some((label, tail)) = split_label(rest) {
+            if labels.insert(label.to_owned(), addr).is_some() {
+                return Err(AsmError::DuplicateLabel {
+                    line: line_no,
+                    label: label.to_owned(),
+                });
+            }
+            rest = tail;
+        }
+        if rest.is_empty() {
+            continue;
+        }
+        let (mnemonic, operands) = match rest.split_once(char::is_whitespace) {
+            Some((mnemonic, operands)) => (mnemonic, operands.trim()),
+            None => (rest, ""),
+        };
+        let statement = Statement {
+            line: line_no,
+            addr,
+            mnemonic: mnemonic.to_ascii_lowercase(),
+            operands: if operands.is_empty() {
+                Vec::new()
+            } else {
+                operands.split(',').map(str::trim).collect()
+            },
+        };
+        addr = addr.wrapping_add(statement.size()?);
+        statements.push(statement);
+    }
+
+    // Pass 2: encode with all labels resolved.
+    let mut bytes = Vec::new();
+    for statement in &statements {
+        let start = bytes.len();
+        statement.emit(&labels, &mut bytes)?;
+        debug_assert_eq!(
+            (bytes.len() - start) as u32,
+            statement.size()?,
+            "{}",
+            statement.mnemonic
+        );
+    }
+
+    Ok(Program {
+        base,
+        bytes,
+        labels,
+    })
+}
+
+/// Split leading `label:` from the statement.
+fn split_label(s: &str) -> Option<(&str, &str)> {
+    let (label, rest) = s.split_once(':')?;
+    let label = label.trim();
+    let valid = label
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        && label.chars().next().map_or(false, |c| !c.is_ascii_digit());
+    valid.then_some((label, rest.trim()))
+}
+
+struct Statement<'a> {
+    line: usize,
+    addr: u32,
+    mnemonic: String,
+    operands: Vec<&'a str>,
+}
+
+impl<'a> Statement<'a> {
+    fn error(&self, message: impl Into<String>) -> AsmError {
+        AsmError::Syntax {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    /// Size of the statement in bytes.
+    fn size(&self) -> Result<u32, AsmError> {
+        let size = match self.mnemonic.as_str() {
+            ".word" => 4 * self.operands.len() as u32,
+            ".half" => 2 * self.operands.len() as u32,
+            ".byte" => self.operands.len() as u32,
+            ".zero" => self.imm_at(0)? as u32,
+            ".align" => {
+                let align = 1u32 << self.imm_at(0)?.clamp(0, 12);
+                self.addr.wrapping_neg() & (align - 1)
+            }
+            "li" => 4 * li(self.imm_at(1)?).len() as u32,
+            "la" => 8,
+            _ => 4,
+        };
+        Ok(size)
+    }
+
+    fn operand(&self, idx: usize) -> Result<&'a str, AsmError> {
+        self.operands
+            .get(idx)
+            .copied()
+            .ok_or_else(|| self.error(format!("{} expects more operands", self.mnemonic)))
+    }
+
+    fn expect_operands(&self, n: usize) -> Result<(), AsmError> {
+        if self.operands.len() == n {
+            Ok(())
+        } else {
+            Err(self.error(format!(
+                "{} expects {n} operands but {} given",
+                self.mnemonic,
+                self.operands.len()
+            )))
+        }
+    }
+
+    fn reg_at(&self, idx: usize) -> Result<RegisterIdx, AsmError> {
+        let s = self.operand(idx)?;
+        register(s).ok_or_else(|| self.error(format!("invalid register {s}")))
+    }
+
+    fn freg_at(&self, idx: usize) -> Result<RegisterIdx, AsmError> {
+        let s = self.operand(idx)?;
+        fp_register(s).ok_or_else(|| self.error(format!("invalid floating-point register {s}")))
+    }
+
+    /// Parse optional rounding mode operand. Dynamic rounding mode if omitted.
+    fn rm_at(&self, idx: usize) -> Result<u32, AsmError> {
+        let rm = match self.operands.get(idx).copied() {
+            None | Some("dyn") => 0b111,
+            Some("rne") => 0b000,
+            Some("rtz") => 0b001,
+            Some("rdn") => 0b010,
+            Some("rup") => 0b011,
+            Some("rmm") => 0b100,
+            Some(s) => return Err(self.error(format!("invalid rounding mode {s}"))),
+        };
+        Ok(rm)
+    }
+
+    /// Check the number of operands where the last one is an optional rounding mode.
+    fn expect_operands_with_rm(&self, n: usize) -> Result<(), AsmError> {
+        if self.operands.len() == n + 1 {
+            Ok(())
+        } else {
+            self.expect_operands(n)
+        }
+    }
+
+    fn imm_at(&self, idx: usize) -> Result<i32, AsmError> {
+        let s = self.operand(idx)?;
+        number(s).ok_or_else(|| self.error(format!("invalid immediate {s}")))
+    }
+
+    /// Parse `imm(reg)` or `(reg)`.
+    fn mem_at(&self, idx: usize) -> Result<(i32, RegisterIdx), AsmError> {
+        let s = self.operand(idx)?;
+        let invalid = || self.error(format!("invalid memory operand {s}"));
+        let (imm, reg) = s
+            .strip_suffix(')')
+            .and_then(|s| s.split_once('('))
+            .ok_or_else(invalid)?;
+        let imm = if imm.trim().is_empty() {
+            0
+        } else {
+            number(imm.trim()).ok_or_else(invalid)?
+        };
+        let reg = register(reg.trim()).ok_or_else(invalid)?;
+        Ok((imm, reg))
+    }
+
+    fn csr_at(&self, idx: usize) -> Result<usize, AsmError> {
+        let s = self.operand(idx)?;
+        CsrAddr::from_name(s)
+            .map(|csr| csr as usize)
+            .or_else(|| number(s).map(|n| n as usize))
+            .ok_or_else(|| self.error(format!("invalid csr {s}")))
+    }
+
+    /// Resolve branch or jump target into an offset from the statement.
+    fn target_at(&self, idx: usize, labels: &HashMap<String, u32>) -> Result<i32, AsmError> {
+        let s = self.operand(idx)?;
+        if let Some(offset) = number(s) {
+            return Ok(offset);
+        }
+        labels
+            .get(s)
+            .map(|addr| addr.wrapping_sub(self.addr) as i32)
+            .ok_or_else(|| AsmError::UndefinedLabel {
+                line: self.line,
+                label: s.to_owned(),
+            })
+    }
+
+    fn emit(&self, labels: &HashMap<String, u32>, out: &mut Vec<u8>) -> Result<(), AsmError> {
+        use OpCode::*;
+        let op = self.mnemonic.as_str();
+        let encoded = |r: Result<u32, EncodeError>| {
+            r.map_err(|source| AsmError::Encode {
+                line: self.line,
+                source,
+            })
+        };
+        let mut words = Vec::new();
+        let mut push = |r: Result<u32, EncodeError>| -> Result<(), AsmError> {
+            words.push(encoded(r)?);
+            Ok(())
+        };
+
+        match op {
+            ".word" | ".half" | ".byte" => {
+                for i in 0..self.operands.len() {
+                    let v = self.imm_at(i)?.to_le_bytes();
+                    let len = match op {
+                        ".word" => 4,
+                        ".half" => 2,
+                        _ => 1,
+                    };
+                    out.extend_from_slice(&v[..len]);
+                }
+                return Ok(());
+            }
+            ".zero" | ".align" => {
+                out.resize(out.len() + self.size()? as usize, 0);
+                return Ok(());
+            }
+
+            "nop" => push(encode::i(Addi, 0, 0, 0))?,
+            "li" => {
+                self.expect_operands(2)?;
+                let rd = self.reg_at(0)?;
+                for (i, part) in li(self.imm_at(1)?).into_iter().enumerate() {
+                    match part {
+                        Part::Lui(hi) => push(encode::u(Lui, rd, hi))?,
+                        Part::Addi(lo) => {
+                            push(encode::i(Addi, rd, if i == 0 { 0 } else { rd }, lo))?
+                        }
+                    }
+                }
+            }
+            "la" => {
+                self.expect_operands(2)?;
+                let rd = self.reg_at(0)?;
+                let offset = self.target_at(1, labels)?;
+                let lo = (offset << 20) >> 20;
+                let hi = (offset.wrapping_sub(lo) >> 12) & 0xfffff;
+                push(encode::u(Auipc, rd, hi))?;
+                push(encode::i(Addi, rd, rd, lo))?;
+            }
+            "mv" => push(encode::i(Addi, self.reg_at(0)?, self.reg_at(1)?, 0))?,
+            "not" => push(encode::i(Xori, self.reg_at(0)?, self.reg_at(1)?, -1))?,
+            "neg" => push(encode::r(Sub, self.reg_at(0)?, 0, self.reg_at(1)?))?,
+            "seqz" => push(encode::i(Sltiu, self.reg_at(0)?, self.reg_at(1)?, 1))?,
+            "snez" => push(encode::r(Sltu, self.reg_at(0)?, 0, self.reg_at(1)?))?,
+            "sltz" => push(encode::r(Slt, self.reg_at(0)?, self.reg_at(1)?, 0))?,
+            "sgtz" => push(encode::r(Slt, self.reg_at(0)?, 0, self.reg_at(1)?))?,
+
+            "beqz" | "bnez" | "bltz" | "bgez" => {
+                let op_code = match op {
+                    "beqz" => Beq,
+                    "bnez" => Bne,
+                    "bltz" => Blt,
+                    _ => Bge,
+                };
+                push(encode::b(
+                    op_code,
+                    self.reg_at(0)?,
+                    0,
+                    self.target_at(1, labels)?,
+                ))?
+            }
+            "blez" | "bgtz" => {
+                let op_code = if op == "blez" { Bge } else { Blt };
+                push(encode::b(
+                    op_code,
+                    0,
+                    self.reg_at(0)?,
+                    self.target_at(1, labels)?,
+                ))?
+            }
+            "bgt" | "ble" | "bgtu" | "bleu" => {
+                let op_code = match op {
+                    "bgt" => Blt,
+                    "ble" => Bge,
+                    "bgtu" => Bltu,
+                    _ => Bgeu,
+                };
+                // Operands are swapped.
+                let (rs1, rs2) = (self.reg_at(1)?, self.reg_at(0)?);
+                push(encode::b(op_code, rs1, rs2, self.target_at(2, labels)?))?
+            }
+            "j" | "tail" => push(encode::j(0, self.target_at(0, labels)?))?,
+            "call" => push(encode::j(1, self.target_at(0, labels)?))?,
+            "jal" if self.operands.len() == 1 => push(encode::j(1, self.target_at(0, labels)?))?,
+            "jal" => push(encode::j(self.reg_at(0)?, self.target_at(1, labels)?))?,
+            "ret" => push(encode::i(Jalr, 0, 1, 0))?,
+            "jr" => push(encode::i(Jalr, 0, self.reg_at(0)?, 0))?,
+            "jalr" if self.operands.len() == 1 => push(encode::i(Jalr, 1, self.reg_at(0)?, 0))?,
+            "jalr" if self.operands.len() == 3 => push(encode::i(
+                Jalr,
+                self.reg_at(0)?,
+                self.reg_at(1)?,
+                self.imm_at(2)?,
+            ))?,
+            "jalr" => {
+                let (imm, rs1) = self.mem_at(1)?;
+                push(encode::i(Jalr, self.reg_at(0)?, rs1, imm))?
+            }
+
+            "csrr" => push(encode::csr(Csrrs, self.reg_at(0)?, self.csr_at(1)?, 0))?,
+            "csrw" | "csrs" | "csrc" => {
+                let op_code = match op {
+                    "csrw" => Csrrw,
+                    "csrs" => Csrrs,
+                    _ => Csrrc,
+                };
+                push(encode::csr(
+                    op_code,
+                    0,
+                    self.csr_at(0)?,
+                    self.reg_at(1)? as u32,
+                ))?
+            }
+            "csrwi" | "csrsi" | "csrci" => {
+                let op_code = match op {
+                    "csrwi" => Csrrwi,
+                    "csrsi" => Csrrsi,
+                    _ => Csrrci,
+                };
+                push(encode::csr(
+                    op_code,
+                    0,
+                    self.csr_at(0)?,
+                    self.imm_at(1)? as u32,
+                ))?
+            }
+
+            "fmv.s" | "fneg.s" | "fabs.s" | "fmv.d" | "fneg.d" | "fabs.d" => {
+                self.expect_operands(2)?;
+                let op_code = match op {
+                    "fmv.s" => FsgnjS,
+                    "fneg.s" => FsgnjnS,
+                    "fabs.s" => FsgnjxS,
+                    "fmv.d" => FsgnjD,
+                    "fneg.d" => FsgnjnD,
+                    _ => FsgnjxD,
+                };
+                let (rd, rs) = (self.freg_at(0)?, self.freg_at(1)?);
+                push(encode::fp(op_code, rd, rs, rs, 0))?
+            }
+
+            "fence" => {
+                let (pred, succ) = match self.operands.len() {
+                    0 => (0b1111, 0b1111),
+                    _ => (self.fence_set(0)?, self.fence_set(1)?),
+                };
+                push(encode::encode(
+                    Fence,
+                    encode::Operands {
+                        imm: (pred << 4) | succ,
+                        ..Default::default()
+                    },
+                ))?
+            }
+
+            _ => {
+                let (base, ordering) = match op.find(".aq").or_else(|| op.find(".rl")) {
+                    Some(i) => op.split_at(i),
+                    None => (op, ""),
+                };
+                let (aq, rl) = match ordering {
+                    "" => (false, false),
+                    ".aq" => (true, false),
+                    ".rl" => (false, true),
+                    ".aqrl" => (true, true),
+                    _ => return Err(self.error(format!("unknown instruction {op}"))),
+                };
+                let op_code =
+                    op_code(base).ok_or_else(|| self.error(format!("unknown instruction {op}")))?;
+                push(self.base_instruction(op_code, aq, rl, labels)?)?
+            }
+        }
+
+        for word in words {
+            out.extend_from_slice(&word.to_le_bytes());
+        }
+        Ok(())
+    }
+
+    fn base_instruction(
+        &self,
+        op_code: OpCode,
+        aq: bool,
+        rl: bool,
+        labels: &HashMap<String, u32>,
+    ) -> Result<Result<u32, EncodeError>, AsmError> {
+        use OpCode::*;
+        if aq || rl {
+            match op_code {
+                LrW | ScW | AmoswapW | AmoaddW | AmoxorW | AmoandW | AmoorW | AmominW | AmomaxW
+                | AmominuW | AmomaxuW => (),
+                _ => return Err(self.error(format!("unknown instruction {}", self.mnemonic))),
+            }
+        }
+
+        let encoded = match op_code {
+            Lui | Auipc => {
+                self.expect_operands(2)?;
+                encode::u(op_code, self.reg_at(0)?, self.imm_at(1)?)
+            }
+            Jal | Jalr => unreachable!("handled as pseudo-instruction"),
+            Beq | Bne | Blt | Bltu | Bge | Bgeu => {
+                self.expect_operands(3)?;
+                encode::b(
+                    op_code,
+                    self.reg_at(0)?,
+                    self.reg_at(1)?,
+                    self.target_at(2, labels)?,
+                )
+            }
+            Lb | Lh | Lw | Lbu | Lhu => {
+                self.expect_operands(2)?;
+                let (imm, rs1) = self.mem_at(1)?;
+                encode::i(op_code, self.reg_at(0)?, rs1, imm)
+            }
+            Sb | Sh | Sw => {
+                self.expect_operands(2)?;
+                let (imm, rs1) = self.mem_at(1)?;
+                encode::s(op_code, self.reg_at(0)?, rs1, imm)
+            }
+            Addi | Slti | Sltiu | Xori | Ori | Andi | Slli | Srli | Srai => {
+                self.expect_operands(3)?;
+                encode::i(op_code, self.reg_at(0)?, self.reg_at(1)?, self.imm_at(2)?)
+            }
+            Add | Sub | Sll | Slt | Sltu | Xor | Srl | Sra | Or | And | Mul | Mulh | Mulhsu
+            | Mulhu | Div | Divu | Rem | Remu => {
+                self.expect_operands(3)?;
+                encode::r(op_code, self.reg_at(0)?, self.reg_at(1)?, self.reg_at(2)?)
+            }
+            Fence => unreachable!("handled as pseudo-instruction"),
+            LrW => {
+                self.expect_operands(2)?;
+                let (_, rs1) = self.mem_at(1)?;
+                encode::amo(op_code, self.reg_at(0)?, rs1, 0, aq, rl)
+            }
+            ScW | AmoswapW | AmoaddW | AmoxorW | AmoandW | AmoorW | AmominW | AmomaxW
+            | AmominuW | AmomaxuW => {
+                self.expect_operands(3)?;
+                let (_, rs1) = self.mem_at(2)?;
+                encode::amo(op_code, self.reg_at(0)?, rs1, self.reg_at(1)?, aq, rl)
+            }
+            FenceI | Ecall | Ebreak | Mret | Sret | Wfi => {
+                self.expect_operands(0)?;
+                encode::encode(op_code, Default::default())
+            }
+            SfenceVma => {
+                let (rs1, rs2) = match self.operands.len() {
+                    0 => (0, 0),
+                    1 => (self.reg_at(0)?, 0),
+                    _ => {
+                        self.expect_operands(2)?;
+                        (self.reg_at(0)?, self.reg_at(1)?)
+                    }
+                };
+                encode::r(op_code, 0, rs1, rs2)
+            }
+            Csrrw | Csrrs | Csrrc => {
+                self.expect_operands(3)?;
+                encode::csr(
+                    op_code,
+                    self.reg_at(0)?,
+                    self.csr_at(1)?,
+                    self.reg_at(2)? as u32,
+                )
+            }
+            Csrrwi | Csrrsi | Csrrci => {
+                self.expect_operands(3)?;
+                encode::csr(
+                    op_code,
+                    self.reg_at(0)?,
+                    self.csr_at(1)?,
+                    self.imm_at(2)? as u32,
+                )
+            }
+            Flw | Fld => {
+                self.expect_operands(2)?;
+                let (imm, rs1) = self.mem_at(1)?;
+                encode::i(op_code, self.freg_at(0)?, rs1, imm)
+            }
+            Fsw | Fsd => {
+                self.expect_operands(2)?;
+                let (imm, rs1) = self.mem_at(1)?;
+                encode::s(op_code, self.freg_at(0)?, rs1, imm)
+            }
+            FmaddS | FmsubS | FnmsubS | FnmaddS | FmaddD | FmsubD | FnmsubD | FnmaddD => {
+                self.expect_operands_with_rm(4)?;
+                encode::r4(
+                    op_code,
+                    self.freg_at(0)?,
+                    self.freg_at(1)?,
+                    self.freg_at(2)?,
+                    self.freg_at(3)?,
+                    self.rm_at(4)?,
+                )
+            }
+            FaddS | FsubS | FmulS | FdivS | FaddD | FsubD | FmulD | FdivD => {
+                self.expect_operands_with_rm(3)?;
+                encode::fp(
+                    op_code,
+                    self.freg_at(0)?,
+                    self.freg_at(1)?,
+                    self.freg_at(2)?,
+                    self.rm_at(3)?,
+                )
+            }
+            FsqrtS | FsqrtD | FcvtSD => {
+                self.expect_operands_with_rm(2)?;
+                encode::fp(
+                    op_code,
+                    self.freg_at(0)?,
+                    self.freg_at(1)?,
+                    0,
+                    self.rm_at(2)?,
+                )
+            }
+            FsgnjS | FsgnjnS | FsgnjxS | FminS | FmaxS | FsgnjD | FsgnjnD | FsgnjxD | FminD
+            | FmaxD => {
+                self.expect_operands(3)?;
+                encode::fp(
+                    op_code,
+                    self.freg_at(0)?,
+                    self.freg_at(1)?,
+                    self.freg_at(2)?,
+                    0,
+                )
+            }
+            FcvtWS | FcvtWuS | FcvtWD | FcvtWuD => {
+                self.expect_operands_with_rm(2)?;
+                encode::fp(
+                    op_code,
+                    self.reg_at(0)?,
+                    self.freg_at(1)?,
+                    0,
+                    self.rm_at(2)?,
+                )
+            }
+            FmvXW | FclassS | FclassD => {
+                self.expect_operands(2)?;
+                encode::fp(op_code, self.reg_at(0)?, self.freg_at(1)?, 0, 0)
+            }
+            FeqS | FltS | FleS | FeqD | FltD | FleD => {
+                self.expect_operands(3)?;
+                encode::fp(
+                    op_code,
+                    self.reg_at(0)?,
+                    self.freg_at(1)?,
+                    self.freg_at(2)?,
+                    0,
+                )
+            }
+            FcvtSW | FcvtSWu => {
+                self.expect_operands_with_rm(2)?;
+                encode::fp(
+                    op_code,
+                    self.freg_at(0)?,
+                    self.reg_at(1)?,
+                    0,
+                    self.rm_at(2)?,
+                )
+            }
+            FmvWX => {
+                self.expect_operands(2)?;
+                encode::fp(op_code, self.freg_at(0)?, self.reg_at(1)?, 0, 0)
+            }
+            // The conversions are always exact. rne is used if the rounding mode is omitted like GNU as does.
+            FcvtDS | FcvtDW | FcvtDWu => {
+                self.expect_operands_with_rm(2)?;
+                let rs1 = match op_code {
+                    FcvtDS => self.freg_at(1)?,
+                    _ => self.reg_at(1)?,
+                };
+                let rm = match self.operands.len() {
+                    2 => 0b000,
+                    _ => self.rm_at(2)?,
+                };
+                encode::fp(op_code, self.freg_at(0)?, rs1, 0, rm)
+            }
+        };
+        Ok(encoded)
+    }
+
+    /// Parse predecessor/successor set of FENCE like `rw`.
+    fn fence_set(&self, idx: usize) -> Result<i32, AsmError> {
+        let s = self.operand(idx)?;
+        let mut set = 0;
+        for c in s.chars() {
+            set |= match c {
+                'i' => 8,
+                'o' => 4,
+                'r' => 2,
+                'w' => 1,
+                _ => return Err(self.error(format!("invalid fence operand {s}"))),
+            };
+        }
+        Ok(set)
+    }
+}
+
+/// Op code of base instruction mnemonics.
+fn op_code(mnemonic: &str) -> Option<OpCode> {
+    use OpCode::*;
+    let op_code = match mnemonic {
+        "lui" => Lui,
+        "auipc" => Auipc,
+        "beq" => Beq,
+        "bne" => Bne,
+        "blt" => Blt,
+        "bge" => Bge,
+        "bltu" => Bltu,
+        "bgeu" => Bgeu,
+        "lb" => Lb,
+        "lh" => Lh,
+        "lw" => Lw,
+        "lbu" => Lbu,
+        "lhu" => Lhu,
+        "sb" => Sb,
+        "sh" => Sh,
+        "sw" => Sw,
+        "addi" => Addi,
+        "slti" => Slti,
+        "sltiu" => Sltiu,
+        "xori" => Xori,
+        "ori" => Ori,
+        "andi" => Andi,
+        "slli" => Slli,
+        "srli" => Srli,
+        "srai" => Srai,
+        "add" => Add,
+        "sub" => Sub,
+        "sll" => Sll,
+        "slt" => Slt,
+        "sltu" => Sltu,
+        "xor" => Xor,
+        "srl" => Srl,
+        "sra" => Sra,
+        "or" => Or,
+        "and" => And,
+        "mul" => Mul,
+        "mulh" => Mulh,
+        "mulhsu" => Mulhsu,
+        "mulhu" => Mulhu,
+        "div" => Div,
+        "divu" => Divu,
+        "rem" => Rem,
+        "remu" => Remu,
+        "lr.w" => LrW,
+        "sc.w" => ScW,
+        "amoswap.w" => AmoswapW,
+        "amoadd.w" => AmoaddW,
+        "amoxor.w" => AmoxorW,
+        "amoand.w" => AmoandW,
+        "amoor.w" => AmoorW,
+        "amomin.w" => AmominW,
+        "amomax.w" => AmomaxW,
+        "amominu.w" => AmominuW,
+        "amomaxu.w" => AmomaxuW,
+        "fence.i" => FenceI,
+        "ecall" => Ecall,
+        "ebreak" => Ebreak,
+        "mret" => Mret,
+        "sret" => Sret,
+        "wfi" => Wfi,
+        "sfence.vma" => SfenceVma,
+        "csrrw" => Csrrw,
+        "csrrs" => Csrrs,
+        "csrrc" => Csrrc,
+        "csrrwi" => Csrrwi,
+        "csrrsi" => Csrrsi,
+        "csrrci" => Csrrci,
+        "flw" => Flw,
+        "fsw" => Fsw,
+        "fmadd.s" => FmaddS,
+        "fmsub.s" => FmsubS,
+        "fnmsub.s" => FnmsubS,
+        "fnmadd.s" => FnmaddS,
+        "fadd.s" => FaddS,
+        "fsub.s" => FsubS,
+        "fmul.s" => FmulS,
+        "fdiv.s" => FdivS,
+        "fsqrt.s" => FsqrtS,
+        "fsgnj.s" => FsgnjS,
+        "fsgnjn.s" => FsgnjnS,
+        "fsgnjx.s" => FsgnjxS,
+        "fmin.s" => FminS,
+        "fmax.s" => FmaxS,
+        "fcvt.w.s" => FcvtWS,
+        "fcvt.wu.s" => FcvtWuS,
+        "fmv.x.w" => FmvXW,
+        "feq.s" => FeqS,
+        "flt.s" => FltS,
+        "fle.s" => FleS,
+        "fclass.s" => FclassS,
+        "fcvt.s.w" => FcvtSW,
+        "fcvt.s.wu" => FcvtSWu,
+        "fmv.w.x" => FmvWX,
+        "fld" => Fld,
+        "fsd" => Fsd,
+        "fmadd.d" => FmaddD,
+        "fmsub.d" => FmsubD,
+        "fnmsub.d" => FnmsubD,
+        "fnmadd.d" => FnmaddD,
+        "fadd.d" => FaddD,
+        "fsub.d" => FsubD,
+        "fmul.d" => FmulD,
+        "fdiv.d" => FdivD,
+        "fsqrt.d" => FsqrtD,
+        "fsgnj.d" => FsgnjD,
+        "fsgnjn.d" => FsgnjnD,
+        "fsgnjx.d" => FsgnjxD,
+        "fmin.d" => FminD,
+        "fmax.d" => FmaxD,
+        "fcvt.s.d" => FcvtSD,
+        "fcvt.d.s" => FcvtDS,
+        "feq.d" => FeqD,
+        "flt.d" => FltD,
+        "fle.d" => FleD,
+        "fclass.d" => FclassD,
+        "fcvt.w.d" => FcvtWD,
+        "fcvt.wu.d" => FcvtWuD,
+        "fcvt.d.w" => FcvtDW,
+        "fcvt.d.wu" => FcvtDWu,
+        _ => return None,
+    };
+    Some(op_code)
+}
+
+fn register(s: &str) -> Option<RegisterIdx> {
+    if let Some(idx) = s.strip_prefix('x').and_then(|n| n.parse().ok()) {
+        return (idx < 32).then_some(idx);
+    }
+    if s == "fp" {
+        return Some(8);
+    }
+    ABI_NAMES.iter().position(|&name| name == s)
+}
+
+fn fp_register(s: &str) -> Option<RegisterIdx> {
+    if let Some(idx) = s.strip_prefix('f').and_then(|n| n.parse().ok()) {
+        return (idx < 32).then_some(idx);
+    }
+    FP_ABI_NAMES.iter().position(|&name| name == s)
+}
+
+/// Parse integer literal. Values up to u32::MAX are accepted and wrap into i32.
+fn number(s: &str) -> Option<i32> {
+    if let Some(c) = s.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        let mut chars = c.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii() => Some(c as i32),
+            _ => None,
+        };
+    }
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let v = if let Some(hex) = s.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = s.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()?
+    } else {
+        s.parse::<i64>().ok()?
+    };
+    let v = if negative { -v } else { v };
+    (i64::from(i32::MIN)..=i64::from(u32::MAX))
+        .contains(&v)
+        .then_some(v as i32)
+}
+
+enum Part {
+    Lui(i32),
+    Addi(i32),
+}
+
+/// Split constant into LUI and ADDI so that each immediate fits in its field.
+fn li(imm: i32) -> Vec<Part> {
+    let lo = (imm << 20) >> 20;
+    let hi = (imm.wrapping_sub(lo) >> 12) & 0xfffff;
+    match (hi, lo) {
+        (0, lo) => vec![Part::Addi(lo)],
+        (hi, 0) => vec![Part::Lui(hi)],
+        (hi, lo) => vec![Part::Lui(hi), Part::Addi(lo)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::disasm::disassemble;
+
+    fn listing(src: &str) -> Vec<String> {
+        let program = assemble(src, 0x100).unwrap();
+        disassemble(&program.bytes, program.base)
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn labels_and_branches() {
+        let src = "
+            # count down from 3
+            start:  li a0, 3
+            loop:   addi a0, a0, -1
+                    bnez a0, loop
+                    beq x0, zero, end  # forward reference
+                    nop
+            end:    j start
+        ";
+        assert_eq!(
+            listing(src),
+            vec![
+                "00000100: 00300513  li a0, 3",
+                "00000104: fff50513  addi a0, a0, -1",
+                "00000108: fe051ee3  bnez a0, 0x104",
+                "0000010c: 00000463  beqz zero, 0x114",
+                "00000110: 00000013  nop",
+                "00000114: fedff06f  j 0x100",
+            ]
+        );
+    }
+
+    #[test]
+    fn pseudo_instructions() {
+        let src = "
+            li t0, 0x12345678
+            li t1, 0x1000
+            li t2, -2048
+            la a0, data
+            call func
+            csrr a1, mstatus
+            csrsi mie, 8
+            func: ret
+            data: .word 0xdeadbeef
+        ";
+        assert_eq!(
+            listing(src),
+            vec![
+                "00000100: 123452b7  lui t0, 0x12345",
+                "00000104: 67828293  addi t0, t0, 1656",
+                "00000108: 00001337  lui t1, 0x1",
+                "0000010c: 80000393  li t2, -2048",
+                "00000110: 00000517  auipc a0, 0x0",
+                "00000114: 01850513  addi a0, a0, 24",
+                "00000118: 00c000ef  jal 0x124",
+                "0000011c: 300025f3  csrr a1, mstatus",
+                "00000120: 30446073  csrsi mie, 8",
+                "00000124: 00008067  ret",
+                "00000128: deadbeef  jal t4, 0xfffdb712",
+            ]
+        );
+    }
+
+    #[test]
+    fn memory_and_atomics() {
+        let src = "
+            lw a0, 8(sp)
+            sw a0, -4(fp)
+            lr.w.aq t0, (a0)
+            sc.w.aqrl t1, t2, (a0)
+            amoadd.w x5, x6, 0(x10)
+            fence rw, w
+            fence.i
+            csrrw x0, 0x7c0, x1
+        ";
+        assert_eq!(
+            listing(src),
+            vec![
+                "00000100: 00812503  lw a0, 8(sp)",
+                "00000104: fea42e23  sw a0, -4(s0)",
+                "00000108: 140522af  lr.w.aq t0, (a0)",
+                "0000010c: 1e75232f  sc.w.aqrl t1, t2, (a0)",
+                "00000110: 006522af  amoadd.w t0, t1, (a0)",
+                "00000114: 0310000f  fence rw, w",
+                "00000118: 0000100f  fence.i",
+                "0000011c: 7c009073  csrw 0x7c0, ra",
+            ]
+        );
+    }
+
+    #[test]
+    fn privileged_instructions() {
+        let src = "
+            mret
+            sret
+            wfi
+            sfence.vma
+            sfence.vma a0
+            sfence.vma a0, a1
+        ";
+        assert_eq!(
+            listing(src),
+            vec![
+                "00000100: 30200073  mret",
+                "00000104: 10200073  sret",
+                "00000108: 10500073  wfi",
+                "0000010c: 12000073  sfence.vma",
+                "00000110: 12050073  sfence.vma a0",
+                "00000114: 12b50073  sfence.vma a0, a1",
+            ]
+        );
+    }
+
+    #[test]
+    fn floating_point() {
+        let src = "
+            flw fa0, 4(a0)
+            fsw f31, -8(sp)
+            fmadd.s fa0, fa1, fa2, fa3, rtz
+            fadd.s ft0, ft1, ft2
+            fsqrt.s fs0, fs1, rne
+            fneg.s fa0, fa1
+            fcvt.w.s a0, fa0, rtz
+            fcvt.s.wu fa0, a0
+            feq.s a0, fa0, fa1
+            fmv.w.x ft0, zero
+            csrrs a0, fcsr, zero
+        ";
+        assert_eq!(
+            listing(src),
+            vec![
+                "00000100: 00452507  flw fa0, 4(a0)",
+                "00000104: fff12c27  fsw ft11, -8(sp)",
+                "00000108: 68c59543  fmadd.s fa0, fa1, fa2, fa3, rtz",
+                "0000010c: 0020f053  fadd.s ft0, ft1, ft2",
+                "00000110: 58048453  fsqrt.s fs0, fs1, rne",
+                "00000114: 20b59553  fneg.s fa0, fa1",
+                "00000118: c0051553  fcvt.w.s a0, fa0, rtz",
+                "0000011c: d0157553  fcvt.s.wu fa0, a0",
+                "00000120: a0b52553  feq.s a0, fa0, fa1",
+                "00000124: f0000053  fmv.w.x ft0, zero",
+                "00000128: 00302573  csrr a0, fcsr",
+            ]
+        );
+        let src = "
+            fld fa0, 8(sp)
+            fsd fa0, -8(sp)
+            fcvt.d.s fa1, fa0
+            fcvt.d.wu fa1, a0
+            fcvt.s.d fa0, fa1
+            fabs.d fa0, fa1
+            fcvt.wu.d a0, fa0, rtz
+        ";
+        assert_eq!(
+            listing(src),
+            vec![
+                "00000100: 00813507  fld fa0, 8(sp)",
+                "00000104: fea13c27  fsd fa0, -8(sp)",
+                "00000108: 420505d3  fcvt.d.s fa1, fa0",
+                "0000010c: d21505d3  fcvt.d.wu fa1, a0",
+                "00000110: 4015f553  fcvt.s.d fa0, fa1",
+                "00000114: 22b5a553  fabs.d fa0, fa1",
+                "00000118: c2151553  fcvt.wu.d a0, fa0, rtz",
+            ]
+        );
+        assert!(matches!(
+            assemble("fadd.s fa0, fa1, fa2, rnd", 0),
+            Err(AsmError::Syntax { line: 1, .. })
+        ));
+        assert!(matches!(
+            assemble("fadd.s a0, fa1, fa2", 0),
+            Err(AsmError::Syntax { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn data_directives() {
+        let program = assemble(
+            "
+            .byte 1, 'A'
+            .align 2
+            label: .half 0x1234
+            .zero 2
+            .word -1
+            ",
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            program.bytes,
+            vec![1, b'A', 0, 0, 0x34, 0x12, 0, 0, 0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(program.label("label"), Some(4));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            assemble("j nowhere", 0),
+            Err(AsmError::UndefinedLabel {
+                line: 1,
+                label: "nowhere".into()
+            })
+        );
+        assert_eq!(
+            assemble("a:\na:", 0),
+            Err(AsmError::DuplicateLabel {
+                line: 2,
+                label: "a".into()
+            })
+        );
+        assert!(matches!(
+            assemble("nop\naddi a0, a0, 4096", 0),
+            Err(AsmError::Encode { line: 2, .. })
+        ));
+        assert!(matches!(
+            assemble("add a0, a1", 0),
+            Err(AsmError::Syntax { line: 1, .. })
+        ));
+        assert!(matches!(
+            assemble("mov a0, a1", 0),
+            Err(AsmError::Syntax { line: 1, .. })
+        ));
+        assert!(matches!(
+            assemble("addi x32, x0, 1", 0),
+            Err(AsmError::Syntax { line: 1, .. })
+        ));
+    }
+}
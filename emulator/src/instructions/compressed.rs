@@ -0,0 +1,308 @@
+//! Expansion of RV32C compressed instructions into their 32-bit equivalents.
+//! Volume I: RISC-V Unprivileged ISA V20191213 P97
+
+use super::{encode, DecodeError, OpCode, RegisterIdx};
+
+/// Return true if the lowest two bits mark a 16-bit instruction.
+pub fn is_compressed(ir: u32) -> bool {
+    ir & 0b11 != 0b11
+}
+
+/// Expand 16-bit instruction into the equivalent 32-bit instruction.
+/// Reserved encodings and encodings of unsupported extensions are errors.
+pub fn expand(ir: u16) -> Result<u32, DecodeError> {
+    use OpCode::*;
+    let c = Compressed(ir as u32);
+    let invalid = DecodeError::InvalidOpCode { ir: ir as u32 };
+
+    let expanded = match (c.0 & 0b11, c.funct3()) {
+        // Quadrant 0
+        // C.ADDI4SPN: addi rd', x2, nzuimm
+        (0b00, 0b000) => {
+            let nzuimm = c.bits(12, 11) << 4 | c.bits(10, 7) << 6 | c.bit(6) << 2 | c.bit(5) << 3;
+            if nzuimm == 0 {
+                return Err(invalid);
+            }
+            encode::i(Addi, c.rs2_prime(), 2, nzuimm as i32)
+        }
+        // C.FLD: fld rd', uimm(rs1')
+        (0b00, 0b001) => encode::i(Fld, c.rs2_prime(), c.rs1_prime(), c.ld_uimm()),
+        // C.LW: lw rd', uimm(rs1')
+        (0b00, 0b010) => encode::i(Lw, c.rs2_prime(), c.rs1_prime(), c.lw_uimm()),
+        // C.FLW: flw rd', uimm(rs1')
+        (0b00, 0b011) => encode::i(Flw, c.rs2_prime(), c.rs1_prime(), c.lw_uimm()),
+        // C.FSD: fsd rs2', uimm(rs1')
+        (0b00, 0b101) => encode::s(Fsd, c.rs2_prime(), c.rs1_prime(), c.ld_uimm()),
+        // C.SW: sw rs2', uimm(rs1')
+        (0b00, 0b110) => encode::s(Sw, c.rs2_prime(), c.rs1_prime(), c.lw_uimm()),
+        // C.FSW: fsw rs2', uimm(rs1')
+        (0b00, 0b111) => encode::s(Fsw, c.rs2_prime(), c.rs1_prime(), c.lw_uimm()),
+
+        // Quadrant 1
+        // C.ADDI: addi rd, rd, imm. C.NOP when rd is x0.
+        (0b01, 0b000) => encode::i(Addi, c.rd(), c.rd(), c.imm6()),
+        // C.JAL: jal x1, offset
+        (0b01, 0b001) => encode::j(1, c.j_offset()),
+        // C.LI: addi rd, x0, imm
+        (0b01, 0b010) => encode::i(Addi, c.rd(), 0, c.imm6()),
+        // C.ADDI16SP: addi x2, x2, nzimm
+        (0b01, 0b011) if c.rd() == 2 => {
+            let nzimm =
+                c.bit(12) << 9 | c.bit(6) << 4 | c.bit(5) << 6 | c.bits(4, 3) << 7 | c.bit(2) << 5;
+            if nzimm == 0 {
+                return Err(invalid);
+            }
+            encode::i(Addi, 2, 2, sign_extend(nzimm, 10))
+        }
+        // C.LUI: lui rd, nzimm
+        (0b01, 0b011) => {
+            let nzimm = c.imm6();
+            if nzimm == 0 {
+                return Err(invalid);
+            }
+            encode::u(Lui, c.rd(), nzimm & 0xfffff)
+        }
+        (0b01, 0b100) => {
+            let rd = c.rs1_prime();
+            match (c.bits(11, 10), c.bit(12)) {
+                // shamt[5] must be zero for RV32C.
+                (0b00, 0) => encode::i(Srli, rd, rd, c.bits(6, 2) as i32),
+                (0b01, 0) => encode::i(Srai, rd, rd, c.bits(6, 2) as i32),
+                (0b10, _) => encode::i(Andi, rd, rd, c.imm6()),
+                (0b11, 0) => {
+                    let op_code = match c.bits(6, 5) {
+                        0b00 => Sub,
+                        0b01 => Xor,
+                        0b10 => Or,
+                        _ => And,
+                    };
+                    encode::r(op_code, rd, rd, c.rs2_prime())
+                }
+                _ => return Err(invalid),
+            }
+        }
+        // C.J: jal x0, offset
+        (0b01, 0b101) => encode::j(0, c.j_offset()),
+        // C.BEQZ, C.BNEZ: beq/bne rs1', x0, offset
+        (0b01, 0b110 | 0b111) => {
+            let offset = c.bit(12) << 8
+                | c.bits(11, 10) << 3
+                | c.bits(6, 5) << 6
+                | c.bits(4, 3) << 1
+                | c.bit(2) << 5;
+            let op_code = if c.funct3() == 0b110 { Beq } else { Bne };
+            encode::b(op_code, c.rs1_prime(), 0, sign_extend(offset, 9))
+        }
+
+        // Quadrant 2
+        // C.SLLI: slli rd, rd, shamt
+        (0b10, 0b000) if c.bit(12) == 0 => encode::i(Slli, c.rd(), c.rd(), c.bits(6, 2) as i32),
+        // C.FLDSP: fld rd, uimm(x2)
+        (0b10, 0b001) => encode::i(Fld, c.rd(), 2, c.ldsp_uimm()),
+        // C.LWSP: lw rd, uimm(x2)
+        (0b10, 0b010) if c.rd() != 0 => encode::i(Lw, c.rd(), 2, c.lwsp_uimm()),
+        // C.FLWSP: flw rd, uimm(x2)
+        (0b10, 0b011) => encode::i(Flw, c.rd(), 2, c.lwsp_uimm()),
+        (0b10, 0b100) => match (c.bit(12), c.rd(), c.rs2()) {
+            // C.JR: jalr x0, 0(rs1)
+            (0, rs1, 0) if rs1 != 0 => encode::i(Jalr, 0, rs1, 0),
+            // C.MV: add rd, x0, rs2
+            (0, rd, rs2) if rs2 != 0 => encode::r(Add, rd, 0, rs2),
+            // C.EBREAK
+            (1, 0, 0) => encode::encode(Ebreak, Default::default()),
+            // C.JALR: jalr x1, 0(rs1)
+            (1, rs1, 0) => encode::i(Jalr, 1, rs1, 0),
+            // C.ADD: add rd, rd, rs2
+            (1, rd, rs2) => encode::r(Add, rd, rd, rs2),
+            _ => return Err(invalid),
+        },
+        // C.FSDSP: fsd rs2, uimm(x2)
+        (0b10, 0b101) => encode::s(Fsd, c.rs2(), 2, c.sdsp_uimm()),
+        // C.SWSP: sw rs2, uimm(x2)
+        (0b10, 0b110) => encode::s(Sw, c.rs2(), 2, c.swsp_uimm()),
+        // C.FSWSP: fsw rs2, uimm(x2)
+        (0b10, 0b111) => encode::s(Fsw, c.rs2(), 2, c.swsp_uimm()),
+
+        _ => return Err(invalid),
+    };
+    // Immediates of valid compressed instructions always fit in the expanded form.
+    expanded.map_err(|_| invalid)
+}
+
+/// Accessors of the compressed instruction fields.
+struct Compressed(u32);
+
+impl Compressed {
+    fn bit(&self, n: u32) -> u32 {
+        (self.0 >> n) & 1
+    }
+
+    /// Bits hi..=lo shifted down to bit 0.
+    fn bits(&self, hi: u32, lo: u32) -> u32 {
+        (self.0 >> lo) & ((1 << (hi - lo + 1)) - 1)
+    }
+
+    fn funct3(&self) -> u32 {
+        self.bits(15, 13)
+    }
+
+    /// rd or rs1 of CR and CI formats
+    fn rd(&self) -> RegisterIdx {
+        self.bits(11, 7) as RegisterIdx
+    }
+
+    fn rs2(&self) -> RegisterIdx {
+        self.bits(6, 2) as RegisterIdx
+    }
+
+    /// 3-bit register field at bits 9:7 mapped to x8-x15
+    fn rs1_prime(&self) -> RegisterIdx {
+        self.bits(9, 7) as RegisterIdx + 8
+    }
+
+    /// 3-bit register field at bits 4:2 mapped to x8-x15
+    fn rs2_prime(&self) -> RegisterIdx {
+        self.bits(4, 2) as RegisterIdx + 8
+    }
+
+    /// Sign-extended imm[5] = inst[12], imm[4:0] = inst[6:2]
+    fn imm6(&self) -> i32 {
+        sign_extend(self.bit(12) << 5 | self.bits(6, 2), 6)
+    }
+
+    /// Offset of C.LW and C.SW. uimm[5:3] = inst[12:10], uimm[2] = inst[6], uimm[6] = inst[5]
+    fn lw_uimm(&self) -> i32 {
+        (self.bits(12, 10) << 3 | self.bit(6) << 2 | self.bit(5) << 6) as i32
+    }
+
+    /// Offset of C.FLD and C.FSD. uimm[5:3] = inst[12:10], uimm[7:6] = inst[6:5]
+    fn ld_uimm(&self) -> i32 {
+        (self.bits(12, 10) << 3 | self.bits(6, 5) << 6) as i32
+    }
+
+    /// Offset of C.FLDSP. uimm[5] = inst[12], uimm[4:3] = inst[6:5], uimm[8:6] = inst[4:2]
+    fn ldsp_uimm(&self) -> i32 {
+        (self.bit(12) << 5 | self.bits(6, 5) << 3 | self.bits(4, 2) << 6) as i32
+    }
+
+    /// Offset of C.FSDSP. uimm[5:3] = inst[12:10], uimm[8:6] = inst[9:7]
+    fn sdsp_uimm(&self) -> i32 {
+        (self.bits(12, 10) << 3 | self.bits(9, 7) << 6) as i32
+    }
+
+    /// Offset of C.LWSP and C.FLWSP. uimm[5] = inst[12], uimm[4:2] = inst[6:4], uimm[7:6] = inst[3:2]
+    fn lwsp_uimm(&self) -> i32 {
+        (self.bit(12) << 5 | self.bits(6, 4) << 2 | self.bits(3, 2) << 6) as i32
+    }
+
+    /// Offset of C.SWSP and C.FSWSP. uimm[5:2] = inst[12:9], uimm[7:6] = inst[8:7]
+    fn swsp_uimm(&self) -> i32 {
+        (self.bits(12, 9) << 2 | self.bits(8, 7) << 6) as i32
+    }
+
+    /// Offset of C.J and C.JAL. offset[11|4|9:8|10|6|7|3:1|5] = inst[12:2]
+    fn j_offset(&self) -> i32 {
+        let offset = self.bit(12) << 11
+            | self.bit(11) << 4
+            | self.bits(10, 9) << 8
+            | self.bit(8) << 10
+            | self.bit(7) << 6
+            | self.bit(6) << 7
+            | self.bits(5, 3) << 1
+            | self.bit(2) << 5;
+        sign_extend(offset, 12)
+    }
+}
+
+/// Sign-extend the lower bits of v.
+fn sign_extend(v: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((v << shift) as i32) >> shift
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::Decoder;
+
+    fn expanded(ir: u16) -> String {
+        let ir = expand(ir).unwrap();
+        Decoder::new().try_decode(ir).unwrap().to_string()
+    }
+
+    #[test]
+    fn quadrant0() {
+        // c.addi4spn a0, sp, 16
+        assert_eq!(expanded(0x0808), "addi a0, sp, 16");
+        // c.lw a1, 4(a0)
+        assert_eq!(expanded(0x414c), "lw a1, 4(a0)");
+        // c.sw a1, 124(a0)
+        assert_eq!(expanded(0xdd6c), "sw a1, 124(a0)");
+        // c.flw fa1, 4(a0), c.fsw fa1, 124(a0)
+        assert_eq!(expanded(0x614c), "flw fa1, 4(a0)");
+        assert_eq!(expanded(0xfd6c), "fsw fa1, 124(a0)");
+        assert!(expand(0x0000).is_err());
+        // c.fld fa1, 8(a0), c.fsd fa1, 248(a0)
+        assert_eq!(expanded(0x250c), "fld fa1, 8(a0)");
+        assert_eq!(expanded(0xbd6c), "fsd fa1, 248(a0)");
+    }
+
+    #[test]
+    fn quadrant1() {
+        assert_eq!(expanded(0x0001), "nop");
+        // c.addi a0, -1
+        assert_eq!(expanded(0x157d), "addi a0, a0, -1");
+        // c.li a0, 31
+        assert_eq!(expanded(0x457d), "li a0, 31");
+        // c.addi16sp sp, -64
+        assert_eq!(expanded(0x7139), "addi sp, sp, -64");
+        // c.lui a0, 0xfffe1
+        assert_eq!(expanded(0x7505), "lui a0, 0xfffe1");
+        // c.srli a0, 3
+        assert_eq!(expanded(0x810d), "srli a0, a0, 3");
+        // c.srai a1, 31
+        assert_eq!(expanded(0x85fd), "srai a1, a1, 31");
+        // c.andi a0, -16
+        assert_eq!(expanded(0x9941), "andi a0, a0, -16");
+        assert_eq!(expanded(0x8d0d), "sub a0, a0, a1");
+        assert_eq!(expanded(0x8d2d), "xor a0, a0, a1");
+        assert_eq!(expanded(0x8d4d), "or a0, a0, a1");
+        assert_eq!(expanded(0x8d6d), "and a0, a0, a1");
+        // c.jal 0x7fe, c.j -2048
+        assert_eq!(expanded(0x2ffd), "jal 2046");
+        assert_eq!(expanded(0xb001), "j -2048");
+        // c.beqz a0, -256, c.bnez a5, 254
+        assert_eq!(expanded(0xd101), "beqz a0, -256");
+        assert_eq!(expanded(0xeffd), "bnez a5, 254");
+        // reserved
+        assert!(expand(0x6101).is_err());
+        assert!(expand(0x6501).is_err());
+        // shamt[5] = 1 is reserved for RV32C
+        assert!(expand(0x9101).is_err());
+    }
+
+    #[test]
+    fn quadrant2() {
+        // c.slli a0, 31
+        assert_eq!(expanded(0x057e), "slli a0, a0, 31");
+        // c.lwsp ra, 252(sp)
+        assert_eq!(expanded(0x50fe), "lw ra, 252(sp)");
+        assert_eq!(expanded(0x8502), "jr a0");
+        assert_eq!(expanded(0x8082), "ret");
+        assert_eq!(expanded(0x852e), "mv a0, a1");
+        assert_eq!(expanded(0x9002), "ebreak");
+        assert_eq!(expanded(0x9502), "jalr a0");
+        assert_eq!(expanded(0x952e), "add a0, a0, a1");
+        // c.swsp ra, 252(sp)
+        assert_eq!(expanded(0xdf86), "sw ra, 252(sp)");
+        // c.flwsp ft0, 252(sp), c.fswsp ft1, 252(sp)
+        assert_eq!(expanded(0x707e), "flw ft0, 252(sp)");
+        assert_eq!(expanded(0xff86), "fsw ft1, 252(sp)");
+        // c.fldsp ft0, 504(sp), c.fsdsp ft1, 504(sp)
+        assert_eq!(expanded(0x307e), "fld ft0, 504(sp)");
+        assert_eq!(expanded(0xbf86), "fsd ft1, 504(sp)");
+        // c.lwsp x0 and c.jr x0 are reserved
+        assert!(expand(0x4002).is_err());
+        assert!(expand(0x8002).is_err());
+    }
+}
@@ -0,0 +1,598 @@
+use std::fmt;
+
+use super::{compressed, DecodeError, Decoder, Instruction, OpCode, RegisterIdx};
+use crate::cpu::CsrAddr;
+
+/// ABI names of the integer registers.
+/// Volume I: RISC-V Unprivileged ISA V20191213 P137
+pub const ABI_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+/// ABI names of the floating-point registers.
+/// Volume I: RISC-V Unprivileged ISA V20191213 P137
+pub const FP_ABI_NAMES: [&str; 32] = [
+    "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7", "fs0", "fs1", "fa0", "fa1", "fa2",
+    "fa3", "fa4", "fa5", "fa6", "fa7", "fs2", "fs3", "fs4", "fs5", "fs6", "fs7", "fs8", "fs9",
+    "fs10", "fs11", "ft8", "ft9", "ft10", "ft11",
+];
+
+fn reg(idx: RegisterIdx) -> &'static str {
+    ABI_NAMES[idx]
+}
+
+fn freg(idx: RegisterIdx) -> &'static str {
+    FP_ABI_NAMES[idx]
+}
+
+/// Rounding mode operand. Nothing is shown for the dynamic rounding mode.
+struct RoundingMode(u32);
+
+impl fmt::Display for RoundingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.0 {
+            0b000 => "rne",
+            0b001 => "rtz",
+            0b010 => "rdn",
+            0b011 => "rup",
+            0b100 => "rmm",
+            _ => return Ok(()),
+        };
+        write!(f, ", {name}")
+    }
+}
+
+/// Assembler name of the CSR or its address if it is not implemented.
+struct Csr(usize);
+
+impl fmt::Display for Csr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match CsrAddr::from_addr(self.0) {
+            Some(csr) => f.write_str(csr.name()),
+            None => write!(f, "{:#x}", self.0),
+        }
+    }
+}
+
+/// Branch or jump target. Absolute address if pc is known, otherwise the offset.
+struct Target {
+    pc: Option<u32>,
+    offset: i32,
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.pc {
+            Some(pc) => write!(f, "{:#x}", pc.wrapping_add(self.offset as u32)),
+            None => write!(f, "{}", self.offset),
+        }
+    }
+}
+
+/// Predecessor/successor set of FENCE.
+struct FenceSet(u32);
+
+impl fmt::Display for FenceSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 == 0 {
+            return f.write_str("0");
+        }
+        for (bit, name) in [(8, 'i'), (4, 'o'), (2, 'r'), (1, 'w')] {
+            if self.0 & bit != 0 {
+                write!(f, "{name}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Instruction rendered with absolute branch targets.
+pub struct DisplayAt<'a> {
+    instruction: &'a Instruction,
+    pc: u32,
+}
+
+impl fmt::Display for DisplayAt<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.instruction.fmt_asm(f, Some(self.pc))
+    }
+}
+
+impl Instruction {
+    /// Render instruction located at pc. Branch and jump targets are shown as absolute addresses.
+    pub fn display_at(&self, pc: u32) -> DisplayAt<'_> {
+        DisplayAt {
+            instruction: self,
+            pc,
+        }
+    }
+
+    /// Mnemonic without pseudo-instruction forms.
+    pub fn mnemonic(&self) -> &'static str {
+        use OpCode::*;
+        match self.op_code {
+            Lui => "lui",
+            Auipc => "auipc",
+            Jal => "jal",
+            Jalr => "jalr",
+            Beq => "beq",
+            Bne => "bne",
+            Blt => "blt",
+            Bltu => "bltu",
+            Bge => "bge",
+            Bgeu => "bgeu",
+            Lb => "lb",
+            Lh => "lh",
+            Lw => "lw",
+            Lbu => "lbu",
+            Lhu => "lhu",
+            Sb => "sb",
+            Sh => "sh",
+            Sw => "sw",
+            Addi => "addi",
+            Slti => "slti",
+            Sltiu => "sltiu",
+            Xori => "xori",
+            Ori => "ori",
+            Andi => "andi",
+            Slli => "slli",
+            Srli => "srli",
+            Srai => "srai",
+            Add => "add",
+            Sub => "sub",
+            Sll => "sll",
+            Slt => "slt",
+            Sltu => "sltu",
+            Xor => "xor",
+            Srl => "srl",
+            Sra => "sra",
+            Or => "or",
+            And => "and",
+            Mul => "mul",
+            Mulh => "mulh",
+            Mulhsu => "mulhsu",
+            Mulhu => "mulhu",
+            Div => "div",
+            Divu => "divu",
+            Rem => "rem",
+            Remu => "remu",
+            Fence => "fence",
+            FenceI => "fence.i",
+            LrW => "lr.w",
+            ScW => "sc.w",
+            AmoswapW => "amoswap.w",
+            AmoaddW => "amoadd.w",
+            AmoxorW => "amoxor.w",
+            AmoandW => "amoand.w",
+            AmoorW => "amoor.w",
+            AmominW => "amomin.w",
+            AmomaxW => "amomax.w",
+            AmominuW => "amominu.w",
+            AmomaxuW => "amomaxu.w",
+            Ecall => "ecall",
+            Ebreak => "ebreak",
+            Mret => "mret",
+            Sret => "sret",
+            Wfi => "wfi",
+            SfenceVma => "sfence.vma",
+            Csrrw => "csrrw",
+            Csrrs => "csrrs",
+            Csrrc => "csrrc",
+            Csrrwi => "csrrwi",
+            Csrrsi => "csrrsi",
+            Csrrci => "csrrci",
+            Flw => "flw",
+            Fsw => "fsw",
+            FmaddS => "fmadd.s",
+            FmsubS => "fmsub.s",
+            FnmsubS => "fnmsub.s",
+            FnmaddS => "fnmadd.s",
+            FaddS => "fadd.s",
+            FsubS => "fsub.s",
+            FmulS => "fmul.s",
+            FdivS => "fdiv.s",
+            FsqrtS => "fsqrt.s",
+            FsgnjS => "fsgnj.s",
+            FsgnjnS => "fsgnjn.s",
+            FsgnjxS => "fsgnjx.s",
+            FminS => "fmin.s",
+            FmaxS => "fmax.s",
+            FcvtWS => "fcvt.w.s",
+            FcvtWuS => "fcvt.wu.s",
+            FmvXW => "fmv.x.w",
+            FeqS => "feq.s",
+            FltS => "flt.s",
+            FleS => "fle.s",
+            FclassS => "fclass.s",
+            FcvtSW => "fcvt.s.w",
+            FcvtSWu => "fcvt.s.wu",
+            FmvWX => "fmv.w.x",
+            Fld => "fld",
+            Fsd => "fsd",
+            FmaddD => "fmadd.d",
+            FmsubD => "fmsub.d",
+            FnmsubD => "fnmsub.d",
+            FnmaddD => "fnmadd.d",
+            FaddD => "fadd.d",
+            FsubD => "fsub.d",
+            FmulD => "fmul.d",
+            FdivD => "fdiv.d",
+            FsqrtD => "fsqrt.d",
+            FsgnjD => "fsgnj.d",
+            FsgnjnD => "fsgnjn.d",
+            FsgnjxD => "fsgnjx.d",
+            FminD => "fmin.d",
+            FmaxD => "fmax.d",
+            FcvtSD => "fcvt.s.d",
+            FcvtDS => "fcvt.d.s",
+            FeqD => "feq.d",
+            FltD => "flt.d",
+            FleD => "fle.d",
+            FclassD => "fclass.d",
+            FcvtWD => "fcvt.w.d",
+            FcvtWuD => "fcvt.wu.d",
+            FcvtDW => "fcvt.d.w",
+            FcvtDWu => "fcvt.d.wu",
+        }
+    }
+
+    fn fmt_asm(&self, f: &mut fmt::Formatter<'_>, pc: Option<u32>) -> fmt::Result {
+        use OpCode::*;
+        let op = self.mnemonic();
+        let (rd, rs1, rs2) = (self.rd(), self.rs1(), self.rs2());
+        let target = || Target {
+            pc,
+            offset: self.imm_signed(),
+        };
+
+        match self.op_code {
+            Lui | Auipc => write!(f, "{op} {}, {:#x}", reg(rd), self.imm() >> 12),
+
+            Jal if rd == 0 => write!(f, "j {}", target()),
+            Jal if rd == 1 => write!(f, "jal {}", target()),
+            Jal => write!(f, "{op} {}, {}", reg(rd), target()),
+            Jalr if rd == 0 && rs1 == 1 && self.imm_signed() == 0 => f.write_str("ret"),
+            Jalr if rd == 0 && self.imm_signed() == 0 => write!(f, "jr {}", reg(rs1)),
+            Jalr if rd == 1 && self.imm_signed() == 0 => write!(f, "jalr {}", reg(rs1)),
+            Jalr => write!(f, "{op} {}, {}({})", reg(rd), self.imm_signed(), reg(rs1)),
+
+            Beq | Bne | Blt | Bge if rs2 == 0 => {
+                let op = match self.op_code {
+                    Beq => "beqz",
+                    Bne => "bnez",
+                    Blt => "bltz",
+                    _ => "bgez",
+                };
+                write!(f, "{op} {}, {}", reg(rs1), target())
+            }
+            Blt | Bge if rs1 == 0 => {
+                let op = if self.op_code == Blt { "bgtz" } else { "blez" };
+                write!(f, "{op} {}, {}", reg(rs2), target())
+            }
+            Beq | Bne | Blt | Bltu | Bge | Bgeu => {
+                write!(f, "{op} {}, {}, {}", reg(rs1), reg(rs2), target())
+            }
+
+            Lb | Lh | Lw | Lbu | Lhu => {
+                write!(f, "{op} {}, {}({})", reg(rd), self.imm_signed(), reg(rs1))
+            }
+            Sb | Sh | Sw => write!(f, "{op} {}, {}({})", reg(rs2), self.imm_signed(), reg(rs1)),
+
+            Addi if rd == 0 && rs1 == 0 && self.imm_signed() == 0 => f.write_str("nop"),
+            Addi if rs1 == 0 => write!(f, "li {}, {}", reg(rd), self.imm_signed()),
+            Addi if self.imm_signed() == 0 => write!(f, "mv {}, {}", reg(rd), reg(rs1)),
+            Xori if self.imm_signed() == -1 => write!(f, "not {}, {}", reg(rd), reg(rs1)),
+            Sltiu if self.imm_signed() == 1 => write!(f, "seqz {}, {}", reg(rd), reg(rs1)),
+            Addi | Slti | Sltiu | Xori | Ori | Andi => {
+                write!(f, "{op} {}, {}, {}", reg(rd), reg(rs1), self.imm_signed())
+            }
+            Slli | Srli | Srai => write!(f, "{op} {}, {}, {}", reg(rd), reg(rs1), self.shamt()),
+
+            Add if rs1 == 0 => write!(f, "mv {}, {}", reg(rd), reg(rs2)),
+            Sub if rs1 == 0 => write!(f, "neg {}, {}", reg(rd), reg(rs2)),
+            Sltu if rs1 == 0 => write!(f, "snez {}, {}", reg(rd), reg(rs2)),
+            Slt if rs2 == 0 => write!(f, "sltz {}, {}", reg(rd), reg(rs1)),
+            Slt if rs1 == 0 => write!(f, "sgtz {}, {}", reg(rd), reg(rs2)),
+            Add | Sub | Sll | Slt | Sltu | Xor | Srl | Sra | Or | And | Mul | Mulh | Mulhsu
+            | Mulhu | Div | Divu | Rem | Remu => {
+                write!(f, "{op} {}, {}, {}", reg(rd), reg(rs1), reg(rs2))
+            }
+
+            Fence => {
+                let raw = self.raw();
+                let (fm, pred, succ) = (raw >> 28, (raw >> 24) & 0xf, (raw >> 20) & 0xf);
+                match (fm, pred, succ) {
+                    (0b1000, 0b0011, 0b0011) => f.write_str("fence.tso"),
+                    (_, 0b1111, 0b1111) => f.write_str("fence"),
+                    _ => write!(f, "fence {}, {}", FenceSet(pred), FenceSet(succ)),
+                }
+            }
+
+            LrW | ScW | AmoswapW | AmoaddW | AmoxorW | AmoandW | AmoorW | AmominW | AmomaxW
+            | AmominuW | AmomaxuW => {
+                let ordering = match (self.aq(), self.rl()) {
+                    (false, false) => "",
+                    (true, false) => ".aq",
+                    (false, true) => ".rl",
+                    (true, true) => ".aqrl",
+                };
+                if self.op_code == LrW {
+                    write!(f, "{op}{ordering} {}, ({})", reg(rd), reg(rs1))
+                } else {
+                    write!(
+                        f,
+                        "{op}{ordering} {}, {}, ({})",
+                        reg(rd),
+                        reg(rs2),
+                        reg(rs1)
+                    )
+                }
+            }
+
+            FenceI | Ecall | Ebreak | Mret | Sret | Wfi => f.write_str(op),
+            SfenceVma => match (rs1, rs2) {
+                (0, 0) => f.write_str(op),
+                (_, 0) => write!(f, "{op} {}", reg(rs1)),
+                _ => write!(f, "{op} {}, {}", reg(rs1), reg(rs2)),
+            },
+
+            Csrrs if rs1 == 0 => write!(f, "csrr {}, {}", reg(rd), Csr(self.csr())),
+            Csrrw | Csrrs | Csrrc if rd == 0 => {
+                write!(f, "csr{} {}, {}", &op[4..], Csr(self.csr()), reg(rs1))
+            }
+            Csrrwi | Csrrsi | Csrrci if rd == 0 => {
+                write!(f, "csr{} {}, {}", &op[4..], Csr(self.csr()), rs1)
+            }
+            Csrrw | Csrrs | Csrrc => {
+                write!(f, "{op} {}, {}, {}", reg(rd), Csr(self.csr()), reg(rs1))
+            }
+            Csrrwi | Csrrsi | Csrrci => write!(f, "{op} {}, {}, {}", reg(rd), Csr(self.csr()), rs1),
+
+            Flw | Fld => write!(f, "{op} {}, {}({})", freg(rd), self.imm_signed(), reg(rs1)),
+            Fsw | Fsd => write!(f, "{op} {}, {}({})", freg(rs2), self.imm_signed(), reg(rs1)),
+            FmaddS | FmsubS | FnmsubS | FnmaddS | FmaddD | FmsubD | FnmsubD | FnmaddD => write!(
+                f,
+                "{op} {}, {}, {}, {}{}",
+                freg(rd),
+                freg(rs1),
+                freg(rs2),
+                freg(self.rs3()),
+                RoundingMode(self.rm())
+            ),
+            FaddS | FsubS | FmulS | FdivS | FaddD | FsubD | FmulD | FdivD => write!(
+                f,
+                "{op} {}, {}, {}{}",
+                freg(rd),
+                freg(rs1),
+                freg(rs2),
+                RoundingMode(self.rm())
+            ),
+            FsqrtS | FsqrtD | FcvtSD => write!(
+                f,
+                "{op} {}, {}{}",
+                freg(rd),
+                freg(rs1),
+                RoundingMode(self.rm())
+            ),
+            FsgnjS | FsgnjnS | FsgnjxS | FsgnjD | FsgnjnD | FsgnjxD if rs1 == rs2 => {
+                let op = match self.op_code {
+                    FsgnjS => "fmv.s",
+                    FsgnjnS => "fneg.s",
+                    FsgnjxS => "fabs.s",
+                    FsgnjD => "fmv.d",
+                    FsgnjnD => "fneg.d",
+                    _ => "fabs.d",
+                };
+                write!(f, "{op} {}, {}", freg(rd), freg(rs1))
+            }
+            FsgnjS | FsgnjnS | FsgnjxS | FminS | FmaxS | FsgnjD | FsgnjnD | FsgnjxD | FminD
+            | FmaxD => {
+                write!(f, "{op} {}, {}, {}", freg(rd), freg(rs1), freg(rs2))
+            }
+            FcvtWS | FcvtWuS | FcvtWD | FcvtWuD => write!(
+                f,
+                "{op} {}, {}{}",
+                reg(rd),
+                freg(rs1),
+                RoundingMode(self.rm())
+            ),
+            FmvXW | FclassS | FclassD => write!(f, "{op} {}, {}", reg(rd), freg(rs1)),
+            FeqS | FltS | FleS | FeqD | FltD | FleD => {
+                write!(f, "{op} {}, {}, {}", reg(rd), freg(rs1), freg(rs2))
+            }
+            FcvtSW | FcvtSWu => write!(
+                f,
+                "{op} {}, {}{}",
+                freg(rd),
+                reg(rs1),
+                RoundingMode(self.rm())
+            ),
+            FmvWX => write!(f, "{op} {}, {}", freg(rd), reg(rs1)),
+            // Rounding mode is not shown since the conversions are always exact.
+            FcvtDS => write!(f, "{op} {}, {}", freg(rd), freg(rs1)),
+            FcvtDW | FcvtDWu => write!(f, "{op} {}, {}", freg(rd), reg(rs1)),
+        }
+    }
+}
+
+/// Render instruction in assembly syntax with ABI register names and pseudo-instruction forms.
+/// Branch and jump targets are shown as offsets, use `Instruction::display_at` for absolute ones.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_asm(f, None)
+    }
+}
+
+/// Disassembled instruction. Compressed instructions are held in the lower 16 bits of word.
+#[derive(Debug)]
+pub struct Line {
+    pub addr: u32,
+    pub word: u32,
+    pub instruction: Result<Instruction, DecodeError>,
+}
+
+impl Line {
+    /// Instruction length in bytes.
+    pub fn size(&self) -> u32 {
+        if compressed::is_compressed(self.word) {
+            2
+        } else {
+            4
+        }
+    }
+}
+
+/// Render in objdump like form `addr: word  assembly`. Invalid words are shown as `.word` or `.half`.
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.size() == 2 {
+            write!(f, "{:08x}: {:04x}      ", self.addr, self.word)?;
+        } else {
+            write!(f, "{:08x}: {:08x}  ", self.addr, self.word)?;
+        }
+        match (&self.instruction, self.size()) {
+            (Ok(instruction), _) => write!(f, "{}", instruction.display_at(self.addr)),
+            (Err(_), 2) => write!(f, ".half {:#06x}", self.word),
+            (Err(_), _) => write!(f, ".word {:#010x}", self.word),
+        }
+    }
+}
+
+/// Disassemble little-endian 16-bit and 32-bit instructions in bytes located at addr.
+/// Trailing bytes which do not form a whole instruction are ignored.
+pub fn disassemble(bytes: &[u8], addr: u32) -> impl Iterator<Item = Line> + '_ {
+    let decoder = Decoder::new();
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        let half = |i: usize| {
+            bytes
+                .get(i..i + 2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]) as u32)
+        };
+        let lo = half(offset)?;
+        let word = if compressed::is_compressed(lo) {
+            lo
+        } else {
+            half(offset + 2)? << 16 | lo
+        };
+        let line = Line {
+            addr: addr.wrapping_add(offset as u32),
+            word,
+            instruction: decoder.try_decode(word),
+        };
+        offset += line.size() as usize;
+        Some(line)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asm(ir: u32) -> String {
+        Decoder::new().try_decode(ir).unwrap().to_string()
+    }
+
+    #[test]
+    fn display_base_instructions() {
+        assert_eq!(asm(0x1234_5537), "lui a0, 0x12345");
+        assert_eq!(asm(0x0000_0517), "auipc a0, 0x0");
+        assert_eq!(asm(0x0085_2583), "lw a1, 8(a0)");
+        assert_eq!(asm(0xfeb5_2e23), "sw a1, -4(a0)");
+        assert_eq!(asm(0x00b5_0633), "add a2, a0, a1");
+        assert_eq!(asm(0x40b5_0633), "sub a2, a0, a1");
+        assert_eq!(asm(0x4035_d513), "srai a0, a1, 3");
+        assert_eq!(asm(0xfff5_4513), "not a0, a0");
+        assert_eq!(asm(0x02b5_4533), "div a0, a0, a1");
+        assert_eq!(asm(0x0ff0_000f), "fence");
+        assert_eq!(asm(0x0230_000f), "fence r, rw");
+        assert_eq!(asm(0x0000_100f), "fence.i");
+        assert_eq!(asm(0x1050_0073), "wfi");
+        assert_eq!(asm(0x1020_0073), "sret");
+        assert_eq!(asm(0x1200_0073), "sfence.vma");
+        assert_eq!(asm(0x1205_0073), "sfence.vma a0");
+        assert_eq!(asm(0x12b5_0073), "sfence.vma a0, a1");
+        assert_eq!(asm(0x12b0_0073), "sfence.vma zero, a1");
+    }
+
+    #[test]
+    fn display_pseudo_instructions() {
+        assert_eq!(asm(0x0000_0013), "nop");
+        assert_eq!(asm(0xfff0_0513), "li a0, -1");
+        assert_eq!(asm(0x0005_8513), "mv a0, a1");
+        assert_eq!(asm(0x0000_8067), "ret");
+        assert_eq!(asm(0x0005_0067), "jr a0");
+        assert_eq!(asm(0x0005_00e7), "jalr a0");
+        assert_eq!(asm(0xffdf_f06f), "j -4");
+        assert_eq!(asm(0x0100_00ef), "jal 16");
+        assert_eq!(asm(0x0005_0463), "beqz a0, 8");
+        assert_eq!(asm(0x00a0_4463), "bgtz a0, 8");
+        assert_eq!(asm(0x00a0_05b3), "mv a1, a0");
+        assert_eq!(asm(0x40a0_05b3), "neg a1, a0");
+        assert_eq!(asm(0x00a0_35b3), "snez a1, a0");
+        assert_eq!(asm(0x0015_3593), "seqz a1, a0");
+    }
+
+    #[test]
+    fn display_atomic_and_csr() {
+        assert_eq!(asm(0x1005_26af), "lr.w a3, (a0)");
+        assert_eq!(asm(0x1ec5_26af), "sc.w.aqrl a3, a2, (a0)");
+        assert_eq!(asm(0x00c5_26af), "amoadd.w a3, a2, (a0)");
+        assert_eq!(asm(0x3000_2573), "csrr a0, mstatus");
+        assert_eq!(asm(0x3055_1073), "csrw mtvec, a0");
+        assert_eq!(asm(0x3004_6073), "csrsi mstatus, 8");
+        assert_eq!(asm(0x3415_95f3), "csrrw a1, mepc, a1");
+        assert_eq!(asm(0x7c00_25f3), "csrr a1, 0x7c0");
+    }
+
+    #[test]
+    fn display_floating_point() {
+        assert_eq!(asm(0xffc5_a507), "flw fa0, -4(a1)");
+        assert_eq!(asm(0x00a5_a227), "fsw fa0, 4(a1)");
+        assert_eq!(asm(0x68c5_9543), "fmadd.s fa0, fa1, fa2, fa3, rtz");
+        assert_eq!(asm(0x00c5_f553), "fadd.s fa0, fa1, fa2");
+        assert_eq!(asm(0x5805_f553), "fsqrt.s fa0, fa1");
+        assert_eq!(asm(0x20b5_8553), "fmv.s fa0, fa1");
+        assert_eq!(asm(0x20b5_9553), "fneg.s fa0, fa1");
+        assert_eq!(asm(0x28c5_9553), "fmax.s fa0, fa1, fa2");
+        assert_eq!(asm(0xc015_9553), "fcvt.wu.s a0, fa1, rtz");
+        assert_eq!(asm(0xe005_8553), "fmv.x.w a0, fa1");
+        assert_eq!(asm(0xa0c5_a553), "feq.s a0, fa1, fa2");
+        assert_eq!(asm(0xe005_9553), "fclass.s a0, fa1");
+        assert_eq!(asm(0xd005_f553), "fcvt.s.w fa0, a1");
+        assert_eq!(asm(0xf005_8553), "fmv.w.x fa0, a1");
+        assert_eq!(asm(0x00a1_3427), "fsd fa0, 8(sp)");
+        assert_eq!(asm(0x6ac5_9543), "fmadd.d fa0, fa1, fa2, fa3, rtz");
+        assert_eq!(asm(0x4015_9553), "fcvt.s.d fa0, fa1, rtz");
+        assert_eq!(asm(0x4205_8553), "fcvt.d.s fa0, fa1");
+        assert_eq!(asm(0xd205_8553), "fcvt.d.w fa0, a1");
+        assert_eq!(asm(0x22b5_9553), "fneg.d fa0, fa1");
+        assert_eq!(asm(0x22c5_9553), "fsgnjn.d fa0, fa1, fa2");
+        assert_eq!(asm(0xe205_9553), "fclass.d a0, fa1");
+    }
+
+    #[test]
+    fn disassemble_with_addresses() {
+        let bytes: Vec<u8> = [0x0000_0013u32, 0xffdf_f06f, 0xffff_ffff]
+            .iter()
+            .flat_map(|ir| ir.to_le_bytes())
+            // c.li a0, 1, reserved c.lwsp x0 and a truncated 32-bit instruction
+            .chain([0x05, 0x45, 0x02, 0x40, 0x13])
+            .collect();
+        let lines: Vec<String> = disassemble(&bytes, 0x8000_0000)
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "80000000: 00000013  nop",
+                "80000004: ffdff06f  j 0x80000000",
+                "80000008: ffffffff  .word 0xffffffff",
+                "8000000c: 4505      li a0, 1",
+                "8000000e: 4002      .half 0x4002",
+            ]
+        );
+    }
+}
@@ -0,0 +1,674 @@
+//! Encoder which builds instruction words from typed operands.
+//! Volume I: RISC-V Unprivileged ISA V20191213 P130
+
+use thiserror::Error;
+
+use super::{Format, OpCode, RegisterIdx};
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeError {
+    #[error("register x{0} does not exist")]
+    InvalidRegister(RegisterIdx),
+    #[error("immediate {imm} of {op_code:?} is out of range {min}..={max}")]
+    ImmediateOutOfRange {
+        op_code: OpCode,
+        imm: i32,
+        min: i32,
+        max: i32,
+    },
+    #[error("offset {imm} of {op_code:?} is not a multiple of 2")]
+    MisalignedOffset { op_code: OpCode, imm: i32 },
+    #[error("csr {0:#x} is out of range")]
+    InvalidCsr(usize),
+    #[error("rounding mode {0:#b} is out of range")]
+    InvalidRoundingMode(u32),
+}
+
+/// Operands of an instruction. Fields not used by the op code are ignored.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Operands {
+    pub rd: RegisterIdx,
+    pub rs1: RegisterIdx,
+    pub rs2: RegisterIdx,
+    /// Third source register of R4-type instructions
+    pub rs3: RegisterIdx,
+    /// Sign-extended immediate, shift amount, branch/jump offset, upper 20 bits of LUI/AUIPC,
+    /// zimm of CSR immediate instructions or fm/pred/succ of FENCE.
+    pub imm: i32,
+    pub csr: usize,
+    /// Acquire bit of atomic instructions
+    pub aq: bool,
+    /// Release bit of atomic instructions
+    pub rl: bool,
+    /// Rounding mode of floating-point instructions. 0b111 selects the dynamic rounding mode in frm.
+    pub rm: u32,
+}
+
+/// Major opcode, funct3 and funct7 of the op code.
+/// funct7 of atomic instructions holds funct5 in the upper bits.
+fn fields(op_code: OpCode) -> (u32, u32, u32) {
+    use OpCode::*;
+    match op_code {
+        Lui => (0b0110111, 0, 0),
+        Auipc => (0b0010111, 0, 0),
+        Jal => (0b1101111, 0, 0),
+        Jalr => (0b1100111, 0b000, 0),
+        Beq => (0b1100011, 0b000, 0),
+        Bne => (0b1100011, 0b001, 0),
+        Blt => (0b1100011, 0b100, 0),
+        Bge => (0b1100011, 0b101, 0),
+        Bltu => (0b1100011, 0b110, 0),
+        Bgeu => (0b1100011, 0b111, 0),
+        Lb => (0b0000011, 0b000, 0),
+        Lh => (0b0000011, 0b001, 0),
+        Lw => (0b0000011, 0b010, 0),
+        Lbu => (0b0000011, 0b100, 0),
+        Lhu => (0b0000011, 0b101, 0),
+        Sb => (0b0100011, 0b000, 0),
+        Sh => (0b0100011, 0b001, 0),
+        Sw => (0b0100011, 0b010, 0),
+        Addi => (0b0010011, 0b000, 0),
+        Slti => (0b0010011, 0b010, 0),
+        Sltiu => (0b0010011, 0b011, 0),
+        Xori => (0b0010011, 0b100, 0),
+        Ori => (0b0010011, 0b110, 0),
+        Andi => (0b0010011, 0b111, 0),
+        Slli => (0b0010011, 0b001, 0b0000000),
+        Srli => (0b0010011, 0b101, 0b0000000),
+        Srai => (0b0010011, 0b101, 0b0100000),
+        Add => (0b0110011, 0b000, 0b0000000),
+        Sub => (0b0110011, 0b000, 0b0100000),
+        Sll => (0b0110011, 0b001, 0b0000000),
+        Slt => (0b0110011, 0b010, 0b0000000),
+        Sltu => (0b0110011, 0b011, 0b0000000),
+        Xor => (0b0110011, 0b100, 0b0000000),
+        Srl => (0b0110011, 0b101, 0b0000000),
+        Sra => (0b0110011, 0b101, 0b0100000),
+        Or => (0b0110011, 0b110, 0b0000000),
+        And => (0b0110011, 0b111, 0b0000000),
+        Mul => (0b0110011, 0b000, 0b0000001),
+        Mulh => (0b0110011, 0b001, 0b0000001),
+        Mulhsu => (0b0110011, 0b010, 0b0000001),
+        Mulhu => (0b0110011, 0b011, 0b0000001),
+        Div => (0b0110011, 0b100, 0b0000001),
+        Divu => (0b0110011, 0b101, 0b0000001),
+        Rem => (0b0110011, 0b110, 0b0000001),
+        Remu => (0b0110011, 0b111, 0b0000001),
+        Fence => (0b0001111, 0b000, 0),
+        FenceI => (0b0001111, 0b001, 0),
+        LrW => (0b0101111, 0b010, 0b00010 << 2),
+        ScW => (0b0101111, 0b010, 0b00011 << 2),
+        AmoswapW => (0b0101111, 0b010, 0b00001 << 2),
+        AmoaddW => (0b0101111, 0b010, 0b00000 << 2),
+        AmoxorW => (0b0101111, 0b010, 0b00100 << 2),
+        AmoandW => (0b0101111, 0b010, 0b01100 << 2),
+        AmoorW => (0b0101111, 0b010, 0b01000 << 2),
+        AmominW => (0b0101111, 0b010, 0b10000 << 2),
+        AmomaxW => (0b0101111, 0b010, 0b10100 << 2),
+        AmominuW => (0b0101111, 0b010, 0b11000 << 2),
+        AmomaxuW => (0b0101111, 0b010, 0b11100 << 2),
+        Ecall | Ebreak | Mret | Sret | Wfi => (0b1110011, 0b000, 0),
+        SfenceVma => (0b1110011, 0b000, 0b0001001),
+        Csrrw => (0b1110011, 0b001, 0),
+        Csrrs => (0b1110011, 0b010, 0),
+        Csrrc => (0b1110011, 0b011, 0),
+        Csrrwi => (0b1110011, 0b101, 0),
+        Csrrsi => (0b1110011, 0b110, 0),
+        Csrrci => (0b1110011, 0b111, 0),
+        Flw => (0b0000111, 0b010, 0),
+        Fsw => (0b0100111, 0b010, 0),
+        FmaddS => (0b1000011, 0, 0),
+        FmsubS => (0b1000111, 0, 0),
+        FnmsubS => (0b1001011, 0, 0),
+        FnmaddS => (0b1001111, 0, 0),
+        FaddS => (0b1010011, 0, 0b0000000),
+        FsubS => (0b1010011, 0, 0b0000100),
+        FmulS => (0b1010011, 0, 0b0001000),
+        FdivS => (0b1010011, 0, 0b0001100),
+        FsqrtS => (0b1010011, 0, 0b0101100),
+        FsgnjS => (0b1010011, 0b000, 0b0010000),
+        FsgnjnS => (0b1010011, 0b001, 0b0010000),
+        FsgnjxS => (0b1010011, 0b010, 0b0010000),
+        FminS => (0b1010011, 0b000, 0b0010100),
+        FmaxS => (0b1010011, 0b001, 0b0010100),
+        FcvtWS | FcvtWuS => (0b1010011, 0, 0b1100000),
+        FmvXW => (0b1010011, 0b000, 0b1110000),
+        FeqS => (0b1010011, 0b010, 0b1010000),
+        FltS => (0b1010011, 0b001, 0b1010000),
+        FleS => (0b1010011, 0b000, 0b1010000),
+        FclassS => (0b1010011, 0b001, 0b1110000),
+        FcvtSW | FcvtSWu => (0b1010011, 0, 0b1101000),
+        FmvWX => (0b1010011, 0b000, 0b1111000),
+        Fld => (0b0000111, 0b011, 0),
+        Fsd => (0b0100111, 0b011, 0),
+        // funct7 of R4-type instructions holds fmt(bits 26:25).
+        FmaddD => (0b1000011, 0, 0b01),
+        FmsubD => (0b1000111, 0, 0b01),
+        FnmsubD => (0b1001011, 0, 0b01),
+        FnmaddD => (0b1001111, 0, 0b01),
+        FaddD => (0b1010011, 0, 0b0000001),
+        FsubD => (0b1010011, 0, 0b0000101),
+        FmulD => (0b1010011, 0, 0b0001001),
+        FdivD => (0b1010011, 0, 0b0001101),
+        FsqrtD => (0b1010011, 0, 0b0101101),
+        FsgnjD => (0b1010011, 0b000, 0b0010001),
+        FsgnjnD => (0b1010011, 0b001, 0b0010001),
+        FsgnjxD => (0b1010011, 0b010, 0b0010001),
+        FminD => (0b1010011, 0b000, 0b0010101),
+        FmaxD => (0b1010011, 0b001, 0b0010101),
+        FcvtSD => (0b1010011, 0, 0b0100000),
+        FcvtDS => (0b1010011, 0, 0b0100001),
+        FeqD => (0b1010011, 0b010, 0b1010001),
+        FltD => (0b1010011, 0b001, 0b1010001),
+        FleD => (0b1010011, 0b000, 0b1010001),
+        FclassD => (0b1010011, 0b001, 0b1110001),
+        FcvtWD | FcvtWuD => (0b1010011, 0, 0b1100001),
+        FcvtDW | FcvtDWu => (0b1010011, 0, 0b1101001),
+    }
+}
+
+/// Return whether funct3 of the op code holds the rounding mode.
+fn has_rounding_mode(op_code: OpCode) -> bool {
+    use OpCode::*;
+    matches!(
+        op_code,
+        FmaddS
+            | FmsubS
+            | FnmsubS
+            | FnmaddS
+            | FaddS
+            | FsubS
+            | FmulS
+            | FdivS
+            | FsqrtS
+            | FcvtWS
+            | FcvtWuS
+            | FcvtSW
+            | FcvtSWu
+            | FmaddD
+            | FmsubD
+            | FnmsubD
+            | FnmaddD
+            | FaddD
+            | FsubD
+            | FmulD
+            | FdivD
+            | FsqrtD
+            | FcvtSD
+            | FcvtDS
+            | FcvtWD
+            | FcvtWuD
+            | FcvtDW
+            | FcvtDWu
+    )
+}
+
+fn check_register(r: RegisterIdx) -> Result<u32, EncodeError> {
+    if r < 32 {
+        Ok(r as u32)
+    } else {
+        Err(EncodeError::InvalidRegister(r))
+    }
+}
+
+fn check_range(op_code: OpCode, imm: i32, min: i32, max: i32) -> Result<u32, EncodeError> {
+    if (min..=max).contains(&imm) {
+        Ok(imm as u32)
+    } else {
+        Err(EncodeError::ImmediateOutOfRange {
+            op_code,
+            imm,
+            min,
+            max,
+        })
+    }
+}
+
+fn check_offset(op_code: OpCode, imm: i32, bits: u32) -> Result<u32, EncodeError> {
+    let imm = check_range(op_code, imm, -(1 << (bits - 1)), (1 << (bits - 1)) - 2)?;
+    if imm & 1 != 0 {
+        return Err(EncodeError::MisalignedOffset {
+            op_code,
+            imm: imm as i32,
+        });
+    }
+    Ok(imm)
+}
+
+/// Encode instruction into a 32-bit word.
+pub fn encode(op_code: OpCode, operands: Operands) -> Result<u32, EncodeError> {
+    use OpCode::*;
+    let (opcode, funct3, funct7) = fields(op_code);
+    let rd = check_register(operands.rd)?;
+    let rs1 = check_register(operands.rs1)?;
+    let rs2 = check_register(operands.rs2)?;
+    let imm = operands.imm;
+    let funct3 = if has_rounding_mode(op_code) {
+        match operands.rm {
+            rm @ 0..=0b111 => rm,
+            rm => return Err(EncodeError::InvalidRoundingMode(rm)),
+        }
+    } else {
+        funct3
+    };
+
+    let ir = match op_code {
+        Ecall => 0x0000_0073,
+        Ebreak => 0x0010_0073,
+        Mret => 0x3020_0073,
+        Sret => 0x1020_0073,
+        Wfi => 0x1050_0073,
+        FenceI => 0x0000_100f,
+        // rd is always zero.
+        SfenceVma => (funct7 << 25) | (rs2 << 20) | (rs1 << 15) | opcode,
+        Slli | Srli | Srai => {
+            let shamt = check_range(op_code, imm, 0, 31)?;
+            (funct7 << 25) | (shamt << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+        }
+        Fence => {
+            let imm = check_range(op_code, imm, 0, 0xfff)?;
+            (imm << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+        }
+        Csrrw | Csrrs | Csrrc | Csrrwi | Csrrsi | Csrrci => {
+            if operands.csr > 0xfff {
+                return Err(EncodeError::InvalidCsr(operands.csr));
+            }
+            let src = match op_code {
+                Csrrwi | Csrrsi | Csrrci => check_range(op_code, imm, 0, 31)?,
+                _ => rs1,
+            };
+            ((operands.csr as u32) << 20) | (src << 15) | (funct3 << 12) | (rd << 7) | opcode
+        }
+        LrW | ScW | AmoswapW | AmoaddW | AmoxorW | AmoandW | AmoorW | AmominW | AmomaxW
+        | AmominuW | AmomaxuW => {
+            let rs2 = if op_code == LrW { 0 } else { rs2 };
+            let funct7 = funct7 | (u32::from(operands.aq) << 1) | u32::from(operands.rl);
+            (funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+        }
+        FsqrtS | FcvtWS | FcvtWuS | FmvXW | FclassS | FcvtSW | FcvtSWu | FmvWX | FsqrtD
+        | FcvtSD | FcvtDS | FclassD | FcvtWD | FcvtWuD | FcvtDW | FcvtDWu => {
+            // rs2 selects the variant.
+            let rs2 = match op_code {
+                FcvtWuS | FcvtSWu | FcvtSD | FcvtWuD | FcvtDWu => 1,
+                _ => 0,
+            };
+            (funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+        }
+        _ => match Format::of(op_code) {
+            Format::R4 => {
+                let rs3 = check_register(operands.rs3)?;
+                (rs3 << 27)
+                    | (funct7 << 25)
+                    | (rs2 << 20)
+                    | (rs1 << 15)
+                    | (funct3 << 12)
+                    | (rd << 7)
+                    | opcode
+            }
+            Format::R => {
+                (funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+            }
+            Format::I => {
+                let imm = check_range(op_code, imm, -2048, 2047)?;
+                ((imm & 0xfff) << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+            }
+            Format::S => {
+                let imm = check_range(op_code, imm, -2048, 2047)?;
+                ((imm & 0xfe0) << 20)
+                    | (rs2 << 20)
+                    | (rs1 << 15)
+                    | (funct3 << 12)
+                    | ((imm & 0x1f) << 7)
+                    | opcode
+            }
+            Format::B => {
+                let imm = check_offset(op_code, imm, 13)?;
+                ((imm & 0x1000) << 19)
+                    | ((imm & 0x7e0) << 20)
+                    | (rs2 << 20)
+                    | (rs1 << 15)
+                    | (funct3 << 12)
+                    | ((imm & 0x1e) << 7)
+                    | ((imm & 0x800) >> 4)
+                    | opcode
+            }
+            Format::U => {
+                let imm = check_range(op_code, imm, -(1 << 19), (1 << 20) - 1)?;
+                ((imm & 0xfffff) << 12) | (rd << 7) | opcode
+            }
+            Format::J => {
+                let imm = check_offset(op_code, imm, 21)?;
+                (imm & 0x10_0000) << 11
+                    | ((imm & 0x7fe) << 20)
+                    | ((imm & 0x800) << 9)
+                    | (imm & 0xf_f000)
+                    | (rd << 7)
+                    | opcode
+            }
+        },
+    };
+    Ok(ir)
+}
+
+/// Encode R-type instruction `op rd, rs1, rs2`.
+pub fn r(
+    op_code: OpCode,
+    rd: RegisterIdx,
+    rs1: RegisterIdx,
+    rs2: RegisterIdx,
+) -> Result<u32, EncodeError> {
+    encode(
+        op_code,
+        Operands {
+            rd,
+            rs1,
+            rs2,
+            ..Default::default()
+        },
+    )
+}
+
+/// Encode I-type instruction `op rd, rs1, imm`. Loads and JALR take `op rd, imm(rs1)` in assembly.
+pub fn i(op_code: OpCode, rd: RegisterIdx, rs1: RegisterIdx, imm: i32) -> Result<u32, EncodeError> {
+    encode(
+        op_code,
+        Operands {
+            rd,
+            rs1,
+            imm,
+            ..Default::default()
+        },
+    )
+}
+
+/// Encode store `op rs2, imm(rs1)`.
+pub fn s(
+    op_code: OpCode,
+    rs2: RegisterIdx,
+    rs1: RegisterIdx,
+    imm: i32,
+) -> Result<u32, EncodeError> {
+    encode(
+        op_code,
+        Operands {
+            rs1,
+            rs2,
+            imm,
+            ..Default::default()
+        },
+    )
+}
+
+/// Encode branch `op rs1, rs2, offset`.
+pub fn b(
+    op_code: OpCode,
+    rs1: RegisterIdx,
+    rs2: RegisterIdx,
+    offset: i32,
+) -> Result<u32, EncodeError> {
+    encode(
+        op_code,
+        Operands {
+            rs1,
+            rs2,
+            imm: offset,
+            ..Default::default()
+        },
+    )
+}
+
+/// Encode LUI/AUIPC `op rd, imm`. imm is the upper 20 bits.
+pub fn u(op_code: OpCode, rd: RegisterIdx, imm: i32) -> Result<u32, EncodeError> {
+    encode(
+        op_code,
+        Operands {
+            rd,
+            imm,
+            ..Default::default()
+        },
+    )
+}
+
+/// Encode JAL `jal rd, offset`.
+pub fn j(rd: RegisterIdx, offset: i32) -> Result<u32, EncodeError> {
+    encode(
+        OpCode::Jal,
+        Operands {
+            rd,
+            imm: offset,
+            ..Default::default()
+        },
+    )
+}
+
+/// Encode CSR instruction `op rd, csr, rs1` or `op rd, csr, zimm` for the immediate variants.
+pub fn csr(op_code: OpCode, rd: RegisterIdx, csr: usize, src: u32) -> Result<u32, EncodeError> {
+    let (rs1, imm) = match op_code {
+        OpCode::Csrrwi | OpCode::Csrrsi | OpCode::Csrrci => (0, src as i32),
+        _ => (src as RegisterIdx, 0),
+    };
+    encode(
+        op_code,
+        Operands {
+            rd,
+            rs1,
+            imm,
+            csr,
+            ..Default::default()
+        },
+    )
+}
+
+/// Encode atomic instruction `op rd, rs2, (rs1)`. rs2 is ignored for LR.
+pub fn amo(
+    op_code: OpCode,
+    rd: RegisterIdx,
+    rs1: RegisterIdx,
+    rs2: RegisterIdx,
+    aq: bool,
+    rl: bool,
+) -> Result<u32, EncodeError> {
+    encode(
+        op_code,
+        Operands {
+            rd,
+            rs1,
+            rs2,
+            aq,
+            rl,
+            ..Default::default()
+        },
+    )
+}
+
+/// Encode floating-point instruction `op rd, rs1, rs2, rm`. rs2 is ignored by single operand instructions.
+pub fn fp(
+    op_code: OpCode,
+    rd: RegisterIdx,
+    rs1: RegisterIdx,
+    rs2: RegisterIdx,
+    rm: u32,
+) -> Result<u32, EncodeError> {
+    encode(
+        op_code,
+        Operands {
+            rd,
+            rs1,
+            rs2,
+            rm,
+            ..Default::default()
+        },
+    )
+}
+
+/// Encode fused multiply-add `op rd, rs1, rs2, rs3, rm`.
+pub fn r4(
+    op_code: OpCode,
+    rd: RegisterIdx,
+    rs1: RegisterIdx,
+    rs2: RegisterIdx,
+    rs3: RegisterIdx,
+    rm: u32,
+) -> Result<u32, EncodeError> {
+    encode(
+        op_code,
+        Operands {
+            rd,
+            rs1,
+            rs2,
+            rs3,
+            rm,
+            ..Default::default()
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::Decoder;
+    use OpCode::*;
+
+    const ALL: [OpCode; 122] = [
+        Lui, Auipc, Jal, Jalr, Beq, Bne, Blt, Bltu, Bge, Bgeu, Lb, Lh, Lw, Lbu, Lhu, Sb, Sh, Sw,
+        Addi, Slti, Sltiu, Xori, Ori, Andi, Slli, Srli, Srai, Add, Sub, Sll, Slt, Sltu, Xor, Srl,
+        Sra, Or, And, Mul, Mulh, Mulhsu, Mulhu, Div, Divu, Rem, Remu, Fence, FenceI, LrW, ScW,
+        AmoswapW, AmoaddW, AmoxorW, AmoandW, AmoorW, AmominW, AmomaxW, AmominuW, AmomaxuW, Ecall,
+        Ebreak, Mret, Sret, Wfi, SfenceVma, Csrrw, Csrrs, Csrrc, Csrrwi, Csrrsi, Csrrci, Flw, Fsw,
+        FmaddS, FmsubS, FnmsubS, FnmaddS, FaddS, FsubS, FmulS, FdivS, FsqrtS, FsgnjS, FsgnjnS,
+        FsgnjxS, FminS, FmaxS, FcvtWS, FcvtWuS, FmvXW, FeqS, FltS, FleS, FclassS, FcvtSW, FcvtSWu,
+        FmvWX, Fld, Fsd, FmaddD, FmsubD, FnmsubD, FnmaddD, FaddD, FsubD, FmulD, FdivD, FsqrtD,
+        FsgnjD, FsgnjnD, FsgnjxD, FminD, FmaxD, FcvtSD, FcvtDS, FeqD, FltD, FleD, FclassD, FcvtWD,
+        FcvtWuD, FcvtDW, FcvtDWu,
+    ];
+
+    #[test]
+    fn round_trip() {
+        let d = Decoder::new();
+        for op_code in ALL {
+            let imm = match Format::of(op_code) {
+                Format::U => -3,
+                Format::B | Format::J => -6,
+                _ => match op_code {
+                    Slli | Srli | Srai | Csrrwi | Csrrsi | Csrrci => 7,
+                    Fence => 0x0ff,
+                    _ => -5,
+                },
+            };
+            let operands = Operands {
+                rd: 3,
+                rs1: 17,
+                rs2: 31,
+                rs3: 9,
+                imm,
+                csr: 0x340,
+                aq: true,
+                rl: false,
+                rm: 0b111,
+            };
+            let ir = d.try_decode(encode(op_code, operands).unwrap()).unwrap();
+            assert_eq!(ir.op_code, op_code);
+
+            let format = Format::of(op_code);
+            match op_code {
+                Ecall | Ebreak | Mret | Sret | Wfi | Fence | FenceI => continue,
+                _ => (),
+            }
+            if format != Format::S && format != Format::B && op_code != SfenceVma {
+                assert_eq!(ir.rd(), 3, "{op_code:?}");
+            }
+            if format != Format::U && format != Format::J {
+                match op_code {
+                    Csrrwi | Csrrsi | Csrrci => assert_eq!(ir.rs1(), 7),
+                    _ => assert_eq!(ir.rs1(), 17, "{op_code:?}"),
+                }
+            }
+            match op_code {
+                LrW | FsqrtS | FcvtWS | FmvXW | FclassS | FcvtSW | FmvWX | FsqrtD | FcvtDS
+                | FclassD | FcvtWD | FcvtDW => assert_eq!(ir.rs2(), 0),
+                FcvtWuS | FcvtSWu | FcvtSD | FcvtWuD | FcvtDWu => assert_eq!(ir.rs2(), 1),
+                _ if matches!(format, Format::R | Format::R4 | Format::S | Format::B) => {
+                    assert_eq!(ir.rs2(), 31, "{op_code:?}")
+                }
+                _ => (),
+            }
+            if format == Format::R4 {
+                assert_eq!(ir.rs3(), 9);
+            }
+            if has_rounding_mode(op_code) {
+                assert_eq!(ir.rm(), 0b111, "{op_code:?}");
+            }
+            match op_code {
+                Slli | Srli | Srai => assert_eq!(ir.shamt(), 7),
+                Csrrw | Csrrs | Csrrc | Csrrwi | Csrrsi | Csrrci => assert_eq!(ir.csr(), 0x340),
+                Lui | Auipc => assert_eq!(ir.imm(), 0xffff_d000),
+                LrW | ScW | AmoswapW | AmoaddW | AmoxorW | AmoandW | AmoorW | AmominW | AmomaxW
+                | AmominuW | AmomaxuW => assert!(ir.aq() && !ir.rl()),
+                _ if matches!(format, Format::R | Format::R4) => (),
+                _ => assert_eq!(ir.imm_signed(), imm, "{op_code:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn known_encodings() {
+        assert_eq!(u(Lui, 10, 0x12345), Ok(0x1234_5537));
+        assert_eq!(i(Addi, 10, 0, -1), Ok(0xfff0_0513));
+        assert_eq!(i(Lw, 11, 10, 8), Ok(0x0085_2583));
+        assert_eq!(s(Sw, 11, 10, -4), Ok(0xfeb5_2e23));
+        assert_eq!(r(Sub, 12, 10, 11), Ok(0x40b5_0633));
+        assert_eq!(i(Srai, 10, 11, 3), Ok(0x4035_d513));
+        assert_eq!(b(Bge, 0, 10, 8), Ok(0x00a0_5463));
+        assert_eq!(b(Bne, 1, 2, -4096), Ok(0x8020_9063));
+        assert_eq!(j(0, -4), Ok(0xffdf_f06f));
+        assert_eq!(j(1, 16), Ok(0x0100_00ef));
+        assert_eq!(amo(ScW, 13, 10, 12, true, true), Ok(0x1ec5_26af));
+        assert_eq!(csr(Csrrs, 10, 0x300, 0), Ok(0x3000_2573));
+        assert_eq!(csr(Csrrsi, 0, 0x300, 8), Ok(0x3004_6073));
+        assert_eq!(encode(Wfi, Operands::default()), Ok(0x1050_0073));
+        assert_eq!(r4(FmaddS, 10, 11, 12, 13, 0b001), Ok(0x68c5_9543));
+        assert_eq!(fp(FcvtWuS, 10, 11, 0, 0b001), Ok(0xc015_9553));
+        assert_eq!(fp(FeqS, 10, 11, 12, 0), Ok(0xa0c5_a553));
+        assert_eq!(i(Flw, 10, 11, -4), Ok(0xffc5_a507));
+        assert_eq!(r4(FmaddD, 10, 11, 12, 13, 0b001), Ok(0x6ac5_9543));
+        assert_eq!(fp(FcvtSD, 10, 11, 0, 0b111), Ok(0x4015_f553));
+        assert_eq!(s(Fsd, 10, 2, 8), Ok(0x00a1_3427));
+    }
+
+    #[test]
+    fn range_checks() {
+        assert_eq!(
+            i(Addi, 1, 0, 2048),
+            Err(EncodeError::ImmediateOutOfRange {
+                op_code: Addi,
+                imm: 2048,
+                min: -2048,
+                max: 2047
+            })
+        );
+        assert!(i(Addi, 1, 0, -2048).is_ok());
+        assert!(s(Sw, 1, 0, -2049).is_err());
+        assert!(i(Slli, 1, 1, 32).is_err());
+        assert!(b(Beq, 1, 2, 4096).is_err());
+        assert_eq!(
+            b(Beq, 1, 2, 3),
+            Err(EncodeError::MisalignedOffset {
+                op_code: Beq,
+                imm: 3
+            })
+        );
+        assert!(j(0, 1 << 20).is_err());
+        assert!(j(0, -(1 << 20)).is_ok());
+        assert!(u(Lui, 1, 1 << 20).is_err());
+        assert_eq!(r(Add, 32, 0, 0), Err(EncodeError::InvalidRegister(32)));
+        assert_eq!(
+            csr(Csrrw, 0, 0x1000, 1),
+            Err(EncodeError::InvalidCsr(0x1000))
+        );
+        assert!(csr(Csrrwi, 0, 0x300, 32).is_err());
+        assert_eq!(
+            fp(FaddS, 1, 2, 3, 8),
+            Err(EncodeError::InvalidRoundingMode(8))
+        );
+    }
+}
@@ -0,0 +1,887 @@
+pub mod asm;
+pub mod compressed;
+pub mod disasm;
+pub mod encode;
+
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCode {
+    /// Load upper immediate
+    /// LUI is used to build 32-bit constants and uses the U-type format.
+    /// LUI palces the U-immediate value in the top 20bits of the destination register rd
+    /// filling in the lowest 12 bits with zeros.
+    Lui,
+    /// Add upper immediate to pc
+    /// Auipc is used to build pc-relative addressed and uses the U-type format.
+    /// AUIPC forms a 32-bit offset from the 20-bit U-immediaate, filling in the lowest 12 bits with zeros
+    /// adds this offset to the address of the AUIPC instruction, then places the result in register rd.
+    Auipc,
+    /// The jump and link (JAL) instruction uses the J-type format,
+    /// where the J-immediate encodes a signed offset in multiples of 2 bytes.
+    /// The offset is sign-extended and added to the address of the jump instruction to form the jump target address.
+    /// Jumps can therefore target a ±1 MiB range. JAL stores the address of the instruction following the jump (pc+4) into register rd.
+    Jal,
+    /// The indirect jump instruction JALR (jump and link register) uses the I-type encoding.
+    /// The target address is obtained by adding the sign-extended 12-bit I-immediate to the register rs1
+    /// then setting the least-significant bit of the result to zero.
+    /// The address of the instruction following the jump (pc+4) is written to register rd.
+    Jalr,
+
+    /// All branch instructions use the B-type instruction format.
+    /// The 12-bit B-immediate encodes signed offsets in multiples of 2 bytes.
+    /// The offset is sign-extended and added to the address of the branch instruction to give the target address.
+    /// The conditional branch range is ±4 KiB.
+    /// Branch if Equal r1 == r2
+    Beq,
+    /// Branch if r1 != r2
+    Bne,
+    /// Branch if r1 < r2
+    Blt,
+    Bltu,
+    /// Branch if r1 >= r2
+    Bge,
+    Bgeu,
+
+    /// Load and store instructions transfer a value between the registers and memory.
+    /// Loads are encoded in the I-type format and stores are S-type.
+    /// The effective address is obtained by adding register rs1 to the sign-extended 12-bit offset.
+    /// Loads copy a value from memory to register rd. Stores copy the value in register rs2 to memory.
+    /// Load byte
+    Lb,
+    /// Load halfword
+    Lh,
+    /// Load word
+    Lw,
+    /// Load byte unsigned
+    Lbu,
+    /// Load halfword unsigned
+    Lhu,
+    /// Store byte
+    Sb,
+    /// Store halfword
+    Sh,
+    /// Store word
+    Sw,
+
+    /// Integer register-immediate instructions are encoded in the I-type format.
+    /// Shifts by a constant use a specialized I-type format where the shift amount is in the lower 5 bits of the I-immediate.
+    /// Add immediate. Arithmetic overflow is ignored.
+    Addi,
+    /// Set less than immediate. Places 1 in rd if rs1 < sign-extended imm (signed comparison).
+    Slti,
+    /// Set less than immediate unsigned. The immediate is first sign-extended then treated as unsigned.
+    Sltiu,
+    /// Bitwise XOR immediate
+    Xori,
+    /// Bitwise OR immediate
+    Ori,
+    /// Bitwise AND immediate
+    Andi,
+    /// Shift left logical immediate
+    Slli,
+    /// Shift right logical immediate
+    Srli,
+    /// Shift right arithmetic immediate
+    Srai,
+
+    /// Integer register-register operations use the R-type format.
+    /// rs1 and rs2 are read as source operands and the result is written into register rd.
+    /// Add. Overflows are ignored.
+    Add,
+    /// Subtract rs2 from rs1. Overflows are ignored.
+    Sub,
+    /// Shift left logical by the lower 5 bits of rs2
+    Sll,
+    /// Set less than (signed)
+    Slt,
+    /// Set less than unsigned
+    Sltu,
+    /// Bitwise XOR
+    Xor,
+    /// Shift right logical by the lower 5 bits of rs2
+    Srl,
+    /// Shift right arithmetic by the lower 5 bits of rs2
+    Sra,
+    /// Bitwise OR
+    Or,
+    /// Bitwise AND
+    And,
+
+    /// Multiplication and division operations of the M extension use the R-type format with funct7 = 0000001.
+    /// Multiply and place the lower 32 bits of the product in rd.
+    Mul,
+    /// Multiply signed x signed and place the upper 32 bits of the product in rd.
+    Mulh,
+    /// Multiply signed rs1 x unsigned rs2 and place the upper 32 bits of the product in rd.
+    Mulhsu,
+    /// Multiply unsigned x unsigned and place the upper 32 bits of the product in rd.
+    Mulhu,
+    /// Signed division rounding towards zero.
+    /// Division by zero results in -1 and overflow(-2^31 / -1) results in -2^31.
+    Div,
+    /// Unsigned division. Division by zero results in 2^32 - 1.
+    Divu,
+    /// Signed remainder. The sign of the result equals the sign of the dividend.
+    /// Remainder by zero results in the dividend and overflow results in 0.
+    Rem,
+    /// Unsigned remainder. Remainder by zero results in the dividend.
+    Remu,
+
+    /// Order device I/O and memory accesses as viewed by other harts and devices.
+    /// Since this emulator executes a single hart in order, FENCE has no effect.
+    Fence,
+    /// Synchronize the instruction stream with preceding stores to instruction memory.
+    /// Instructions are fetched from the bus every cycle without a cache, so FENCE.I has no effect.
+    FenceI,
+
+    /// Atomic instructions of the A extension use the R-type format with funct5 in the upper bits of funct7.
+    /// The aq and rl bits specify the ordering constraints. The address held in rs1 must be naturally aligned.
+    /// Load reserved word. Loads a word from the address in rs1 and registers a reservation set.
+    LrW,
+    /// Store conditional word. Writes rs2 to the address in rs1 only if a valid reservation exists on it.
+    /// Writes zero to rd on success or nonzero on failure.
+    ScW,
+    /// AMOs atomically load a data value from the address in rs1, place the value into rd,
+    /// apply a binary operator to the loaded value and rs2, then store the result back to the address in rs1.
+    /// Atomic swap
+    AmoswapW,
+    /// Atomic add
+    AmoaddW,
+    /// Atomic XOR
+    AmoxorW,
+    /// Atomic AND
+    AmoandW,
+    /// Atomic OR
+    AmoorW,
+    /// Atomic signed minimum
+    AmominW,
+    /// Atomic signed maximum
+    AmomaxW,
+    /// Atomic unsigned minimum
+    AmominuW,
+    /// Atomic unsigned maximum
+    AmomaxuW,
+
+    /// Make a service request to the execution environment
+    Ecall,
+    /// Return control to a debugging environment
+    Ebreak,
+    /// Return from machine mode trap
+    Mret,
+    /// Return from supervisor mode trap
+    Sret,
+    /// Wait for interrupt. Stall the hart until an interrupt might need servicing.
+    Wfi,
+    /// Synchronize updates to in-memory page tables with the address translation cache
+    SfenceVma,
+
+    /// Atomic read/write csr
+    Csrrw,
+    /// Atomic read and set bits
+    Csrrs,
+    /// Read and clear
+    Csrrc,
+    /// Read and write immediate
+    Csrrwi,
+    /// Read and set immediate
+    Csrrsi,
+    /// Read and clear immediate
+    Csrrci,
+
+    /// Single-precision floating-point instructions of the F extension operate on the f registers.
+    /// Arithmetic instructions round with the rm field, or with frm if rm is DYN(0b111),
+    /// and accrue the exception flags into fflags. NaN results are the canonical NaN.
+    /// Load a single-precision value from memory into f[rd]. Uses the I-type format.
+    Flw,
+    /// Store f[rs2] into memory. Uses the S-type format.
+    Fsw,
+    /// Fused multiply-add instructions use the R4-type format with a third source register rs3.
+    /// f[rd] = f[rs1] × f[rs2] + f[rs3] with a single rounding.
+    FmaddS,
+    /// f[rd] = f[rs1] × f[rs2] - f[rs3]
+    FmsubS,
+    /// f[rd] = -(f[rs1] × f[rs2]) + f[rs3]
+    FnmsubS,
+    /// f[rd] = -(f[rs1] × f[rs2]) - f[rs3]
+    FnmaddS,
+    /// Add
+    FaddS,
+    /// Subtract f[rs2] from f[rs1]
+    FsubS,
+    /// Multiply
+    FmulS,
+    /// Divide f[rs1] by f[rs2]
+    FdivS,
+    /// Square root of f[rs1]
+    FsqrtS,
+    /// Sign injection. The result takes all bits except the sign bit from f[rs1].
+    /// The sign bit is the sign bit of f[rs2].
+    FsgnjS,
+    /// The sign bit is the opposite of the sign bit of f[rs2].
+    FsgnjnS,
+    /// The sign bit is the XOR of the sign bits of f[rs1] and f[rs2].
+    FsgnjxS,
+    /// Minimum. -0 is less than +0. If only one operand is NaN, the result is the other operand.
+    FminS,
+    /// Maximum. -0 is less than +0. If only one operand is NaN, the result is the other operand.
+    FmaxS,
+    /// Convert f[rs1] into a signed word in x[rd]. Out of range values saturate.
+    FcvtWS,
+    /// Convert f[rs1] into an unsigned word in x[rd]. Out of range values saturate.
+    FcvtWuS,
+    /// Move the bits of f[rs1] into x[rd].
+    FmvXW,
+    /// Write 1 to x[rd] if f[rs1] == f[rs2]. Only signaling NaNs raise invalid operation.
+    FeqS,
+    /// Write 1 to x[rd] if f[rs1] < f[rs2]. NaNs raise invalid operation.
+    FltS,
+    /// Write 1 to x[rd] if f[rs1] <= f[rs2]. NaNs raise invalid operation.
+    FleS,
+    /// Write a mask to x[rd] which indicates the class of f[rs1].
+    FclassS,
+    /// Convert the signed word in x[rs1] into f[rd].
+    FcvtSW,
+    /// Convert the unsigned word in x[rs1] into f[rd].
+    FcvtSWu,
+    /// Move the bits of x[rs1] into f[rd].
+    FmvWX,
+
+    /// Double-precision floating-point instructions of the D extension.
+    /// Single-precision values are NaN-boxed in the 64-bit f registers.
+    /// Load a double-precision value from memory into f[rd]. Uses the I-type format.
+    Fld,
+    /// Store f[rs2] into memory. Uses the S-type format.
+    Fsd,
+    /// f[rd] = f[rs1] × f[rs2] + f[rs3] with a single rounding.
+    FmaddD,
+    /// f[rd] = f[rs1] × f[rs2] - f[rs3]
+    FmsubD,
+    /// f[rd] = -(f[rs1] × f[rs2]) + f[rs3]
+    FnmsubD,
+    /// f[rd] = -(f[rs1] × f[rs2]) - f[rs3]
+    FnmaddD,
+    /// Add
+    FaddD,
+    /// Subtract f[rs2] from f[rs1]
+    FsubD,
+    /// Multiply
+    FmulD,
+    /// Divide f[rs1] by f[rs2]
+    FdivD,
+    /// Square root of f[rs1]
+    FsqrtD,
+    /// Sign injection. The sign bit is the sign bit of f[rs2].
+    FsgnjD,
+    /// The sign bit is the opposite of the sign bit of f[rs2].
+    FsgnjnD,
+    /// The sign bit is the XOR of the sign bits of f[rs1] and f[rs2].
+    FsgnjxD,
+    /// Minimum
+    FminD,
+    /// Maximum
+    FmaxD,
+    /// Convert the double-precision value in f[rs1] into single-precision.
+    FcvtSD,
+    /// Convert the single-precision value in f[rs1] into double-precision. It is always exact.
+    FcvtDS,
+    /// Write 1 to x[rd] if f[rs1] == f[rs2].
+    FeqD,
+    /// Write 1 to x[rd] if f[rs1] < f[rs2].
+    FltD,
+    /// Write 1 to x[rd] if f[rs1] <= f[rs2].
+    FleD,
+    /// Write a mask to x[rd] which indicates the class of f[rs1].
+    FclassD,
+    /// Convert f[rs1] into a signed word in x[rd]. Out of range values saturate.
+    FcvtWD,
+    /// Convert f[rs1] into an unsigned word in x[rd]. Out of range values saturate.
+    FcvtWuD,
+    /// Convert the signed word in x[rs1] into f[rd]. It is always exact.
+    FcvtDW,
+    /// Convert the unsigned word in x[rs1] into f[rd]. It is always exact.
+    FcvtDWu,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub op_code: OpCode,
+    ir: u32,
+    compressed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    U,
+    J,
+    I,
+    B,
+    S,
+    R,
+    R4,
+}
+
+pub type RegisterIdx = usize;
+
+impl Format {
+    /// Return instruction format of the op code.
+    pub fn of(op_code: OpCode) -> Self {
+        use Format::*;
+        use OpCode::*;
+        match op_code {
+            Lui | Auipc => U,
+            Jal => J,
+            Jalr => I,
+            Beq | Bne | Blt | Bltu | Bge | Bgeu => B,
+            Lb | Lh | Lw | Lbu | Lhu => I,
+            Sb | Sh | Sw => S,
+            Addi | Slti | Sltiu | Xori | Ori | Andi | Slli | Srli | Srai => I,
+            Add | Sub | Sll | Slt | Sltu | Xor | Srl | Sra | Or | And => R,
+            Mul | Mulh | Mulhsu | Mulhu | Div | Divu | Rem | Remu => R,
+            Fence | FenceI => I,
+            LrW | ScW | AmoswapW | AmoaddW | AmoxorW | AmoandW | AmoorW | AmominW | AmomaxW
+            | AmominuW | AmomaxuW => R,
+            Csrrw | Csrrs | Csrrc | Csrrwi | Csrrsi | Csrrci => I,
+            Ecall | Ebreak | Mret | Sret | Wfi => I,
+            SfenceVma => R,
+            Flw => I,
+            Fsw => S,
+            FmaddS | FmsubS | FnmsubS | FnmaddS => R4,
+            FaddS | FsubS | FmulS | FdivS | FsqrtS | FsgnjS | FsgnjnS | FsgnjxS | FminS | FmaxS
+            | FcvtWS | FcvtWuS | FmvXW | FeqS | FltS | FleS | FclassS | FcvtSW | FcvtSWu
+            | FmvWX => R,
+            Fld => I,
+            Fsd => S,
+            FmaddD | FmsubD | FnmsubD | FnmaddD => R4,
+            FaddD | FsubD | FmulD | FdivD | FsqrtD | FsgnjD | FsgnjnD | FsgnjxD | FminD | FmaxD
+            | FcvtSD | FcvtDS | FeqD | FltD | FleD | FclassD | FcvtWD | FcvtWuD | FcvtDW
+            | FcvtDWu => R,
+        }
+    }
+}
+
+impl Instruction {
+    pub fn format(&self) -> Format {
+        Format::of(self.op_code)
+    }
+
+    /// Immediate as raw 32-bit value. U-type immediates keep the low 12 bits zero.
+    /// R-type and R4-type instructions have no immediate and return 0.
+    pub fn imm(&self) -> u32 {
+        self.imm_signed() as u32
+    }
+
+    /// Sign-extended immediate.
+    /// R-type and R4-type instructions have no immediate and return 0.
+    pub fn imm_signed(&self) -> i32 {
+        match self.format() {
+            Format::U => (self.ir & 0xffff_f000) as i32,
+            Format::J => {
+                let imm = ((self.ir & 0x80000000) >> 11)
+                    | ((self.ir & 0x7fe00000) >> 20)
+                    | ((self.ir & 0x00100000) >> 9)
+                    | (self.ir & 0x000ff000);
+
+                let imm = if imm & 0x0010_0000 != 0 {
+                    imm | 0xffe0_0000
+                } else {
+                    imm
+                };
+                imm as i32
+            }
+            Format::I => {
+                let imm = self.ir >> 20;
+                let imm = if imm & 0x800 != 0 {
+                    imm | 0xfffff000
+                } else {
+                    imm
+                };
+                imm as i32
+            }
+            Format::B => {
+                let imm = ((self.ir & 0x80000000) >> 19)
+                    | ((self.ir & 0x7e000000) >> 20)
+                    | ((self.ir & 0x00000f00) >> 7)
+                    | ((self.ir & 0x00000080) << 4);
+                let imm = if imm & 0x1000 != 0 {
+                    imm | 0xffffe000
+                } else {
+                    imm
+                };
+                imm as i32
+            }
+            Format::S => {
+                // imm[11:5] = inst[31:25], imm[4:0] = inst[11:7]
+                let imm = ((self.ir >> 20) & 0xfe0) | ((self.ir >> 7) & 0x1f);
+                let imm = if imm & 0x800 != 0 {
+                    imm | 0xfffff000
+                } else {
+                    imm
+                };
+                imm as i32
+            }
+            Format::R | Format::R4 => 0,
+        }
+    }
+
+    /// Raw instruction bits. Compressed instructions are expanded into the 32-bit form.
+    pub fn raw(&self) -> u32 {
+        self.ir
+    }
+
+    /// True if decoded from a 16-bit compressed instruction.
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// Instruction length in bytes.
+    pub fn size(&self) -> u32 {
+        if self.compressed {
+            2
+        } else {
+            4
+        }
+    }
+
+    pub fn rd(&self) -> RegisterIdx {
+        let r = (self.ir >> 7) & 0x1f;
+        r as usize
+    }
+
+    pub fn rs1(&self) -> usize {
+        let r = (self.ir >> 15) & 0x1f;
+        r as usize
+    }
+
+    pub fn rs2(&self) -> usize {
+        let r = (self.ir >> 20) & 0x1f;
+        r as usize
+    }
+
+    /// Third source register of R4-type instructions
+    pub fn rs3(&self) -> RegisterIdx {
+        (self.ir >> 27) as usize
+    }
+
+    pub fn funct3(&self) -> u32 {
+        (self.ir >> 12) & 0x07
+    }
+
+    /// Rounding mode of floating-point instructions. It is placed in funct3.
+    pub fn rm(&self) -> u32 {
+        self.funct3()
+    }
+
+    pub fn funct7(&self) -> u32 {
+        self.ir >> 25
+    }
+
+    /// Acquire bit for atomic instructions
+    pub fn aq(&self) -> bool {
+        self.ir & (1 << 26) != 0
+    }
+
+    /// Release bit for atomic instructions
+    pub fn rl(&self) -> bool {
+        self.ir & (1 << 25) != 0
+    }
+
+    /// Shift amount for shift by constant instructions(SLLI, SRLI, SRAI)
+    pub fn shamt(&self) -> u32 {
+        (self.ir >> 20) & 0x1f
+    }
+
+    /// for CSR Instructions
+    pub fn csr(&self) -> usize {
+        // make sure instruction is csr
+        debug_assert_eq!(self.ir & 0x7f, 0b1110011);
+        let r = self.ir >> 20;
+        r as usize
+    }
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    #[error("invalid instruction {ir:#010x}")]
+    InvalidOpCode { ir: u32 },
+}
+
+#[derive(Debug)]
+pub struct Decoder {}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Decode instruction. If the lowest two bits mark a compressed instruction,
+    /// only the lower 16 bits are decoded.
+    pub fn try_decode(&self, instruction: u32) -> Result<Instruction, DecodeError> {
+        if compressed::is_compressed(instruction) {
+            let ir = compressed::expand(instruction as u16)?;
+            return Ok(Instruction {
+                op_code: self.op_code(ir)?,
+                ir,
+                compressed: true,
+            });
+        }
+        Ok(Instruction {
+            op_code: self.op_code(instruction)?,
+            ir: instruction,
+            compressed: false,
+        })
+    }
+
+    fn op_code(&self, instruction: u32) -> Result<OpCode, DecodeError> {
+        use OpCode::*;
+        let rs2 = (instruction >> 20) & 0x1f;
+        // Rounding modes 101 and 110 are reserved.
+        let valid_rm = !matches!((instruction >> 12) & 0x07, 0b101 | 0b110);
+        // Volume I: RISC-V Unprivileged ISA V20191213 P130
+        let op_code = match instruction & 0x7f {
+            0b0110111 => Lui,
+            0b0010111 => Auipc,
+            0b1101111 => Jal,
+            0b1100111 => Jalr,
+            0b1100011 => match (instruction >> 12) & 0x07 {
+                0b000 => Beq,
+                0b001 => Bne,
+                0b100 => Blt,
+                0b101 => Bge,
+                0b110 => Bltu,
+                0b111 => Bgeu,
+                _ => return Err(DecodeError::InvalidOpCode { ir: instruction }),
+            },
+            0b0000011 => match (instruction >> 12) & 0x07 {
+                0b000 => Lb,
+                0b001 => Lh,
+                0b010 => Lw,
+                0b100 => Lbu,
+                0b101 => Lhu,
+                _ => return Err(DecodeError::InvalidOpCode { ir: instruction }),
+            },
+            0b0100011 => match (instruction >> 12) & 0x07 {
+                0b000 => Sb,
+                0b001 => Sh,
+                0b010 => Sw,
+                _ => return Err(DecodeError::InvalidOpCode { ir: instruction }),
+            },
+            0b0010011 => match ((instruction >> 12) & 0x07, instruction >> 25) {
+                (0b000, _) => Addi,
+                (0b010, _) => Slti,
+                (0b011, _) => Sltiu,
+                (0b100, _) => Xori,
+                (0b110, _) => Ori,
+                (0b111, _) => Andi,
+                (0b001, 0b0000000) => Slli,
+                (0b101, 0b0000000) => Srli,
+                (0b101, 0b0100000) => Srai,
+                _ => return Err(DecodeError::InvalidOpCode { ir: instruction }),
+            },
+            0b0110011 => match ((instruction >> 12) & 0x07, instruction >> 25) {
+                (0b000, 0b0000000) => Add,
+                (0b000, 0b0100000) => Sub,
+                (0b001, 0b0000000) => Sll,
+                (0b010, 0b0000000) => Slt,
+                (0b011, 0b0000000) => Sltu,
+                (0b100, 0b0000000) => Xor,
+                (0b101, 0b0000000) => Srl,
+                (0b101, 0b0100000) => Sra,
+                (0b110, 0b0000000) => Or,
+                (0b111, 0b0000000) => And,
+                (0b000, 0b0000001) => Mul,
+                (0b001, 0b0000001) => Mulh,
+                (0b010, 0b0000001) => Mulhsu,
+                (0b011, 0b0000001) => Mulhu,
+                (0b100, 0b0000001) => Div,
+                (0b101, 0b0000001) => Divu,
+                (0b110, 0b0000001) => Rem,
+                (0b111, 0b0000001) => Remu,
+                _ => return Err(DecodeError::InvalidOpCode { ir: instruction }),
+            },
+            0b0001111 => match (instruction >> 12) & 0x07 {
+                0b000 => Fence,
+                0b001 => FenceI,
+                _ => return Err(DecodeError::InvalidOpCode { ir: instruction }),
+            },
+            0b0101111 => match ((instruction >> 12) & 0x07, instruction >> 27) {
+                (0b010, 0b00010) if rs2 == 0 => LrW,
+                (0b010, 0b00011) => ScW,
+                (0b010, 0b00001) => AmoswapW,
+                (0b010, 0b00000) => AmoaddW,
+                (0b010, 0b00100) => AmoxorW,
+                (0b010, 0b01100) => AmoandW,
+                (0b010, 0b01000) => AmoorW,
+                (0b010, 0b10000) => AmominW,
+                (0b010, 0b10100) => AmomaxW,
+                (0b010, 0b11000) => AmominuW,
+                (0b010, 0b11100) => AmomaxuW,
+                _ => return Err(DecodeError::InvalidOpCode { ir: instruction }),
+            },
+            0b1110011 => match (instruction >> 12) & 0x07 {
+                0b000 => match instruction {
+                    0x0000_0073 => Ecall,
+                    0x0010_0073 => Ebreak,
+                    0x3020_0073 => Mret,
+                    0x1020_0073 => Sret,
+                    0x1050_0073 => Wfi,
+                    _ if instruction >> 25 == 0b0001001 && (instruction >> 7) & 0x1f == 0 => {
+                        SfenceVma
+                    }
+                    _ => return Err(DecodeError::InvalidOpCode { ir: instruction }),
+                },
+                0b001 => Csrrw,
+                0b010 => Csrrs,
+                0b011 => Csrrc,
+                0b101 => Csrrwi,
+                0b110 => Csrrsi,
+                0b111 => Csrrci,
+                _ => return Err(DecodeError::InvalidOpCode { ir: instruction }),
+            },
+            0b0000111 => match (instruction >> 12) & 0x07 {
+                0b010 => Flw,
+                0b011 => Fld,
+                _ => return Err(DecodeError::InvalidOpCode { ir: instruction }),
+            },
+            0b0100111 => match (instruction >> 12) & 0x07 {
+                0b010 => Fsw,
+                0b011 => Fsd,
+                _ => return Err(DecodeError::InvalidOpCode { ir: instruction }),
+            },
+            // fmt(bits 26:25) is 00 for single-precision and 01 for double-precision.
+            0b1000011 | 0b1000111 | 0b1001011 | 0b1001111 if valid_rm => {
+                match (instruction & 0x7f, (instruction >> 25) & 0b11) {
+                    (0b1000011, 0b00) => FmaddS,
+                    (0b1000111, 0b00) => FmsubS,
+                    (0b1001011, 0b00) => FnmsubS,
+                    (0b1001111, 0b00) => FnmaddS,
+                    (0b1000011, 0b01) => FmaddD,
+                    (0b1000111, 0b01) => FmsubD,
+                    (0b1001011, 0b01) => FnmsubD,
+                    (0b1001111, 0b01) => FnmaddD,
+                    _ => return Err(DecodeError::InvalidOpCode { ir: instruction }),
+                }
+            }
+            0b1010011 => match (instruction >> 25, (instruction >> 12) & 0x07, rs2) {
+                (0b0000000, _, _) if valid_rm => FaddS,
+                (0b0000100, _, _) if valid_rm => FsubS,
+                (0b0001000, _, _) if valid_rm => FmulS,
+                (0b0001100, _, _) if valid_rm => FdivS,
+                (0b0101100, _, 0) if valid_rm => FsqrtS,
+                (0b0010000, 0b000, _) => FsgnjS,
+                (0b0010000, 0b001, _) => FsgnjnS,
+                (0b0010000, 0b010, _) => FsgnjxS,
+                (0b0010100, 0b000, _) => FminS,
+                (0b0010100, 0b001, _) => FmaxS,
+                (0b1100000, _, 0) if valid_rm => FcvtWS,
+                (0b1100000, _, 1) if valid_rm => FcvtWuS,
+                (0b1110000, 0b000, 0) => FmvXW,
+                (0b1010000, 0b010, _) => FeqS,
+                (0b1010000, 0b001, _) => FltS,
+                (0b1010000, 0b000, _) => FleS,
+                (0b1110000, 0b001, 0) => FclassS,
+                (0b1101000, _, 0) if valid_rm => FcvtSW,
+                (0b1101000, _, 1) if valid_rm => FcvtSWu,
+                (0b1111000, 0b000, 0) => FmvWX,
+                (0b0000001, _, _) if valid_rm => FaddD,
+                (0b0000101, _, _) if valid_rm => FsubD,
+                (0b0001001, _, _) if valid_rm => FmulD,
+                (0b0001101, _, _) if valid_rm => FdivD,
+                (0b0101101, _, 0) if valid_rm => FsqrtD,
+                (0b0010001, 0b000, _) => FsgnjD,
+                (0b0010001, 0b001, _) => FsgnjnD,
+                (0b0010001, 0b010, _) => FsgnjxD,
+                (0b0010101, 0b000, _) => FminD,
+                (0b0010101, 0b001, _) => FmaxD,
+                (0b0100000, _, 1) if valid_rm => FcvtSD,
+                (0b0100001, _, 0) if valid_rm => FcvtDS,
+                (0b1010001, 0b010, _) => FeqD,
+                (0b1010001, 0b001, _) => FltD,
+                (0b1010001, 0b000, _) => FleD,
+                (0b1110001, 0b001, 0) => FclassD,
+                (0b1100001, _, 0) if valid_rm => FcvtWD,
+                (0b1100001, _, 1) if valid_rm => FcvtWuD,
+                (0b1101001, _, 0) if valid_rm => FcvtDW,
+                (0b1101001, _, 1) if valid_rm => FcvtDWu,
+                _ => return Err(DecodeError::InvalidOpCode { ir: instruction }),
+            },
+
+            _ => return Err(DecodeError::InvalidOpCode { ir: instruction }),
+        };
+        Ok(op_code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_store() {
+        let d = Decoder::new();
+        // sb x2, 0(x1)
+        assert_eq!(
+            d.try_decode((0b10 << 20) | (0b01 << 15) | 0b0100011)
+                .unwrap()
+                .op_code,
+            OpCode::Sb
+        );
+        // sh x2, 0(x1)
+        assert_eq!(
+            d.try_decode((0b10 << 20) | (0b01 << 15) | (0b001 << 12) | 0b0100011)
+                .unwrap()
+                .op_code,
+            OpCode::Sh
+        );
+        // sw x2, 0(x1)
+        assert_eq!(
+            d.try_decode((0b10 << 20) | (0b01 << 15) | (0b010 << 12) | 0b0100011)
+                .unwrap()
+                .op_code,
+            OpCode::Sw
+        );
+        assert!(d.try_decode((0b011 << 12) | 0b0100011).is_err());
+    }
+
+    #[test]
+    fn decode_fence() {
+        let d = Decoder::new();
+        // fence rw, rw
+        assert_eq!(d.try_decode(0x0330_000f).unwrap().op_code, OpCode::Fence);
+        // fence.i
+        assert_eq!(d.try_decode(0x0000_100f).unwrap().op_code, OpCode::FenceI);
+        assert!(d.try_decode(0x0000_200f).is_err());
+    }
+
+    #[test]
+    fn decode_atomic_ordering_bits() {
+        let d = Decoder::new();
+        // amoswap.w.aqrl x3, x2, (x1)
+        let ir = d
+            .try_decode(
+                (0b00001 << 27)
+                    | (0b11 << 25)
+                    | (0b10 << 20)
+                    | (0b01 << 15)
+                    | (0b010 << 12)
+                    | (0b11 << 7)
+                    | 0b0101111,
+            )
+            .unwrap();
+        assert_eq!(ir.op_code, OpCode::AmoswapW);
+        assert!(ir.aq());
+        assert!(ir.rl());
+        // lr.w.aq x3, (x1)
+        let ir = d
+            .try_decode(
+                (0b00010 << 27)
+                    | (0b10 << 25)
+                    | (0b01 << 15)
+                    | (0b010 << 12)
+                    | (0b11 << 7)
+                    | 0b0101111,
+            )
+            .unwrap();
+        assert_eq!(ir.op_code, OpCode::LrW);
+        assert!(ir.aq());
+        assert!(!ir.rl());
+        // lr.w with non-zero rs2 is reserved
+        assert!(d
+            .try_decode((0b00010 << 27) | (0b10 << 20) | (0b01 << 15) | (0b010 << 12) | 0b0101111)
+            .is_err());
+    }
+
+    #[test]
+    fn s_type_immediate() {
+        let d = Decoder::new();
+        // sw x31, 2047(x1)
+        let ir = d
+            .try_decode(
+                (0x3f << 25) | (31 << 20) | (0b01 << 15) | (0b010 << 12) | (0x1f << 7) | 0b0100011,
+            )
+            .unwrap();
+        assert_eq!(ir.imm_signed(), 2047);
+        assert_eq!(ir.rs2(), 31);
+
+        // sw x31, -2048(x1)
+        let ir = d
+            .try_decode((0x40 << 25) | (31 << 20) | (0b01 << 15) | (0b010 << 12) | 0b0100011)
+            .unwrap();
+        assert_eq!(ir.imm_signed(), -2048);
+
+        // sw x2, -1(x1)
+        let ir = d
+            .try_decode(
+                (0x7f << 25)
+                    | (0b10 << 20)
+                    | (0b01 << 15)
+                    | (0b010 << 12)
+                    | (0x1f << 7)
+                    | 0b0100011,
+            )
+            .unwrap();
+        assert_eq!(ir.imm_signed(), -1);
+    }
+
+    #[test]
+    fn decode_floating_point() {
+        let d = Decoder::new();
+        // fmadd.s fa0, fa1, fa2, fa3, rtz
+        let ir = d.try_decode(0x68c5_9543).unwrap();
+        assert_eq!(ir.op_code, OpCode::FmaddS);
+        assert_eq!(ir.format(), Format::R4);
+        assert_eq!((ir.rd(), ir.rs1(), ir.rs2(), ir.rs3()), (10, 11, 12, 13));
+        assert_eq!(ir.rm(), 0b001);
+        // fmadd.d fa0, fa1, fa2, fa3, rtz
+        assert_eq!(d.try_decode(0x6ac5_9543).unwrap().op_code, OpCode::FmaddD);
+        // fmadd.h is not supported
+        assert!(d.try_decode(0x6cc5_9543).is_err());
+        // fadd.s fa0, fa1, fa2 with reserved rounding modes
+        assert_eq!(d.try_decode(0x00c5_f553).unwrap().op_code, OpCode::FaddS);
+        assert!(d.try_decode(0x00c5_d553).is_err());
+        assert!(d.try_decode(0x00c5_e553).is_err());
+        // fcvt.wu.s a0, fa1, rtz
+        assert_eq!(d.try_decode(0xc015_9553).unwrap().op_code, OpCode::FcvtWuS);
+        // fsqrt.s with non-zero rs2 is reserved
+        assert!(d.try_decode(0x5815_f553).is_err());
+        // flw fa0, -4(a1)
+        let ir = d.try_decode(0xffc5_a507).unwrap();
+        assert_eq!(ir.op_code, OpCode::Flw);
+        assert_eq!(ir.imm_signed(), -4);
+        // fcvt.s.d fa0, fa1 and fcvt.d.s fa0, fa1
+        assert_eq!(d.try_decode(0x4015_f553).unwrap().op_code, OpCode::FcvtSD);
+        assert_eq!(d.try_decode(0x4205_8553).unwrap().op_code, OpCode::FcvtDS);
+        // fsd fa0, 8(sp)
+        assert_eq!(d.try_decode(0x00a1_3427).unwrap().op_code, OpCode::Fsd);
+    }
+
+    #[test]
+    fn b_type_immediate() {
+        let d = Decoder::new();
+        // beq x1, x2, 8
+        assert_eq!(d.try_decode(0x0020_8463).unwrap().imm_signed(), 8);
+        // bne x1, x2, -2
+        assert_eq!(d.try_decode(0xfe20_9fe3).unwrap().imm_signed(), -2);
+        // blt x1, x2, 4094
+        assert_eq!(d.try_decode(0x7e20_cfe3).unwrap().imm_signed(), 4094);
+        // bge x1, x2, -4096
+        assert_eq!(d.try_decode(0x8020_d063).unwrap().imm_signed(), -4096);
+    }
+
+    #[test]
+    fn immediate_of_every_format() {
+        let d = Decoder::new();
+        // lui x1, 0xfffff
+        let ir = d.try_decode(0xffff_f0b7).unwrap();
+        assert_eq!(ir.imm(), 0xffff_f000);
+        assert_eq!(ir.imm_signed(), -4096);
+        // addi x1, x1, -1
+        assert_eq!(d.try_decode(0xfff0_8093).unwrap().imm(), 0xffff_ffff);
+        // add x1, x2, x3
+        let ir = d.try_decode(0x0031_00b3).unwrap();
+        assert_eq!((ir.imm(), ir.imm_signed()), (0, 0));
+        // fmadd.s fa0, fa1, fa2, fa3, rtz
+        let ir = d.try_decode(0x68c5_9543).unwrap();
+        assert_eq!((ir.imm(), ir.imm_signed()), (0, 0));
+    }
+}
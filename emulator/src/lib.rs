@@ -1,5 +1,6 @@
 #![allow(clippy::new_without_default)]
 pub mod bus;
-mod cpu;
-mod instructions;
+pub mod cpu;
+pub mod elf;
+pub mod instructions;
 pub mod runtime;
@@ -1,7 +1,10 @@
+use std::time::{Duration, Instant};
+
 use thiserror::Error;
 
+pub use crate::cpu::Stats;
 use crate::{
-    bus::interface::{BusRead, BusWrite},
+    bus::interface::{BusRead, BusWrite, HaltRequest, InterruptSource, PowerControl},
     cpu::Cpu,
 };
 
@@ -11,32 +14,389 @@ pub enum RuntimeError {
     Internal { message: String },
 }
 
+/// Reason why the run loop stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// Retired instructions reached the limit.
+    InstructionLimit,
+    /// Executed cycles reached the limit.
+    CycleLimit,
+    /// Wall-clock budget is exhausted.
+    Timeout,
+    /// Guest powered off the machine.
+    PowerOff,
+    /// Guest requested exit with a code.
+    Exit,
+    /// pc reached a breakpoint. The instruction at pc is not executed.
+    Breakpoint { pc: u32 },
+}
+
+/// Result of `Runtime::run`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunOutcome {
+    pub reason: StopReason,
+    /// Exit code requested by the guest. Power off is reported as 0.
+    pub exit_code: Option<u32>,
+    pub stats: Stats,
+}
+
+/// State of the hart passed to the tracer before each cycle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceRecord {
+    pub cycle: u64,
+    pub pc: u32,
+    /// Instruction word at pc. None if it can not be fetched.
+    pub ir: Option<u32>,
+}
+
+type Tracer = Box<dyn FnMut(&TraceRecord)>;
+
 /// Runtime represents emulator runtime environment.
-pub struct Runtime {}
+pub struct Runtime {
+    /// Initial program counter.
+    entry: u32,
+    max_instructions: Option<u64>,
+    max_cycles: Option<u64>,
+    timeout: Option<Duration>,
+    breakpoints: Vec<u32>,
+    tohost: Option<u32>,
+    hardware_ad_update: bool,
+    tracer: Option<Tracer>,
+}
 
 impl Runtime {
+    /// Number of cycles between wall-clock checks.
+    const TIMEOUT_CHECK_INTERVAL: u64 = 4096;
+
     /// Construct `Runtime`
     pub fn new() -> Self {
-        Self {}
+        Self {
+            entry: 0,
+            max_instructions: None,
+            max_cycles: None,
+            timeout: None,
+            breakpoints: Vec::new(),
+            tohost: None,
+            hardware_ad_update: false,
+            tracer: None,
+        }
+    }
+
+    /// Start execution at entry instead of address 0. Typically `Elf::entry`.
+    pub fn entry(mut self, entry: u32) -> Self {
+        self.entry = entry;
+        self
+    }
+
+    /// Stop after n instructions are retired.
+    pub fn max_instructions(mut self, n: u64) -> Self {
+        self.max_instructions = Some(n);
+        self
+    }
+
+    /// Stop after n cycles. Cycles spent taking traps or waiting in WFI do not retire
+    /// instructions, so use this limit to bound guests that may hang.
+    pub fn max_cycles(mut self, n: u64) -> Self {
+        self.max_cycles = Some(n);
+        self
+    }
+
+    /// Stop when the run loop takes longer than timeout.
+    /// The budget is checked periodically, so the loop may slightly overrun it.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Stop before the instruction at pc is executed.
+    pub fn breakpoint(mut self, pc: u32) -> Self {
+        self.breakpoints.push(pc);
+        self
+    }
+
+    /// Watch the HTIF `tohost` word at addr, typically `Elf::symbol("tohost")`.
+    /// Writing `code << 1 | 1` stops the run loop with `StopReason::Exit` and the code.
+    /// Other values are syscall requests of the proxy kernel and are ignored.
+    pub fn tohost(mut self, addr: u32) -> Self {
+        self.tohost = Some(addr);
+        self
+    }
+
+    /// Set A and D bits of page table entries on access instead of raising page faults.
+    pub fn hardware_ad_update(mut self, enabled: bool) -> Self {
+        self.hardware_ad_update = enabled;
+        self
+    }
+
+    /// Call tracer before each cycle.
+    pub fn trace(mut self, tracer: impl FnMut(&TraceRecord) + 'static) -> Self {
+        self.tracer = Some(Box::new(tracer));
+        self
     }
 
     /// Entrypoint to run emulator.
-    pub fn run<B>(self, bus: B) -> Result<(), RuntimeError>
+    /// Run until one of the configured stop conditions is met or the guest halts the machine.
+    /// Pass `&mut bus` to inspect memory after the run.
+    pub fn run<B>(mut self, bus: B) -> Result<RunOutcome, RuntimeError>
     where
-        B: BusRead + BusWrite,
+        B: BusRead + BusWrite + InterruptSource + PowerControl,
     {
         let mut cpu = Cpu::new(bus);
+        cpu.set_pc(self.entry);
+        cpu.set_hardware_ad_update(self.hardware_ad_update);
+        let start = Instant::now();
+
+        let (reason, exit_code) = loop {
+            if let Some(request) = cpu.bus().halt_request() {
+                break match request {
+                    HaltRequest::PowerOff => (StopReason::PowerOff, Some(0)),
+                    HaltRequest::Exit(code) => (StopReason::Exit, Some(code)),
+                };
+            }
+            if let Some(addr) = self.tohost {
+                if let Ok(value) = cpu.bus().read32(addr) {
+                    if value & 1 == 1 {
+                        break (StopReason::Exit, Some(value >> 1));
+                    }
+                }
+            }
+            if self
+                .max_instructions
+                .map_or(false, |n| cpu.state().instructions_retired >= n)
+            {
+                break (StopReason::InstructionLimit, None);
+            }
+            if self
+                .max_cycles
+                .map_or(false, |n| cpu.state().cycle_counter >= n)
+            {
+                break (StopReason::CycleLimit, None);
+            }
+            if self.breakpoints.contains(&cpu.pc()) {
+                break (StopReason::Breakpoint { pc: cpu.pc() }, None);
+            }
+            if let Some(timeout) = self.timeout {
+                if cpu.state().cycle_counter % Self::TIMEOUT_CHECK_INTERVAL == 0
+                    && start.elapsed() >= timeout
+                {
+                    break (StopReason::Timeout, None);
+                }
+            }
+
+            if let Some(tracer) = self.tracer.as_mut() {
+                tracer(&TraceRecord {
+                    cycle: cpu.state().cycle_counter,
+                    pc: cpu.pc(),
+                    ir: cpu.peek_instruction(),
+                });
+            }
 
-        loop {
             if let Err(err) = cpu.cycle() {
                 return Err(RuntimeError::Internal {
                     message: format!("{err:#?}"),
                 });
             }
-            _ = cpu.state();
-        }
+        };
+
+        Ok(RunOutcome {
+            reason,
+            exit_code,
+            stats: cpu.state().clone(),
+        })
     }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::{
+        bus::{syscon::Syscon, Bus},
+        instructions::{asm, encode, OpCode},
+    };
+
+    /// Assemble src at 0 into RAM next to syscon.
+    fn bus_with_asm(src: &str) -> Bus {
+        let mut bus = Bus::new(asm::assemble(src, 0).unwrap().bytes);
+        bus.map(Syscon::BASE, Syscon::new()).unwrap();
+        bus
+    }
+
+    #[test]
+    fn power_off() {
+        let bus = bus_with_asm(
+            "
+                    li x1, 0x100000     # syscon
+                    li x2, 0x5555       # poweroff
+                    sw x2, 0(x1)
+            loop:   j loop
+            ",
+        );
+        let outcome = Runtime::new().run(bus).unwrap();
+        assert_eq!(outcome.reason, StopReason::PowerOff);
+        assert_eq!(outcome.exit_code, Some(0));
+        assert_eq!(outcome.stats.instructions_retired, 4);
+    }
+
+    #[test]
+    fn exit_code() {
+        let bus = bus_with_asm(
+            "
+                    li x1, 0x100000     # syscon
+                    li x2, 0x2a3333     # exit code 42
+                    sw x2, 0(x1)
+            loop:   j loop
+            ",
+        );
+        let outcome = Runtime::new().run(bus).unwrap();
+        assert_eq!(outcome.reason, StopReason::Exit);
+        assert_eq!(outcome.exit_code, Some(42));
+    }
+
+    #[test]
+    fn tohost() {
+        let program = |code: u32| {
+            asm::assemble(
+                &format!(
+                    "
+                            li t0, {}
+                            la t1, tohost
+                            sw t0, 0(t1)
+                    loop:   j loop
+                    tohost: .word 0
+                    ",
+                    code << 1 | 1
+                ),
+                0,
+            )
+            .unwrap()
+        };
+
+        let pass = program(0);
+        let tohost = pass.label("tohost").unwrap();
+        let outcome = Runtime::new()
+            .tohost(tohost)
+            .max_instructions(100)
+            .run(Bus::new(pass.bytes))
+            .unwrap();
+        assert_eq!(outcome.reason, StopReason::Exit);
+        assert_eq!(outcome.exit_code, Some(0));
+
+        // riscv-tests report failing test number 3.
+        let fail = program(3);
+        let outcome = Runtime::new()
+            .tohost(tohost)
+            .max_instructions(100)
+            .run(Bus::new(fail.bytes))
+            .unwrap();
+        assert_eq!(outcome.exit_code, Some(3));
+    }
+
+    #[test]
+    fn borrowed_bus() {
+        let program = asm::assemble(
+            "
+                    li t0, 42
+                    sw t0, 0x100(zero)
+            loop:   j loop
+            .zero 0x100
+            ",
+            0,
+        )
+        .unwrap();
+        let mut bus = Bus::new(program.bytes);
+        Runtime::new().max_instructions(10).run(&mut bus).unwrap();
+        assert_eq!(bus.read32(0x100).unwrap(), 42);
+    }
+
+    #[test]
+    fn instruction_limit() {
+        let bus = bus_with_asm("loop: j loop");
+        let outcome = Runtime::new().max_instructions(10).run(bus).unwrap();
+        assert_eq!(outcome.reason, StopReason::InstructionLimit);
+        assert_eq!(outcome.exit_code, None);
+        assert_eq!(outcome.stats.instructions_retired, 10);
+        assert_eq!(outcome.stats.cycle_counter, 10);
+    }
+
+    #[test]
+    fn cycle_limit() {
+        // Illegal instruction traps to mtvec(0) forever without retiring instructions.
+        let bus = bus_with_asm(".word 0");
+        let outcome = Runtime::new()
+            .max_instructions(1)
+            .max_cycles(10)
+            .run(bus)
+            .unwrap();
+        assert_eq!(outcome.reason, StopReason::CycleLimit);
+        assert_eq!(outcome.stats.instructions_retired, 0);
+        assert_eq!(outcome.stats.cycle_counter, 10);
+    }
+
+    #[test]
+    fn breakpoint() {
+        let bus = bus_with_asm(
+            "
+                    nop
+                    nop
+            loop:   addi x3, x3, 1
+                    j loop
+            ",
+        );
+        let outcome = Runtime::new().entry(4).breakpoint(0xc).run(bus).unwrap();
+        assert_eq!(outcome.reason, StopReason::Breakpoint { pc: 0xc });
+        assert_eq!(outcome.stats.instructions_retired, 2);
+    }
+
+    #[test]
+    fn trace() {
+        let bus = bus_with_asm(
+            "
+                    nop
+            loop:   j loop
+            ",
+        );
+        let nop = encode::i(OpCode::Addi, 0, 0, 0).unwrap();
+        let jump = encode::j(0, 0).unwrap();
+        let records = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let outcome = Runtime::new()
+            .max_instructions(3)
+            .trace({
+                let records = records.clone();
+                move |record| records.borrow_mut().push(*record)
+            })
+            .run(bus)
+            .unwrap();
+        assert_eq!(outcome.reason, StopReason::InstructionLimit);
+        assert_eq!(
+            *records.borrow(),
+            vec![
+                TraceRecord {
+                    cycle: 0,
+                    pc: 0,
+                    ir: Some(nop)
+                },
+                TraceRecord {
+                    cycle: 1,
+                    pc: 4,
+                    ir: Some(jump)
+                },
+                TraceRecord {
+                    cycle: 2,
+                    pc: 4,
+                    ir: Some(jump)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn timeout() {
+        let bus = bus_with_asm("loop: j loop");
+        let outcome = Runtime::new()
+            .timeout(Duration::from_millis(10))
+            .run(bus)
+            .unwrap();
+        assert_eq!(outcome.reason, StopReason::Timeout);
+        assert!(outcome.stats.cycle_counter > 0);
+    }
+}
@@ -0,0 +1,45 @@
+#!/bin/sh
+# Build the riscv-tests fixtures from isa/<suite>/*.S into <suite>/<test>.
+#
+# Requires a C preprocessor, llvm-mc and ld.lld. Override them with CPP, LLVM_MC and LD,
+# e.g. LD="rust-lld -flavor gnu".
+#
+# Usage: ./build.sh [SUITE...]
+
+set -eu
+
+cd "$(dirname "$0")"
+
+CPP=${CPP:-cpp}
+LLVM_MC=${LLVM_MC:-llvm-mc}
+LD=${LD:-ld.lld}
+
+# Extensions needed to assemble each suite.
+mattr() {
+  case "$1" in
+    rv32um) echo "+m" ;;
+    rv32ua) echo "+a" ;;
+    rv32uf) echo "+f" ;;
+    rv32ud) echo "+f,+d" ;;
+    rv32uc) echo "+c,+f,+d" ;;
+    rv32mi | rv32si) echo "+m,+a" ;;
+    *) echo "" ;;
+  esac
+}
+
+suites=${*:-$(ls isa)}
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+
+for suite in $suites; do
+  mkdir -p "$suite"
+  for src in isa/"$suite"/*.S; do
+    name=$(basename "$src" .S)
+    out="$suite/$name"
+    $CPP -P -nostdinc -undef -x assembler-with-cpp -I env "$src" > "$tmp/$name.s"
+    $LLVM_MC -triple=riscv32 -mattr="$(mattr "$suite")",-relax -filetype=obj \
+      -o "$tmp/$name.o" "$tmp/$name.s"
+    $LD -m elf32lriscv -static -nostdlib -T env/link.ld -o "$out" "$tmp/$name.o"
+    echo "$out"
+  done
+done
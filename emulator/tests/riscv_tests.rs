@@ -0,0 +1,148 @@
+//! Run the riscv-tests ISA suites checked in under `tests/riscv-tests/<suite>/`.
+//! See `tests/riscv-tests/README.md` for how to build the fixtures.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use emulator::{
+    bus::{clint::Clint, Bus},
+    elf::Elf,
+    runtime::{Runtime, StopReason},
+};
+
+/// Tests finish in a few thousand cycles. The limit catches hangs.
+const MAX_CYCLES: u64 = 1_000_000;
+const RAM_SIZE: u32 = 1 << 20;
+
+enum TestResult {
+    Pass,
+    /// Failing test case number reported through tohost.
+    Fail(u32),
+    /// Stopped without writing tohost.
+    Stopped(StopReason),
+    Error(String),
+}
+
+impl fmt::Display for TestResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestResult::Pass => write!(f, "ok"),
+            TestResult::Fail(n) => write!(f, "FAILED at test #{n}"),
+            TestResult::Stopped(reason) => write!(f, "FAILED stopped by {reason:?}"),
+            TestResult::Error(message) => write!(f, "ERROR {message}"),
+        }
+    }
+}
+
+fn run(path: &Path) -> TestResult {
+    let image = match fs::read(path) {
+        Ok(image) => image,
+        Err(err) => return TestResult::Error(err.to_string()),
+    };
+    let elf = match Elf::parse(&image) {
+        Ok(elf) => elf,
+        Err(err) => return TestResult::Error(err.to_string()),
+    };
+    let Some(tohost) = elf.symbol("tohost") else {
+        return TestResult::Error("tohost symbol not found".to_owned());
+    };
+
+    let mut bus = Bus::empty();
+    bus.map(Clint::BASE, Clint::new()).unwrap();
+    bus.map_ram(Bus::DRAM_BASE, RAM_SIZE).unwrap();
+    if let Err(err) = elf.load(&mut bus) {
+        return TestResult::Error(err.to_string());
+    }
+
+    let outcome = Runtime::new()
+        .entry(elf.entry())
+        .tohost(tohost.addr)
+        .max_cycles(MAX_CYCLES)
+        .run(bus);
+    match outcome {
+        Ok(outcome) => match (outcome.reason, outcome.exit_code) {
+            (StopReason::Exit, Some(0)) => TestResult::Pass,
+            (StopReason::Exit, Some(n)) => TestResult::Fail(n),
+            (reason, _) => TestResult::Stopped(reason),
+        },
+        Err(err) => TestResult::Error(err.to_string()),
+    }
+}
+
+/// Run every test of the suite and print the pass/fail matrix.
+fn run_suite(suite: &str) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/riscv-tests")
+        .join(suite);
+    let mut tests: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap_or_else(|err| panic!("{}: {err}", dir.display()))
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        // Skip .dump listings generated alongside the executables.
+        .filter(|path| path.is_file() && path.extension().is_none())
+        .collect();
+    tests.sort();
+    assert!(
+        !tests.is_empty(),
+        "{suite}: no fixtures in {}",
+        dir.display()
+    );
+
+    let mut failures = Vec::new();
+    for path in &tests {
+        let name = path.file_name().unwrap().to_string_lossy();
+        let result = run(path);
+        eprintln!("{name:<24} {result}");
+        if !matches!(result, TestResult::Pass) {
+            failures.push(format!("{name}: {result}"));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{suite}: {} of {} tests failed\n{}",
+        failures.len(),
+        tests.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn rv32ui() {
+    run_suite("rv32ui");
+}
+
+#[test]
+fn rv32um() {
+    run_suite("rv32um");
+}
+
+#[test]
+fn rv32ua() {
+    run_suite("rv32ua");
+}
+
+#[test]
+fn rv32uf() {
+    run_suite("rv32uf");
+}
+
+#[test]
+fn rv32ud() {
+    run_suite("rv32ud");
+}
+
+#[test]
+fn rv32uc() {
+    run_suite("rv32uc");
+}
+
+#[test]
+fn rv32mi() {
+    run_suite("rv32mi");
+}
+
+#[test]
+fn rv32si() {
+    run_suite("rv32si");
+}
@@ -0,0 +1,66 @@
+import logging
+import os
+import shutil
+
+import riscof.utils as utils
+from riscof.pluginTemplate import pluginTemplate
+
+logger = logging.getLogger()
+
+
+class sail_cSim(pluginTemplate):
+    __model__ = "sail_c_simulator"
+    __version__ = "0.5.0"
+
+    def __init__(self, *args, **kwargs):
+        super().__init__(*args, **kwargs)
+        config = kwargs.get("config")
+        if config is None:
+            logger.error("Config node for sail_cSim missing.")
+            raise SystemExit(1)
+
+        path = config.get("PATH", "")
+        self.sail_exe = {
+            "32": os.path.join(path, "riscv_sim_RV32"),
+            "64": os.path.join(path, "riscv_sim_RV64"),
+        }
+        self.num_jobs = str(config.get("jobs", 1))
+        self.pluginpath = os.path.abspath(config["pluginpath"])
+
+    def initialise(self, suite, work_dir, archtest_env):
+        self.work_dir = work_dir
+        self.suite_dir = suite
+        self.compile_cmd = (
+            "riscv{1}-unknown-elf-gcc -march={0} -static -mcmodel=medany -fvisibility=hidden"
+            " -nostdlib -nostartfiles -g"
+            " -T " + self.pluginpath + "/env/link.ld"
+            " -I " + self.pluginpath + "/env/"
+            " -I " + archtest_env + " {2} -o {3} {4}"
+        )
+
+    def build(self, isa_yaml, platform_yaml):
+        ispec = utils.load_yaml(isa_yaml)["hart0"]
+        self.xlen = "64" if 64 in ispec["supported_xlen"] else "32"
+        self.compile_cmd += " -mabi=" + ("lp64 " if self.xlen == "64" else "ilp32 ")
+        if shutil.which(self.sail_exe[self.xlen]) is None:
+            logger.error(self.sail_exe[self.xlen] + ": executable not found. Set PATH in config.ini.")
+            raise SystemExit(1)
+
+    def runTests(self, testList):
+        make = utils.makeUtil(makefilePath=os.path.join(self.work_dir, "Makefile." + self.name[:-1]))
+        make.makeCommand = "make -k -j" + self.num_jobs
+
+        for testname in testList:
+            testentry = testList[testname]
+            test = testentry["test_path"]
+            test_dir = testentry["work_dir"]
+            elf = "ref.elf"
+            sig_file = os.path.join(test_dir, self.name[:-1] + ".signature")
+            compile_macros = " -D" + " -D".join(testentry["macros"])
+            cmd = self.compile_cmd.format(testentry["isa"].lower(), self.xlen, test, elf, compile_macros)
+            simcmd = "{0} --test-signature={1} {2} > sail.log 2>&1".format(
+                self.sail_exe[self.xlen], sig_file, elf
+            )
+            make.add_target("@cd {0}; {1}; {2};".format(test_dir, cmd, simcmd))
+
+        make.execute_all(self.work_dir)